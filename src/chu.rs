@@ -1,36 +1,196 @@
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap, HashSet},
+    fs,
     io::{self, Read},
+    path::Path,
 };
 
-use anyhow::Result;
-use scraper::{Html, Selector};
+use anyhow::{Context, Result, anyhow, bail};
+use encoding_rs::{Encoding, UTF_8};
+use scraper::{ElementRef, Html, Selector};
+use serde::{Serialize, Serializer, ser::SerializeMap, ser::SerializeSeq};
 
-pub fn run() -> Result<()> {
-    let mut buffer = String::new();
-    io::stdin().read_to_string(&mut buffer)?;
+/// Extracts a page read from a file, or from stdin when no file or `-` is
+/// given, and prints it in the given format. With selectors only what they
+/// match is extracted.
+pub fn run(input: Option<&Path>, format: Format, selectors: &[NamedSelector]) -> Result<()> {
+    let mut bytes = Vec::new();
+    match input {
+        Some(path) if path != Path::new("-") => {
+            bytes = fs::read(path).with_context(|| format!("could not read {}", path.display()))?;
+        }
+        _ => {
+            io::stdin().read_to_end(&mut bytes)?;
+        }
+    }
+    let buffer = decode(&bytes, None);
 
-    let document = extract_tables(&buffer);
-    if let Some(title) = document.title {
+    let document = if selectors.is_empty() {
+        extract_tables(&buffer)
+    } else {
+        extract_selected(&buffer, selectors)?
+    };
+    if format == Format::Text
+        && let Some(title) = &document.title
+    {
         println!("{}", title);
     }
-    let output = tables_to_string(document.tables);
+    let output = self::format(document, format)?;
     print!("{}", output);
     Ok(())
 }
 
+#[derive(Serialize)]
 pub struct Document {
     pub title: Option<String>,
-    pub tables: Vec<Vec<HashMap<String, String>>>,
+    pub tables: Vec<Table>,
+    /// The items of each `<ul>` and `<ol>`.
+    pub lists: Vec<Vec<String>>,
+    /// The terms and descriptions of each `<dl>`. The descriptions of a term
+    /// are joined with commas.
+    pub definitions: Vec<HashMap<String, String>>,
+    /// The paragraphs that follow each heading, up to the next heading.
+    pub sections: Vec<Section>,
+    /// The text matched by each named selector, when extracting with
+    /// [`extract_selected`].
+    pub fields: BTreeMap<String, Vec<String>>,
 }
 
-pub fn extract_tables(html: &str) -> Document {
+/// A table as maps from headers to cell text, one per row.
+#[derive(Debug, Default)]
+pub struct Table {
+    /// The headers, in the order of the columns of the table.
+    pub header: Vec<String>,
+    pub rows: Vec<HashMap<String, String>>,
+}
+
+/// A table is written as an array of row objects, with the cells of each row
+/// in column order.
+impl Serialize for Table {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut rows = serializer.serialize_seq(Some(self.rows.len()))?;
+        for row in &self.rows {
+            rows.serialize_element(&OrderedRow { header: &self.header, row })?;
+        }
+        rows.end()
+    }
+}
+
+struct OrderedRow<'a> {
+    header: &'a [String],
+    row: &'a HashMap<String, String>,
+}
+
+impl Serialize for OrderedRow<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.row.len()))?;
+        for column in self.header {
+            if let Some(value) = self.row.get(column) {
+                map.serialize_entry(column, value)?;
+            }
+        }
+        map.end()
+    }
+}
+
+#[derive(Serialize)]
+pub struct Section {
+    pub heading: String,
+    pub text: String,
+}
+
+/// The ways a document can be written out.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    /// `key: value` lines, as stored for search.
+    #[default]
+    Text,
+    /// The whole document as a JSON object, with each table an array of row
+    /// objects.
+    Json,
+    /// The tables as CSV, separated by blank lines.
+    Csv,
+    /// The whole document as Markdown, with tables as Markdown tables.
+    #[value(name = "md")]
+    Markdown,
+}
+
+impl Document {
+    /// Appends what was extracted from a following page of the same document.
+    pub fn merge(&mut self, other: Document) {
+        if self.title.is_none() {
+            self.title = other.title;
+        }
+        self.tables.extend(other.tables);
+        self.lists.extend(other.lists);
+        self.definitions.extend(other.definitions);
+        self.sections.extend(other.sections);
+        for (name, values) in other.fields {
+            self.fields.entry(name).or_default().extend(values);
+        }
+    }
+}
+
+/// Decodes a page to text with the charset of its `Content-Type` header, or
+/// else of its `<meta>` tags, or else UTF-8, so that pages in other encodings
+/// do not extract as garbled text. A byte order mark overrides both.
+pub fn decode(bytes: &[u8], content_type: Option<&str>) -> String {
+    let head = String::from_utf8_lossy(&bytes[..bytes.len().min(1024)]);
+    let encoding = content_type
+        .and_then(charset)
+        .or_else(|| charset(&head))
+        .and_then(|label| Encoding::for_label(label.as_bytes()))
+        .unwrap_or(UTF_8);
+    let (text, _, _) = encoding.decode(bytes);
+
+    text.into_owned()
+}
+
+/// The label after the first `charset=` of a header or markup.
+fn charset(text: &str) -> Option<&str> {
+    let start = text.to_ascii_lowercase().find("charset=")? + "charset=".len();
+    let label = text[start..].trim_start_matches(['"', '\'']);
+    let end = label
+        .find(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | ':' | '.')))
+        .unwrap_or(label.len());
+
+    Some(&label[..end]).filter(|label| !label.is_empty())
+}
+
+/// Link texts of anchors that lead to the next page of a list. A bare `»` or
+/// `›` often leads to the last page or separates breadcrumbs instead.
+const NEXT_TEXTS: &[&str] = &["next", "next page", "next »", "next ›", "next >"];
+
+/// The URL of the page that follows a page, from a `rel=next` link or anchor
+/// or else an anchor reading "next", resolved against the URL of the page.
+pub fn next_page(html: &str, page_url: &str) -> Option<String> {
     let document = Html::parse_document(html);
+    let rel_selector = Selector::parse("link[rel~=next][href], a[rel~=next][href]").unwrap();
+    let a_selector = Selector::parse("a[href]").unwrap();
+
+    let href = document
+        .select(&rel_selector)
+        .next()
+        .or_else(|| {
+            document.select(&a_selector).find(|anchor| {
+                NEXT_TEXTS.contains(&element_text(*anchor).to_lowercase().as_str())
+            })
+        })?
+        .value()
+        .attr("href")?;
 
+    let url = reqwest::Url::parse(page_url).ok()?.join(href).ok()?;
+    Some(url.to_string())
+}
+
+pub fn extract_tables(html: &str) -> Document {
+    extract(&Html::parse_document(html))
+}
+
+/// The content of a parsed page, as [`extract_tables`] gives it.
+pub fn extract(document: &Html) -> Document {
     let title_selector = Selector::parse("title").unwrap();
     let table_selector = Selector::parse("table").unwrap();
-    let tr_selector = Selector::parse("tr").unwrap();
-    let td_selector = Selector::parse("td, th").unwrap(); // Select both td and th for cells
 
     let title = document
         .select(&title_selector)
@@ -38,58 +198,473 @@ pub fn extract_tables(html: &str) -> Document {
         .and_then(|element| element.text().next())
         .map(|text| text.trim().to_string());
 
-    let mut all_tables: Vec<Vec<HashMap<String, String>>> = Vec::new();
+    // nested tables are selected too, and processed as tables of their own
+    let mut all_tables: Vec<Table> = Vec::new();
     for table_element in document.select(&table_selector) {
-        let mut header_cells: Option<Vec<String>> = None;
-        let mut current_table_processed_rows: Vec<HashMap<String, String>> = Vec::new();
+        let table = table_rows(table_element);
+        if !table.rows.is_empty() {
+            all_tables.push(table);
+        }
+    }
 
-        for row_element in table_element.select(&tr_selector) {
-            let mut row_cells: Vec<String> = Vec::new();
-            for cell_element in row_element.select(&td_selector) {
-                row_cells.push(cell_element.text().collect::<String>().trim().to_string());
+    Document {
+        title,
+        tables: all_tables,
+        lists: extract_lists(document),
+        definitions: extract_definitions(document),
+        sections: extract_sections(document),
+        fields: BTreeMap::new(),
+    }
+}
+
+/// A CSS selector whose matches are extracted under a name.
+#[derive(Clone, Debug)]
+pub struct NamedSelector {
+    pub name: String,
+    pub selector: String,
+}
+
+impl NamedSelector {
+    /// Parses a `name = selector` line, checking that the selector is valid.
+    pub fn parse(line: &str) -> Result<Self> {
+        let Some((name, selector)) = line.split_once('=') else {
+            bail!("expected `name = selector` but found `{}`", line);
+        };
+        let named = NamedSelector {
+            name: name.trim().to_string(),
+            selector: selector.trim().to_string(),
+        };
+        named.compile()?;
+
+        Ok(named)
+    }
+
+    fn compile(&self) -> Result<Selector> {
+        Selector::parse(&self.selector)
+            .map_err(|e| anyhow!("invalid selector `{}` for {}: {}", self.selector, self.name, e))
+    }
+}
+
+/// Extracts only what the given selectors match, instead of every table, so
+/// that noisy pages yield clean documents.
+///
+/// Matched tables are extracted as tables and anything else as text under the
+/// name of its selector. A selector named `title` replaces the page title.
+pub fn extract_selected(html: &str, selectors: &[NamedSelector]) -> Result<Document> {
+    let document = Html::parse_document(html);
+
+    let mut extracted = Document {
+        title: None,
+        tables: Vec::new(),
+        lists: Vec::new(),
+        definitions: Vec::new(),
+        sections: Vec::new(),
+        fields: BTreeMap::new(),
+    };
+    for named in selectors {
+        let selector = named.compile()?;
+        for element in document.select(&selector) {
+            if element.value().name() == "table" {
+                let table = table_rows(element);
+                if !table.rows.is_empty() {
+                    extracted.tables.push(table);
+                }
+                continue;
+            }
+            let text = element_text(element);
+            if text.is_empty() {
+                continue;
             }
+            if named.name == "title" && extracted.title.is_none() {
+                extracted.title = Some(text);
+            } else {
+                extracted.fields.entry(named.name.clone()).or_default().push(text);
+            }
+        }
+    }
+
+    Ok(extracted)
+}
+
+/// Whether a table holds its headers in the first column rather than the
+/// first row, as attribute/value tables such as infoboxes do: every row starts
+/// with a `<th>` and the first row is not all headers.
+fn is_vertical(grid: &[Vec<Cell>]) -> bool {
+    let starts_with_header = grid
+        .iter()
+        .all(|row| row.len() > 1 && row[0].header);
+    let first_row_headers = grid
+        .first()
+        .is_some_and(|row| row.iter().all(|cell| cell.header));
+
+    starts_with_header && !first_row_headers
+}
+
+/// Tokens in the class or id of elements that hold navigation or other
+/// boilerplate rather than content.
+const BOILERPLATE: &[&str] = &[
+    "nav", "menu", "header", "footer", "sidebar", "comment", "breadcrumb", "banner", "advert",
+    "share", "related", "cookie",
+];
+
+fn is_boilerplate(element: ElementRef) -> bool {
+    let value = element.value();
+    if matches!(
+        value.name(),
+        "nav" | "header" | "footer" | "aside" | "form" | "script" | "style"
+    ) {
+        return true;
+    }
+    let names = value.attr("class").into_iter().chain(value.attr("id"));
+    names
+        .flat_map(|names| names.split(|c: char| c.is_whitespace() || c == '-' || c == '_'))
+        .any(|name| BOILERPLATE.contains(&name.to_lowercase().as_str()))
+}
+
+fn in_boilerplate(element: ElementRef) -> bool {
+    is_boilerplate(element) || element.ancestors().filter_map(ElementRef::wrap).any(is_boilerplate)
+}
+
+/// Extracts the main text of a page, leaving out navigation, headers, footers
+/// and other boilerplate, so that searches are not matched by menus.
+///
+/// Like readability, each paragraph scores its parent and, by half, its
+/// grandparent by its length and commas, and the best scored element after
+/// discounting its link text is taken as the article. Its headings and
+/// paragraphs are returned one per line, or nothing when the page has no
+/// paragraphs.
+pub fn main_content(html: &str) -> Option<String> {
+    let document = Html::parse_document(html);
+    let p_selector = Selector::parse("p").unwrap();
+    let link_selector = Selector::parse("a").unwrap();
+    let text_selector = Selector::parse("h1, h2, h3, h4, h5, h6, p, pre, blockquote").unwrap();
+
+    let mut scores = HashMap::new();
+    for paragraph in document.select(&p_selector) {
+        let text = element_text(paragraph);
+        if text.chars().count() < 25 || in_boilerplate(paragraph) {
+            continue;
+        }
+        let score = 1.0 + text.matches(',').count() as f64 + (text.len() / 100).min(3) as f64;
+        let ancestors = paragraph.ancestors().filter_map(ElementRef::wrap);
+        for (ancestor, share) in ancestors.take(2).zip([1.0, 0.5]) {
+            scores
+                .entry(ancestor.id())
+                .or_insert((ancestor, 0.0))
+                .1 += score * share;
+        }
+    }
+
+    let (article, _) = scores
+        .into_values()
+        .map(|(candidate, score)| {
+            let text_length = element_text(candidate).len().max(1) as f64;
+            let link_length: usize = candidate
+                .select(&link_selector)
+                .map(|link| element_text(link).len())
+                .sum();
+            let bonus = match candidate.value().name() {
+                "article" | "main" => 1.25,
+                _ => 1.0,
+            };
+            (candidate, score * bonus * (1.0 - link_length as f64 / text_length))
+        })
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))?;
+
+    let lines: Vec<String> = article
+        .select(&text_selector)
+        .filter(|element| !in_boilerplate(*element))
+        .map(element_text)
+        .filter(|text| !text.is_empty())
+        .collect();
+
+    Some(lines.join("\n"))
+}
+
+/// The ISO 639-3 code of the language a text is most likely written in, or
+/// nothing when it cannot be told reliably, as with short or mixed texts.
+pub fn language(text: &str) -> Option<&'static str> {
+    whatlang::detect(text)
+        .filter(|info| info.is_reliable())
+        .map(|info| info.lang().code())
+}
+
+/// The rows of a table as maps from headers to cell text.
+///
+/// Headers are taken from the first row, or from the first column when the
+/// table is vertical, in which case each further column is one map.
+fn table_rows(table_element: ElementRef) -> Table {
+    let grid = table_grid(table_element);
+    if is_vertical(&grid) {
+        let columns = grid.iter().map(Vec::len).max().unwrap_or_default();
+        let header = unique(grid.iter().map(|row| row[0].text.clone()));
+        let rows = (1..columns)
+            .map(|column| {
+                grid.iter()
+                    .filter_map(|row| {
+                        let value = row.get(column)?;
+                        Some((row[0].text.clone(), value.text.clone()))
+                    })
+                    .collect::<HashMap<String, String>>()
+            })
+            .filter(|row_map| !row_map.is_empty())
+            .collect();
+        return Table { header, rows };
+    }
+
+    let mut header_cells: Option<Vec<String>> = None;
+    let mut rows: Vec<HashMap<String, String>> = Vec::new();
 
-            if row_cells.is_empty() {
-                continue; // Skip empty rows
+    for row in grid {
+        let row_cells: Vec<String> = row.into_iter().map(|cell| cell.text).collect();
+        if let Some(unwrapped_header) = &header_cells {
+            let mut row_map: HashMap<String, String> = HashMap::new();
+            for (index, cell_value) in row_cells.into_iter().enumerate() {
+                if index < unwrapped_header.len() {
+                    row_map.insert(unwrapped_header[index].clone(), cell_value);
+                }
+            }
+            if !row_map.is_empty() {
+                rows.push(row_map);
             }
+        } else {
+            header_cells = Some(row_cells);
+        }
+    }
 
-            if header_cells.is_none() {
-                header_cells = Some(row_cells);
-            } else {
-                let unwrapped_header = header_cells.as_ref().unwrap();
-                let mut row_map: HashMap<String, String> = HashMap::new();
-                for (index, cell_value) in row_cells.into_iter().enumerate() {
-                    if index < unwrapped_header.len() {
-                        row_map.insert(
-                            unwrapped_header[index].clone(),
-                            remove_redundant_spaces(&cell_value),
-                        );
+    Table {
+        header: unique(header_cells.into_iter().flatten()),
+        rows,
+    }
+}
+
+/// Headers in the order they first appear, as a repeated header names one
+/// column of the rows.
+fn unique(headers: impl Iterator<Item = String>) -> Vec<String> {
+    let mut seen = HashSet::new();
+    headers.filter(|header| seen.insert(header.clone())).collect()
+}
+
+fn nearest_table<'a>(mut ancestors: impl Iterator<Item = ElementRef<'a>>) -> Option<ElementRef<'a>> {
+    ancestors.find(|element| element.value().name() == "table")
+}
+
+/// The most columns a cell spans, as browsers clamp `colspan`.
+const MAX_COLSPAN: usize = 1000;
+/// The most rows a cell spans, as browsers clamp `rowspan`.
+const MAX_ROWSPAN: usize = 65534;
+
+/// The number of columns or rows a cell spans, at most `max`.
+fn span(cell: ElementRef, attribute: &str, max: usize) -> usize {
+    cell.value()
+        .attr(attribute)
+        .and_then(|value| value.trim().parse::<usize>().ok())
+        .filter(|span| *span > 0)
+        .map_or(1, |span| span.min(max))
+}
+
+/// The text of a cell, leaving out the text of tables nested in it.
+fn cell_text(cell: ElementRef, table: ElementRef) -> String {
+    let text: String = cell
+        .descendants()
+        .filter(|node| nearest_table(node.ancestors().filter_map(ElementRef::wrap)) == Some(table))
+        .filter_map(|node| node.value().as_text().map(|text| text.to_string()))
+        .collect();
+
+    remove_redundant_spaces(&text)
+}
+
+#[derive(Clone)]
+struct Cell {
+    text: String,
+    /// Whether the cell is a `<th>`.
+    header: bool,
+}
+
+/// The cells of the rows of a table, with cells that span several columns or
+/// rows repeated in each of them so that values line up with headers. Rows of
+/// nested tables are left out.
+fn table_grid(table: ElementRef) -> Vec<Vec<Cell>> {
+    let tr_selector = Selector::parse("tr").unwrap();
+
+    let mut grid = Vec::new();
+    // cells of earlier rows spanning down, by column, with the rows still spanned
+    let mut spans: Vec<Option<(Cell, usize)>> = Vec::new();
+    for row_element in table.select(&tr_selector) {
+        if nearest_table(row_element.ancestors().filter_map(ElementRef::wrap)) != Some(table) {
+            continue;
+        }
+        let mut cells = row_element
+            .children()
+            .filter_map(ElementRef::wrap)
+            .filter(|cell| matches!(cell.value().name(), "td" | "th"));
+
+        let mut row_cells = Vec::new();
+        loop {
+            let column = row_cells.len();
+            if let Some((cell, remaining)) = spans.get_mut(column).and_then(Option::as_mut) {
+                row_cells.push(cell.clone());
+                *remaining -= 1;
+                if *remaining == 0 {
+                    spans[column] = None;
+                }
+                continue;
+            }
+            let Some(cell_element) = cells.next() else {
+                // a short row is still spanned by cells further right, in
+                // their columns
+                if spans.iter().skip(column).any(Option::is_some) {
+                    row_cells.push(Cell {
+                        text: String::new(),
+                        header: false,
+                    });
+                    continue;
+                }
+                break;
+            };
+            let cell = Cell {
+                text: cell_text(cell_element, table),
+                header: cell_element.value().name() == "th",
+            };
+            let rowspan = span(cell_element, "rowspan", MAX_ROWSPAN);
+            for _ in 0..span(cell_element, "colspan", MAX_COLSPAN) {
+                if rowspan > 1 {
+                    let column = row_cells.len();
+                    if spans.len() <= column {
+                        spans.resize(column + 1, None);
                     }
+                    spans[column] = Some((cell.clone(), rowspan - 1));
                 }
-                if !row_map.is_empty() {
-                    current_table_processed_rows.push(row_map);
+                row_cells.push(cell.clone());
+            }
+        }
+        if !row_cells.is_empty() {
+            grid.push(row_cells);
+        }
+    }
+
+    grid
+}
+
+fn element_text(element: ElementRef) -> String {
+    remove_redundant_spaces(&element.text().collect::<String>())
+}
+
+fn extract_lists(document: &Html) -> Vec<Vec<String>> {
+    let list_selector = Selector::parse("ul, ol").unwrap();
+
+    let mut lists = Vec::new();
+    for list_element in document.select(&list_selector) {
+        // only the items of this list, not those of lists nested in them
+        let items: Vec<String> = list_element
+            .children()
+            .filter_map(ElementRef::wrap)
+            .filter(|child| child.value().name() == "li")
+            .map(element_text)
+            .filter(|item| !item.is_empty())
+            .collect();
+        if !items.is_empty() {
+            lists.push(items);
+        }
+    }
+
+    lists
+}
+
+fn extract_definitions(document: &Html) -> Vec<HashMap<String, String>> {
+    let dl_selector = Selector::parse("dl").unwrap();
+
+    let mut definitions = Vec::new();
+    for dl_element in document.select(&dl_selector) {
+        let mut map: HashMap<String, String> = HashMap::new();
+        let mut term: Option<String> = None;
+        for child in dl_element.children().filter_map(ElementRef::wrap) {
+            match child.value().name() {
+                "dt" => term = Some(element_text(child)),
+                "dd" => {
+                    let Some(term) = &term else {
+                        continue; // Skip descriptions without a term
+                    };
+                    let description = element_text(child);
+                    map.entry(term.clone())
+                        .and_modify(|value| {
+                            value.push_str(", ");
+                            value.push_str(&description);
+                        })
+                        .or_insert(description);
                 }
+                _ => {}
             }
         }
-        if !current_table_processed_rows.is_empty() {
-            all_tables.push(current_table_processed_rows);
+        if !map.is_empty() {
+            definitions.push(map);
         }
     }
 
-    Document {
-        title,
-        tables: all_tables,
+    definitions
+}
+
+fn extract_sections(document: &Html) -> Vec<Section> {
+    let selector = Selector::parse("h1, h2, h3, h4, h5, h6, p").unwrap();
+
+    let mut sections: Vec<Section> = Vec::new();
+    for element in document.select(&selector) {
+        let text = element_text(element);
+        if element.value().name() != "p" {
+            sections.push(Section {
+                heading: text,
+                text: String::new(),
+            });
+        } else if let Some(section) = sections.last_mut() {
+            if !section.text.is_empty() {
+                section.text.push('\n');
+            }
+            section.text.push_str(&text);
+        }
     }
+    sections.retain(|section| !section.text.is_empty());
+
+    sections
 }
 
-pub fn tables_to_string(tables: Vec<Vec<HashMap<String, String>>>) -> String {
+/// Renders everything chu extracted from a page as `key: value` text.
+pub fn document_to_string(document: Document) -> String {
+    let mut text = String::new();
+    for (name, values) in document.fields {
+        for value in values {
+            text.push_str(&format!("{}: {}\n", name, value));
+        }
+    }
+    text.push_str(&tables_to_string(document.tables));
+    for list in document.lists {
+        for item in list {
+            text.push_str(&format!("- {}\n", item));
+        }
+        text.push_str("---\n");
+    }
+    for definition in document.definitions {
+        for (term, description) in definition {
+            text.push_str(&format!("{}: {}\n", term, description));
+        }
+        text.push_str("---\n");
+    }
+    for section in document.sections {
+        text.push_str(&format!("{}: {}\n", section.heading, section.text));
+    }
+
+    text
+}
+
+pub fn tables_to_string(tables: Vec<Table>) -> String {
     let mut text = String::new();
     for table in tables {
-        for row in table {
-            for (key, value) in row {
-                text.push_str(&format!("{}: {}\n", key, value));
+        for row in &table.rows {
+            for column in &table.header {
+                if let Some(value) = row.get(column) {
+                    text.push_str(&format!("{}: {}\n", column, value));
+                }
             }
-            text.push_str("\n");
+            text.push('\n');
         }
         text.push_str("---\n");
     }
@@ -100,3 +675,91 @@ pub fn tables_to_string(tables: Vec<Vec<HashMap<String, String>>>) -> String {
 fn remove_redundant_spaces(s: &str) -> String {
     s.split_whitespace().collect::<Vec<&str>>().join(" ")
 }
+
+/// Writes a document out in the given format.
+pub fn format(document: Document, format: Format) -> Result<String> {
+    match format {
+        Format::Text => Ok(document_to_string(document)),
+        Format::Json => Ok(serde_json::to_string_pretty(&document)?),
+        Format::Csv => tables_to_csv(&document.tables),
+        Format::Markdown => Ok(document_to_markdown(&document)),
+    }
+}
+
+fn tables_to_csv(tables: &[Table]) -> Result<String> {
+    let mut text = String::new();
+    for (index, table) in tables.iter().enumerate() {
+        if index > 0 {
+            text.push('\n');
+        }
+        let columns = &table.header;
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        writer.write_record(columns)?;
+        for row in &table.rows {
+            writer.write_record(
+                columns
+                    .iter()
+                    .map(|column| row.get(column).map_or("", String::as_str)),
+            )?;
+        }
+        text.push_str(&String::from_utf8(writer.into_inner()?)?);
+    }
+
+    Ok(text)
+}
+
+fn markdown_cell(text: &str) -> String {
+    text.replace('|', "\\|")
+}
+
+fn document_to_markdown(document: &Document) -> String {
+    let mut blocks = Vec::new();
+    if let Some(title) = &document.title {
+        blocks.push(format!("# {}\n", title));
+    }
+    if !document.fields.is_empty() {
+        let mut block = String::new();
+        for (name, values) in &document.fields {
+            for value in values {
+                block.push_str(&format!("- **{}**: {}\n", name, value));
+            }
+        }
+        blocks.push(block);
+    }
+    for table in &document.tables {
+        let columns = &table.header;
+        let mut block = format!(
+            "| {} |\n|{}\n",
+            columns
+                .iter()
+                .map(|column| markdown_cell(column))
+                .collect::<Vec<_>>()
+                .join(" | "),
+            " --- |".repeat(columns.len())
+        );
+        for row in &table.rows {
+            let cells: Vec<String> = columns
+                .iter()
+                .map(|column| markdown_cell(row.get(column).map_or("", String::as_str)))
+                .collect();
+            block.push_str(&format!("| {} |\n", cells.join(" | ")));
+        }
+        blocks.push(block);
+    }
+    for list in &document.lists {
+        blocks.push(list.iter().map(|item| format!("- {}\n", item)).collect());
+    }
+    for definition in &document.definitions {
+        blocks.push(
+            definition
+                .iter()
+                .map(|(term, description)| format!("- **{}**: {}\n", term, description))
+                .collect(),
+        );
+    }
+    for section in &document.sections {
+        blocks.push(format!("## {}\n\n{}\n", section.heading, section.text));
+    }
+
+    blocks.join("\n")
+}
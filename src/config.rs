@@ -0,0 +1,81 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+use crate::serve::{embedding::Embedder, pipeline::Pipeline};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Defaults for command line options, read from `~/.config/pika/config.toml`.
+///
+/// Options given on the command line or in the environment take precedence.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// The database used when no `--db` is given.
+    pub db: Option<PathBuf>,
+    #[serde(default)]
+    pub serve: ServeConfig,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ServeConfig {
+    /// The port to serve on.
+    pub port: Option<u16>,
+    /// URLs, such as ntfy topics, that crawl events are posted to as JSON.
+    #[serde(default)]
+    pub webhooks: Vec<String>,
+    /// The endpoint crawled documents are embedded with for semantic search.
+    pub embedding: Option<Embedder>,
+    /// Sources imported with mappings after they are crawled.
+    #[serde(default, rename = "pipeline")]
+    pub pipelines: Vec<Pipeline>,
+    /// A directory whose `templates` and `static` directories override the
+    /// built-in templates and static files of the same name.
+    pub theme: Option<PathBuf>,
+}
+
+impl Config {
+    /// The configuration file, under `$XDG_CONFIG_HOME` or `~/.config`.
+    pub fn path() -> Option<PathBuf> {
+        let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+
+        Some(config_dir.join("pika/config.toml"))
+    }
+
+    /// Reads the configuration file, if there is one.
+    pub fn load() -> Result<Self> {
+        match Self::path() {
+            Some(path) if path.exists() => Self::read(&path),
+            _ => Ok(Config::default()),
+        }
+    }
+
+    pub fn read(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("could not read {}", path.display()))?;
+        let mut config: Config = toml::from_str(&text)
+            .with_context(|| format!("could not parse {}", path.display()))?;
+        // relative paths are relative to the configuration file
+        if let Some(dir) = path.parent() {
+            if let Some(db) = &config.db
+                && db.is_relative()
+            {
+                config.db = Some(dir.join(db));
+            }
+            if let Some(theme) = &config.serve.theme {
+                config.serve.theme = Some(dir.join(theme));
+            }
+            for pipeline in &mut config.serve.pipelines {
+                pipeline.data = dir.join(&pipeline.data);
+                pipeline.mapping = dir.join(&pipeline.mapping);
+            }
+        }
+
+        Ok(config)
+    }
+}
@@ -0,0 +1,108 @@
+use anyhow::{Context, Result, bail};
+use aykroyd::rusqlite::{Client, Transaction};
+use std::path::Path;
+use tracing::{info, instrument};
+
+use crate::store::{
+    audit::AuditInsert,
+    entity::{
+        EntityAliasesDelete, EntityDelete, EntityEditsDelete, PropertiesForEntityDelete,
+        PropertyForEntityDelete,
+    },
+    import::ImportEntityDelete,
+};
+
+/// Removes the values of one property of an entity, or with `all` the entity
+/// and all its values.
+///
+/// The property is given as `schema.name`, or as `name` for a property of the
+/// entity's own schema.
+#[instrument(skip(db_path))]
+pub fn run(
+    db_path: &Path,
+    schema: &str,
+    id: &str,
+    property: Option<&str>,
+    all: bool,
+) -> Result<()> {
+    let mut db = Client::open(db_path)?;
+    let mut txn = db.transaction()?;
+    delete(&mut txn, schema, id, property, all, "cli")?;
+    txn.commit()?;
+
+    Ok(())
+}
+
+/// Removes values as [`run`] does, in a transaction, recording the deletion
+/// as done by `actor`.
+pub fn delete(
+    txn: &mut Transaction,
+    schema: &str,
+    id: &str,
+    property: Option<&str>,
+    all: bool,
+    actor: &str,
+) -> Result<()> {
+    match (property, all) {
+        (Some(property), false) => {
+            let (property_schema, property_name) =
+                property.split_once('.').unwrap_or((schema, property));
+            let deleted = txn
+                .execute(&PropertyForEntityDelete {
+                    schema,
+                    id,
+                    property_schema,
+                    name: property_name,
+                })
+                .with_context(|| {
+                    format!(
+                        "could not delete {}.{} of {}/{}",
+                        property_schema, property_name, schema, id
+                    )
+                })?;
+            if deleted == 0 {
+                bail!(
+                    "{}/{} has no values for {}.{}",
+                    schema,
+                    id,
+                    property_schema,
+                    property_name
+                );
+            }
+            info!(
+                "deleted {} values of {}.{} from {}/{}",
+                deleted, property_schema, property_name, schema, id
+            );
+        }
+        (None, true) => {
+            let deleted = txn
+                .execute(&PropertiesForEntityDelete { schema, id })
+                .with_context(|| format!("could not delete properties of {}/{}", schema, id))?;
+            txn.execute(&ImportEntityDelete {
+                schema_name: schema,
+                entity_id: id,
+            })
+            .with_context(|| format!("could not forget import of {}/{}", schema, id))?;
+            // an entity deleted and written again starts without edits or aliases
+            txn.execute(&EntityEditsDelete { schema, id })?;
+            txn.execute(&EntityAliasesDelete { schema, id })?;
+            if txn.execute(&EntityDelete {
+                schema_name: schema,
+                id,
+            })? == 0
+            {
+                bail!("no entity {}/{}", schema, id);
+            }
+            info!("deleted {}/{} and its {} values", schema, id, deleted);
+        }
+        _ => bail!("give either a property or --all"),
+    }
+    txn.execute(&AuditInsert {
+        actor,
+        action: "delete",
+        target: &format!("{}/{}", schema, id),
+        detail: property,
+    })?;
+
+    Ok(())
+}
@@ -1,54 +1,602 @@
 use crate::{
-    mapper, parsedir,
-    store::entity::{InsertEntityStatement, PropertyForEntitySchemaInsert},
+    input, mapper, parsedir,
+    progress::{NoProgress, Progress},
+    store::{
+        audit::AuditInsert,
+        entity::{
+            EntityAliasesQuery, EntityDelete, InsertEntityStatement, PropertiesForEntityDelete,
+            PropertiesForImportFileDelete, PropertyForEntityDelete, PropertyForEntitySchemaInsert,
+        },
+        import::{
+            ImportEntitiesForFileDelete, ImportEntitiesQuery, ImportEntityDelete, ImportFileDelete,
+            ImportFilesQuery, InsertImportEntityStatement, UpsertImportFileStatement,
+        },
+    },
+    validate::Validator,
 };
 use anyhow::{Context, Result};
 use aykroyd::rusqlite::Client;
 use jaq_json::Val;
-use mapper::Mapper;
-use std::path::{Path, PathBuf};
+use mapper::{Mapper, Mapping, Property};
+use rayon::prelude::*;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fmt,
+    fs::File,
+    io::{self, BufReader},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use tracing::{debug, instrument, warn};
+
+#[derive(Default)]
+pub struct Options {
+    /// Warn about and skip properties that do not match the schema, and warn
+    /// about entities missing required properties, instead of failing.
+    pub lenient: bool,
+    /// Run the mappers and print a summary without writing to the database.
+    pub dry_run: bool,
+    /// Import data files even if they have not changed since the last import.
+    pub force: bool,
+    /// Remove imported entities that are no longer in the data files.
+    pub sync: bool,
+    /// How data files are read.
+    pub input: input::Options,
+    /// Told of the data files of each schema as they are imported.
+    pub progress: Option<Arc<dyn Progress>>,
+    /// Import only the mapping of this schema.
+    pub schema: Option<String>,
+    /// What the import is recorded as in the audit log, `cli` when not set.
+    pub actor: Option<&'static str>,
+    /// Where the imported values are recorded as coming from, the data file
+    /// they were read from when not set.
+    pub provenance: Option<String>,
+    /// Write the entities without recording the data files they came from,
+    /// so that imports of other data directories with `sync` leave them be.
+    pub untracked: bool,
+}
+
+/// What an import did, or would do in a dry run.
+#[derive(Default)]
+pub struct Summary {
+    entities: BTreeMap<String, usize>,
+    properties: BTreeMap<String, usize>,
+    removed: BTreeMap<String, usize>,
+    errors: Vec<String>,
+    unchanged: usize,
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Entities to create:")?;
+        for (schema, count) in &self.entities {
+            writeln!(f, "  {}: {}", schema, count)?;
+        }
+        writeln!(f, "Properties per schema:")?;
+        for (schema, count) in &self.properties {
+            writeln!(f, "  {}: {}", schema, count)?;
+        }
+        writeln!(f, "Entities to remove:")?;
+        for (schema, count) in &self.removed {
+            writeln!(f, "  {}: {}", schema, count)?;
+        }
+        writeln!(f, "Unchanged files skipped: {}", self.unchanged)?;
+        writeln!(f, "Validation errors: {}", self.errors.len())?;
+        for error in &self.errors {
+            writeln!(f, "  {}", error)?;
+        }
+        Ok(())
+    }
+}
+
+/// Imports the data files through their mappings, printing a summary of what
+/// would be imported in a dry run.
+pub fn run(db_path: &Path, data_path: PathBuf, mapping_path: PathBuf, options: &Options) -> Result<()> {
+    let summary = execute(db_path, data_path, mapping_path, options)?;
+    if options.dry_run {
+        print!("{}", summary);
+    }
+
+    Ok(())
+}
 
-pub fn run(db_path: &Path, data_path: PathBuf, mapping_path: PathBuf) -> Result<()> {
+/// Imports the data files through their mappings, returning what was
+/// imported, or would be in a dry run.
+#[instrument(name = "import", skip_all, fields(data = %data_path.display(), sync = options.sync))]
+pub fn execute(
+    db_path: &Path,
+    data_path: PathBuf,
+    mapping_path: PathBuf,
+    options: &Options,
+) -> Result<Summary> {
     let mut db = Client::open(db_path)?;
+    let validator = Validator::load(&mut db).context("could not load schemas")?;
+    let progress = options.progress.as_deref().unwrap_or(&NoProgress);
+    let mut importer = Importer {
+        db,
+        dry_run: options.dry_run,
+        provenance: options.provenance.clone(),
+        untracked: options.untracked,
+        summary: Summary::default(),
+    };
 
-    for result in parsedir::parse(&mapping_path, |s| toml::from_str(s))? {
-        let (schema_name, mapping) = result?;
+    let mappings = parsedir::parse(&mapping_path, |s| {
+        toml::from_str::<Mapping>(s).map(|mapping| (s.to_string(), mapping))
+    })?;
+    for result in mappings {
+        let (schema_name, (source, mut mapping)) = result?;
+        if options.schema.as_ref().is_some_and(|schema| *schema != schema_name) {
+            continue;
+        }
+        mapping
+            .resolve_includes(&mapping_path)
+            .with_context(|| format!("could not read includes of mapping {}", schema_name))?;
+        // a data file is imported again when what it is mapped by changes
+        let mapping_hash = Sha256::new()
+            .chain_update(&source)
+            .chain_update(&mapping.defs)
+            .chain_update(format!("{:?}", options.input));
 
-        let mapper = Mapper::new(mapping)
-            .with_context(|| format!("could not create mapper for schema {}", schema_name))?;
+        let schema_data_path = data_path.join(mapping.data.as_deref().unwrap_or(&schema_name));
+        let mapper = Mapper::new(mapping).with_context(|| {
+            format!(
+                "could not create mapper for schema {} from {}",
+                schema_name,
+                mapping_path.join(format!("{}.toml", schema_name)).display()
+            )
+        })?;
+        let hashes = importer.hashes(&schema_name)?;
+        let imported = importer.imported_entities(&schema_name)?;
+        let aliases = importer.aliases(&schema_name)?;
+        let file_mapper = FileMapper {
+            schema_name: &schema_name,
+            data_path: &data_path,
+            hashes: &hashes,
+            mapping_hash,
+            mapper: &mapper,
+            validator: &validator,
+            options,
+        };
 
-        // iterate over data for each schema
-        for result in parsedir::parse(&data_path.join(&schema_name), |s| jaq_json::toml::parse(s))?
-        {
-            let (id, data): (String, Val) = result?;
-            db.execute(&InsertEntityStatement {
-                schema_name: &schema_name,
-                id: &id,
+        let files = parsedir::files(&schema_data_path)?;
+        progress.start(&schema_name, Some(files.len() as u64));
+        let mut present = HashSet::new();
+        let mut write = |path: &Path, batch: Option<Batch>| -> Result<()> {
+            // a file is done with its last batch, which carries its hash
+            if batch.as_ref().is_none_or(|batch| batch.hash.is_some()) {
+                progress.advance(1);
+            }
+            match batch {
+                Some(mut batch) => {
+                    // the data of a merged entity goes to the one it was merged
+                    // into, which it keeps from being removed
+                    for (id, _) in &mut batch.entities {
+                        if let Some(winner) = aliases.get(id) {
+                            *id = winner.clone();
+                        }
+                    }
+                    present.extend(batch.entities.iter().map(|(id, _)| id.clone()));
+                    importer.write(&schema_name, batch)?;
+                }
+                None => {
+                    importer.summary.unchanged += 1;
+                    if let Some(ids) = imported.get(&relative_path(&data_path, path)) {
+                        present.extend(ids.iter().cloned());
+                    }
+                }
+            }
+            Ok(())
+        };
+
+        // map the data files of each schema in parallel, a chunk of a few per
+        // thread at a time so that memory stays bounded, and write each chunk
+        // once mapped in the order of the sorted paths; streamed files are
+        // written after the others, in the same order
+        let (streamed, whole): (Vec<_>, Vec<_>) = files
+            .iter()
+            .partition(|path| input::Format::from_path(path).is_some_and(input::Format::streams));
+        for chunk in whole.chunks(rayon::current_num_threads() * 4) {
+            let batches = chunk
+                .par_iter()
+                .map(|path| file_mapper.file(path))
+                .collect::<Result<Vec<_>>>()?;
+            for (path, batch) in chunk.iter().zip(batches) {
+                write(path, batch)?;
+            }
+        }
+        // files that can be read one record at a time are imported in batches
+        // of records, so that they need not fit in memory
+        for path in streamed {
+            file_mapper.stream(path, |batch| write(path, batch))?;
+        }
+        progress.finish();
+
+        if options.sync {
+            let paths = files
+                .iter()
+                .map(|path| relative_path(&data_path, path))
+                .collect::<HashSet<_>>();
+            let removed_paths = imported
+                .keys()
+                .filter(|path| !paths.contains(*path))
+                .collect::<Vec<_>>();
+            let removed_ids = imported
+                .values()
+                .flatten()
+                .filter(|id| !present.contains(*id))
+                .collect::<BTreeSet<_>>();
+            importer.remove(&schema_name, removed_paths, removed_ids)?;
+        }
+    }
+
+    if !options.dry_run {
+        importer.db.execute(&AuditInsert {
+            actor: options.actor.unwrap_or("cli"),
+            action: "import",
+            target: &data_path.display().to_string(),
+            detail: Some(&mapping_path.display().to_string()),
+        })?;
+    }
+
+    Ok(importer.summary)
+}
+
+/// The path of a data file relative to the data directory, as recorded in the
+/// database.
+fn relative_path(data_path: &Path, path: &Path) -> String {
+    path.strip_prefix(data_path)
+        .unwrap_or(path)
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// How many records of a streamed data file are written per transaction.
+const RECORDS_PER_BATCH: usize = 1000;
+
+/// The entities mapped from a data file, or from part of a streamed one.
+#[derive(Default)]
+struct Batch {
+    /// The path of the data file, relative to the data directory.
+    path: String,
+    /// Whether this is the first batch of the file, which replaces what was
+    /// imported from the file before.
+    first: bool,
+    /// The content hash of the file, set on its last batch.
+    hash: Option<String>,
+    entities: Vec<(String, Vec<Property>)>,
+    errors: Vec<String>,
+}
+
+/// Maps data files into batches. Shared between the import threads.
+struct FileMapper<'a> {
+    schema_name: &'a str,
+    data_path: &'a Path,
+    /// The content hashes of the files imported before, by relative path.
+    hashes: &'a HashMap<String, String>,
+    /// The hash of the mapping, its definitions and the input options, which
+    /// the content hash of each file starts from.
+    mapping_hash: Sha256,
+    mapper: &'a Mapper,
+    validator: &'a Validator,
+    options: &'a Options,
+}
+
+impl FileMapper<'_> {
+    /// Maps the records of a data file, or returns `None` if the file has not
+    /// changed since it was last imported.
+    fn file(&self, path: &Path) -> Result<Option<Batch>> {
+        let contents = input::read(path)?;
+        let relative_path = relative_path(self.data_path, path);
+        let hash = format!(
+            "{:x}",
+            self.mapping_hash.clone().chain_update(contents.as_bytes()).finalize()
+        );
+        if !self.options.force && self.hashes.get(&relative_path) == Some(&hash) {
+            debug!("skipping unchanged {}", path.display());
+            return Ok(None);
+        }
+
+        let records = input::parse(path, &contents, &self.options.input)
+            .with_context(|| format!("could not parse {}", path.display()))?;
+
+        let mut batch = Batch {
+            path: relative_path,
+            first: true,
+            hash: Some(hash),
+            ..Default::default()
+        };
+        for (id, data) in records {
+            self.record(&mut batch, path, &id, data)?;
+        }
+
+        Ok(Some(batch))
+    }
+
+    /// Maps the records of a JSON Lines or CSV file one at a time, handing
+    /// each batch of [`RECORDS_PER_BATCH`] records to `write`, or `None` if the
+    /// file has not changed since it was last imported.
+    fn stream(&self, path: &Path, mut write: impl FnMut(Option<Batch>) -> Result<()>) -> Result<()> {
+        let read_error = || format!("could not read {}", path.display());
+        let mut hasher = self.mapping_hash.clone();
+        io::copy(&mut File::open(path).with_context(read_error)?, &mut hasher)
+            .with_context(read_error)?;
+        let relative_path = relative_path(self.data_path, path);
+        let hash = format!("{:x}", hasher.finalize());
+        if !self.options.force && self.hashes.get(&relative_path) == Some(&hash) {
+            debug!("skipping unchanged {}", path.display());
+            return write(None);
+        }
+
+        let reader = BufReader::new(File::open(path).with_context(read_error)?);
+        let records = input::stream(path, reader, &self.options.input)
+            .with_context(|| format!("could not parse {}", path.display()))?;
+
+        let mut batch = Batch {
+            path: relative_path,
+            first: true,
+            ..Default::default()
+        };
+        for record in records {
+            let (id, data) = record.with_context(|| format!("could not parse {}", path.display()))?;
+            self.record(&mut batch, path, &id, data)?;
+            if batch.entities.len() >= RECORDS_PER_BATCH {
+                let next = Batch {
+                    path: batch.path.clone(),
+                    ..Default::default()
+                };
+                write(Some(std::mem::replace(&mut batch, next)))?;
+            }
+        }
+        batch.hash = Some(hash);
+
+        write(Some(batch))
+    }
+
+    /// Maps one input record into an entity and its properties.
+    fn record(&self, batch: &mut Batch, source: &Path, id: &str, data: Val) -> Result<()> {
+        let schema_name = self.schema_name;
+        let matches = self.mapper.matches(&data).with_context(|| {
+            format!(
+                "could not run mapper for schema {} on {} (id {})",
+                schema_name,
+                source.display(),
+                id
+            )
+        })?;
+        if !matches {
+            return Ok(());
+        }
+        let computed_id = self.mapper.id(&data).with_context(|| {
+            format!(
+                "could not compute id for schema {} on {} (id {})",
+                schema_name,
+                source.display(),
+                id
+            )
+        })?;
+        let id = computed_id.as_deref().unwrap_or(id);
+
+        let mut properties = Vec::new();
+        for result in self.mapper.run(data) {
+            let property = result.with_context(|| {
+                format!(
+                    "could not run mapper for schema {} on {} (id {})",
+                    schema_name,
+                    source.display(),
+                    id
+                )
+            })?;
+            if let Err(e) = self.validator.check(schema_name, &property, &properties) {
+                let e = anyhow::Error::new(e).context(format!(
+                    "invalid property from {} (id {}, filter `{}`)",
+                    source.display(),
+                    id,
+                    property.filter
+                ));
+                self.invalid(batch, e, "skipping property")?;
+                continue;
+            }
+            properties.push(property);
+        }
+        for e in self.validator.validate_required(schema_name, &properties) {
+            let e = anyhow::Error::new(e)
+                .context(format!("invalid entity from {} (id {})", source.display(), id));
+            self.invalid(batch, e, "importing entity anyway")?;
+        }
+        batch.entities.push((id.to_string(), properties));
+
+        Ok(())
+    }
+
+    /// Records a validation error in a dry run and warns about it when
+    /// lenient, or else fails with it.
+    fn invalid(&self, batch: &mut Batch, e: anyhow::Error, lenient_action: &str) -> Result<()> {
+        if self.options.dry_run {
+            batch.errors.push(format!("{:#}", e));
+            return Ok(());
+        }
+        if self.options.lenient {
+            warn!("{}: {:#}", lenient_action, e);
+            return Ok(());
+        }
+        Err(e)
+    }
+}
+
+/// Writes batches to the database, one transaction per data file.
+struct Importer {
+    db: Client,
+    dry_run: bool,
+    /// Where the values are recorded as coming from, instead of their files.
+    provenance: Option<String>,
+    /// Whether the data files and the entities they gave are left unrecorded.
+    untracked: bool,
+    summary: Summary,
+}
+
+impl Importer {
+    /// The content hashes of the files imported before for a schema.
+    fn hashes(&mut self, schema_name: &str) -> Result<HashMap<String, String>> {
+        let rows = self
+            .db
+            .query(&ImportFilesQuery { schema_name })
+            .with_context(|| format!("could not read imported files of schema {}", schema_name))?;
+
+        Ok(rows.into_iter().map(|row| (row.path, row.hash)).collect())
+    }
+
+    /// The ids of the entities imported before for a schema, by the relative
+    /// path of the file they were imported from.
+    fn imported_entities(&mut self, schema_name: &str) -> Result<HashMap<String, Vec<String>>> {
+        let rows = self
+            .db
+            .query(&ImportEntitiesQuery { schema_name })
+            .with_context(|| format!("could not read imported entities of schema {}", schema_name))?;
+
+        let mut entities: HashMap<String, Vec<String>> = HashMap::new();
+        for row in rows {
+            entities.entry(row.path).or_default().push(row.entity_id);
+        }
+
+        Ok(entities)
+    }
+
+    /// The ids of the entities of a schema merged into others, with the ids of
+    /// the entities they were merged into.
+    fn aliases(&mut self, schema_name: &str) -> Result<HashMap<String, String>> {
+        let rows = self
+            .db
+            .query(&EntityAliasesQuery(schema_name))
+            .with_context(|| format!("could not read aliases of schema {}", schema_name))?;
+
+        Ok(rows.into_iter().map(|row| (row.alias, row.id)).collect())
+    }
+
+    /// Removes entities that are no longer in the data files, along with the
+    /// records of data files that no longer exist.
+    fn remove(
+        &mut self,
+        schema_name: &str,
+        paths: Vec<&String>,
+        ids: BTreeSet<&String>,
+    ) -> Result<()> {
+        *self.summary.removed.entry(schema_name.to_string()).or_default() += ids.len();
+        if self.dry_run {
+            return Ok(());
+        }
+
+        let mut txn = self.db.transaction()?;
+        for id in ids {
+            txn.execute(&PropertiesForEntityDelete {
+                schema: schema_name,
+                id,
+            })?;
+            txn.execute(&EntityDelete { schema_name, id })
+                .with_context(|| format!("could not remove entity {} of schema {}", id, schema_name))?;
+            txn.execute(&ImportEntityDelete {
+                schema_name,
+                entity_id: id,
+            })?;
+        }
+        for path in paths {
+            txn.execute(&ImportEntitiesForFileDelete { schema_name, path })?;
+            txn.execute(&ImportFileDelete { schema_name, path })?;
+        }
+        txn.commit()?;
+
+        Ok(())
+    }
+
+    /// Writes a batch of entities, replacing the values the data file gave
+    /// them before and the values of the properties it gives them now. Values
+    /// from other files or edits of other properties are kept.
+    fn write(&mut self, schema_name: &str, batch: Batch) -> Result<()> {
+        self.summary.errors.extend(batch.errors);
+        for (_, properties) in &batch.entities {
+            *self.summary.entities.entry(schema_name.to_string()).or_default() += 1;
+            for property in properties {
+                *self.summary.properties.entry(property.schema.clone()).or_default() += 1;
+            }
+        }
+        if self.dry_run {
+            return Ok(());
+        }
+
+        let provenance = match &self.provenance {
+            Some(provenance) => provenance.clone(),
+            None => format!("import:{}", batch.path),
+        };
+        let mut txn = self.db.transaction()?;
+        if batch.first && !self.untracked {
+            txn.execute(&PropertiesForImportFileDelete {
+                schema: schema_name,
+                provenance: &provenance,
+                path: &batch.path,
+            })?;
+            txn.execute(&ImportEntitiesForFileDelete {
+                schema_name,
+                path: &batch.path,
+            })?;
+        }
+        for (id, properties) in &batch.entities {
+            if !self.untracked {
+                txn.execute(&InsertImportEntityStatement {
+                    schema_name,
+                    path: &batch.path,
+                    entity_id: id,
+                })?;
+            }
+            txn.execute(&InsertEntityStatement {
+                schema_name,
+                id,
             })
             .with_context(|| format!("could not insert schema {}", schema_name))?;
-
-            for result in mapper.run(data) {
-                let property = result.with_context(|| {
-                    format!(
-                        "could not run mapper for schema {} and id {}",
-                        schema_name, id
-                    )
+            let names: HashSet<(&str, &str)> = properties
+                .iter()
+                .map(|property| (property.schema.as_str(), property.name.as_str()))
+                .collect();
+            for (property_schema, name) in names {
+                txn.execute(&PropertyForEntityDelete {
+                    schema: schema_name,
+                    id,
+                    property_schema,
+                    name,
                 })?;
+            }
+            let mut positions: HashMap<(&str, &str), i64> = HashMap::new();
+            for property in properties {
+                let position = positions
+                    .entry((&property.schema, &property.name))
+                    .or_default();
                 let property_value = match &property.value {
                     Val::Str(s, _) => String::from_utf8(s.to_vec())
                         .context("Invalid UTF-8 string in property value")?,
                     _ => property.value.to_string(),
                 };
-                db.execute(&PropertyForEntitySchemaInsert {
-                    schema: &schema_name,
-                    id: &id,
+                txn.execute(&PropertyForEntitySchemaInsert {
+                    schema: schema_name,
+                    id,
                     property_schema: &property.schema,
                     name: &property.name,
                     value: &property_value,
+                    position: *position,
+                    provenance: &provenance,
                 })?;
+                *position += 1;
             }
         }
-    }
+        // the hash is only recorded once the whole file is written, so that a
+        // file whose import failed part way is imported again
+        if let Some(hash) = batch.hash.as_ref().filter(|_| !self.untracked) {
+            txn.execute(&UpsertImportFileStatement {
+                schema_name,
+                path: &batch.path,
+                hash,
+            })?;
+        }
+        txn.commit()?;
 
-    Ok(())
+        Ok(())
+    }
 }
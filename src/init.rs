@@ -1,12 +1,16 @@
 use crate::{
-    parsedir,
-    schema::{self, Schema},
+    migrate, parsedir,
+    schema::{self, Schema, SchemaProperty},
+    store::schema::InsertSchemaVersionStatement,
 };
-use anyhow::{Context, Result};
-use aykroyd::{Statement, rusqlite::Client};
-use rusqlite::{Connection, ToSql};
+use anyhow::{Context, Result, bail};
+use aykroyd::{
+    Statement,
+    rusqlite::{Client, Transaction},
+};
+use rusqlite::Connection;
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap, HashSet},
     path::{Path, PathBuf},
 };
 use topological_sort::TopologicalSort;
@@ -23,7 +27,7 @@ pub struct InsertSchemaStatement<'a> {
 }
 
 #[derive(Statement)]
-#[aykroyd(text = "INSERT INTO schema_property VALUES($1, $2, $3)")]
+#[aykroyd(text = "INSERT INTO schema_property VALUES($1, $2, $3, $4, $5, $6, $7, $8)")]
 pub struct InsertSchemaPropertyStatement<'a> {
     #[aykroyd(param = "$1")]
     pub schema_name: &'a str,
@@ -31,6 +35,38 @@ pub struct InsertSchemaPropertyStatement<'a> {
     pub property_name: &'a str,
     #[aykroyd(param = "$3")]
     pub property_type: &'a schema::Type,
+    #[aykroyd(param = "$4")]
+    pub required: bool,
+    #[aykroyd(param = "$5")]
+    pub cardinality: &'a schema::Cardinality,
+    #[aykroyd(param = "$6")]
+    pub label: Option<&'a str>,
+    #[aykroyd(param = "$7")]
+    pub description: Option<&'a str>,
+    #[aykroyd(param = "$8")]
+    pub display_order: Option<i64>,
+}
+
+#[derive(Statement)]
+#[aykroyd(text = "INSERT INTO schema_property_value VALUES($1, $2, $3)")]
+pub struct InsertSchemaPropertyValueStatement<'a> {
+    #[aykroyd(param = "$1")]
+    pub schema_name: &'a str,
+    #[aykroyd(param = "$2")]
+    pub property_name: &'a str,
+    #[aykroyd(param = "$3")]
+    pub value: &'a str,
+}
+
+#[derive(Statement)]
+#[aykroyd(text = "INSERT INTO schema_override VALUES($1, $2, $3)")]
+pub struct InsertSchemaOverrideStatement<'a> {
+    #[aykroyd(param = "$1")]
+    pub schema_name: &'a str,
+    #[aykroyd(param = "$2")]
+    pub property_name: &'a str,
+    #[aykroyd(param = "$3")]
+    pub from_schema: &'a str,
 }
 
 #[derive(Statement)]
@@ -47,13 +83,30 @@ pub fn run(db_path: &Path, schema_path: PathBuf) -> Result<()> {
     // setup our tables
     connection
         .execute_batch(SCHEMA_SQL)
-        .with_context(|| "could not create tables")?;
+        .with_context(|| {
+            "could not create tables (use `pika schema apply` to update an existing database)"
+        })?;
+    connection.pragma_update(None, "user_version", migrate::VERSION)?;
 
     let mut db: Client = connection.into();
+    let mut txn = db.transaction()?;
+
+    // insert the given schema for the app
+    for (schema_name, schema) in load(&schema_path)? {
+        insert_schema(&mut txn, &schema_name, &schema)?;
+    }
+    txn.execute(&InsertSchemaVersionStatement)
+        .context("could not record schema version")?;
+    txn.commit()?;
 
+    Ok(())
+}
+
+/// Reads the schemas of a directory, each after the schemas it extends.
+pub(crate) fn load(schema_path: &Path) -> Result<Vec<(String, Schema)>> {
     let mut schemas = HashMap::new();
     let mut ts = TopologicalSort::<String>::new();
-    for result in parsedir::parse(&schema_path, |s| toml::from_str(s))? {
+    for result in parsedir::parse(schema_path, |s| toml::from_str(s))? {
         let (schema_name, schema): (String, Schema) = result?;
         ts.insert(schema_name.clone());
         if let Some(extends) = &schema.extends {
@@ -64,57 +117,204 @@ pub fn run(db_path: &Path, schema_path: PathBuf) -> Result<()> {
         schemas.insert(schema_name, schema);
     }
 
-    // insert the given schema for the app
+    for (schema_name, schema) in &schemas {
+        check_conflicts(&schemas, schema_name, schema)?;
+    }
+
+    let mut ordered = Vec::new();
     for schema_name in ts {
-        let schema = schemas.get(&schema_name).context("could not get schema")?;
-
-        db.execute(&InsertSchemaStatement {
-            name: &schema_name,
-            abstrct: schema.abstrct,
-        })
-        .with_context(|| format!("could not insert schema {}", schema_name))?;
-
-        // insert properties
-        if let Some(schema_properties) = &schema.properties {
-            for (name, schema_property) in schema_properties {
-                db.execute(&InsertSchemaPropertyStatement {
-                    schema_name: &schema_name,
-                    property_name: name,
-                    property_type: &schema_property.typ,
-                })
-                .with_context(|| {
-                    format!(
-                        "could not insert property:{} for schema:{}",
-                        name, schema_name
-                    )
-                })?;
-            }
+        let schema = schemas
+            .remove(&schema_name)
+            .with_context(|| format!("could not get schema {}", schema_name))?;
+        ordered.push((schema_name, schema));
+    }
+
+    Ok(ordered)
+}
+
+/// Fails when a schema inherits properties of the same name but with different
+/// types or cardinalities from the schemas it extends, unless it names the
+/// schema whose definition it uses in `overrides`.
+fn check_conflicts(
+    schemas: &HashMap<String, Schema>,
+    schema_name: &str,
+    schema: &Schema,
+) -> Result<()> {
+    let mut inherited: BTreeMap<&str, Vec<(&str, &SchemaProperty)>> = BTreeMap::new();
+    let mut seen = HashSet::new();
+    let mut pending: Vec<&str> = schema.extends.iter().flatten().map(String::as_str).collect();
+    while let Some(ancestor_name) = pending.pop() {
+        if !seen.insert(ancestor_name) {
+            continue;
+        }
+        let Some(ancestor) = schemas.get(ancestor_name) else {
+            continue;
+        };
+        for (name, property) in ancestor.properties.iter().flatten() {
+            inherited.entry(name).or_default().push((ancestor_name, property));
         }
+        pending.extend(ancestor.extends.iter().flatten().map(String::as_str));
+    }
 
-        // insert extends
-        if let Some(schema_extends) = &schema.extends {
-            for name in schema_extends {
-                db.execute(&InsertSchemaExtendStatement {
-                    schema_name: &schema_name,
-                    extends_name: name,
-                })
-                .with_context(|| {
-                    format!(
-                        "could not insert extends {} for schema {}",
-                        name, schema_name
-                    )
-                })?;
-            }
+    for (name, definitions) in inherited {
+        let (_, first) = definitions[0];
+        let conflicting = definitions
+            .iter()
+            .any(|(_, property)| {
+                property.typ != first.typ || property.cardinality != first.cardinality
+            });
+        if !conflicting {
+            continue;
+        }
+        let mut sources: Vec<&str> = definitions.iter().map(|(source, _)| *source).collect();
+        sources.sort();
+        match schema.overrides.get(name) {
+            Some(from) if sources.contains(&from.as_str()) => {}
+            Some(from) => bail!(
+                "schema {} overrides property {} with the definition of {}, which is not one of {}",
+                schema_name,
+                name,
+                from,
+                sources.join(", ")
+            ),
+            None => bail!(
+                "schema {} inherits conflicting definitions of property {} from {}; choose one in `overrides`",
+                schema_name,
+                name,
+                sources.join(", ")
+            ),
         }
     }
 
     Ok(())
 }
 
-impl ToSql for schema::Type {
-    fn to_sql(&self) -> rusqlite::Result<rusqlite::types::ToSqlOutput<'_>> {
-        match self {
-            schema::Type::Name => Ok("name".into()),
+pub(crate) fn insert_schema(txn: &mut Transaction, schema_name: &str, schema: &Schema) -> Result<()> {
+    txn.execute(&InsertSchemaStatement {
+        name: schema_name,
+        abstrct: schema.abstrct,
+    })
+    .with_context(|| format!("could not insert schema {}", schema_name))?;
+
+    // insert properties
+    if let Some(schema_properties) = &schema.properties {
+        for (name, schema_property) in schema_properties {
+            insert_property(txn, schema_name, name, schema_property)?;
         }
     }
+
+    // insert extends
+    if let Some(schema_extends) = &schema.extends {
+        for name in schema_extends {
+            insert_extends(txn, schema_name, name)?;
+        }
+    }
+
+    for (property_name, from_schema) in &schema.overrides {
+        insert_override(txn, schema_name, property_name, from_schema)?;
+    }
+
+    Ok(())
+}
+
+pub(crate) fn insert_override(
+    txn: &mut Transaction,
+    schema_name: &str,
+    property_name: &str,
+    from_schema: &str,
+) -> Result<()> {
+    txn.execute(&InsertSchemaOverrideStatement {
+        schema_name,
+        property_name,
+        from_schema,
+    })
+    .with_context(|| {
+        format!(
+            "could not insert override of property {} for schema {}",
+            property_name, schema_name
+        )
+    })?;
+
+    Ok(())
+}
+
+pub(crate) fn insert_property(
+    txn: &mut Transaction,
+    schema_name: &str,
+    name: &str,
+    schema_property: &SchemaProperty,
+) -> Result<()> {
+    txn.execute(&InsertSchemaPropertyStatement {
+        schema_name,
+        property_name: name,
+        property_type: &schema_property.typ,
+        required: schema_property.required,
+        cardinality: &schema_property.cardinality,
+        label: schema_property.label.as_deref(),
+        description: schema_property.description.as_deref(),
+        display_order: schema_property.order,
+    })
+    .with_context(|| {
+        format!(
+            "could not insert property:{} for schema:{}",
+            name, schema_name
+        )
+    })?;
+
+    match (&schema_property.typ, &schema_property.values) {
+        (schema::Type::Enum, Some(values)) if !values.is_empty() => {
+            for value in values {
+                insert_property_value(txn, schema_name, name, value)?;
+            }
+        }
+        (schema::Type::Enum, _) => bail!(
+            "enum property:{} for schema:{} has no values",
+            name,
+            schema_name
+        ),
+        (_, Some(_)) => bail!(
+            "property:{} for schema:{} has values but is not an enum",
+            name,
+            schema_name
+        ),
+        (_, None) => {}
+    }
+
+    Ok(())
+}
+
+pub(crate) fn insert_property_value(
+    txn: &mut Transaction,
+    schema_name: &str,
+    name: &str,
+    value: &str,
+) -> Result<()> {
+    txn.execute(&InsertSchemaPropertyValueStatement {
+        schema_name,
+        property_name: name,
+        value,
+    })
+    .with_context(|| {
+        format!(
+            "could not insert value {} of property:{} for schema:{}",
+            value, name, schema_name
+        )
+    })?;
+
+    Ok(())
+}
+
+pub(crate) fn insert_extends(txn: &mut Transaction, schema_name: &str, name: &str) -> Result<()> {
+    txn.execute(&InsertSchemaExtendStatement {
+        schema_name,
+        extends_name: name,
+    })
+    .with_context(|| {
+        format!(
+            "could not insert extends {} for schema {}",
+            name, schema_name
+        )
+    })?;
+
+    Ok(())
 }
@@ -0,0 +1,77 @@
+use std::collections::HashMap;
+
+use jaq_json::{Map, Val};
+use scraper::Html;
+
+use crate::{chu, mapper};
+
+fn string_map(map: HashMap<String, String>) -> Val {
+    let mut obj = Map::default();
+    for (key, value) in map {
+        obj.insert(Val::utf8_str(key), Val::utf8_str(value));
+    }
+    Val::obj(obj)
+}
+
+/// Converts an HTML page into a value holding its title, the tables, lists,
+/// definition lists and heading sections chu extracts from it, and the raw
+/// HTML for CSS selectors, which query the page as parsed here.
+pub fn document(html: &str) -> Val {
+    let parsed = Html::parse_document(html);
+    let document = chu::extract(&parsed);
+    mapper::cache_html(html.to_string(), parsed);
+
+    let tables = document
+        .tables
+        .into_iter()
+        .map(|table| {
+            table
+                .rows
+                .iter()
+                .map(|row| {
+                    // cells in column order
+                    let mut obj = Map::default();
+                    for column in &table.header {
+                        if let Some(value) = row.get(column) {
+                            obj.insert(Val::utf8_str(column.clone()), Val::utf8_str(value.clone()));
+                        }
+                    }
+                    Val::obj(obj)
+                })
+                .collect::<Val>()
+        })
+        .collect::<Val>();
+    let lists = document
+        .lists
+        .into_iter()
+        .map(|list| list.into_iter().map(Val::utf8_str).collect::<Val>())
+        .collect::<Val>();
+    let definitions = document
+        .definitions
+        .into_iter()
+        .map(string_map)
+        .collect::<Val>();
+    let sections = document
+        .sections
+        .into_iter()
+        .map(|section| {
+            string_map(HashMap::from([
+                (String::from("heading"), section.heading),
+                (String::from("text"), section.text),
+            ]))
+        })
+        .collect::<Val>();
+
+    let mut map = Map::default();
+    map.insert(
+        Val::utf8_str("title".to_string()),
+        document.title.map_or(Val::Null, Val::utf8_str),
+    );
+    map.insert(Val::utf8_str("tables".to_string()), tables);
+    map.insert(Val::utf8_str("lists".to_string()), lists);
+    map.insert(Val::utf8_str("definitions".to_string()), definitions);
+    map.insert(Val::utf8_str("sections".to_string()), sections);
+    map.insert(Val::utf8_str("html".to_string()), Val::utf8_str(html.to_string()));
+
+    Val::obj(map)
+}
@@ -0,0 +1,223 @@
+mod html;
+pub(crate) mod xml;
+
+use std::{
+    io::{BufRead, Read},
+    path::{Path, PathBuf},
+};
+
+use jaq_json::{Map, Val};
+
+#[derive(thiserror::Error, Debug)]
+pub enum InputError {
+    #[error("could not read {0}: {1}")]
+    Io(PathBuf, std::io::Error),
+    #[error("could not parse TOML in {0}: {1}")]
+    Toml(PathBuf, jaq_json::toml::PError),
+    #[error("could not parse JSON in {0}: {1}")]
+    Json(PathBuf, String),
+    #[error("could not parse JSON on line {1} of {0}: {2}")]
+    JsonLine(PathBuf, usize, String),
+    #[error("could not parse XML in {0}: {1}")]
+    Xml(PathBuf, jaq_json::xml::PError),
+    #[error("could not parse CSV in {0}: {1}")]
+    Csv(PathBuf, csv::Error),
+    #[error("id column {1} not found in the header of {0}")]
+    MissingIdColumn(PathBuf, String),
+    #[error("row {1} of {0} has {2} fields, but the header has {3}")]
+    RowLength(PathBuf, usize, usize, usize),
+    #[error("unsupported data file {0}")]
+    UnsupportedFormat(PathBuf),
+}
+
+/// The formats a data file can be written in, chosen by its extension.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Toml,
+    Json,
+    JsonLines,
+    Csv,
+    Xml,
+    Html,
+}
+
+impl Format {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "toml" => Some(Format::Toml),
+            "json" => Some(Format::Json),
+            "jsonl" | "ndjson" => Some(Format::JsonLines),
+            "csv" => Some(Format::Csv),
+            "xml" => Some(Format::Xml),
+            "html" | "htm" => Some(Format::Html),
+            _ => None,
+        }
+    }
+
+    /// Whether files of this format can be read one record at a time with
+    /// [`stream`].
+    pub fn streams(self) -> bool {
+        matches!(self, Format::JsonLines | Format::Csv)
+    }
+}
+
+/// A record of a data file as an `(id, value)` pair.
+pub type Record = Result<(String, Val), InputError>;
+
+#[derive(Debug, Default)]
+pub struct Options {
+    /// Column whose value is used as the entity id for CSV rows.
+    pub id_column: Option<String>,
+    /// Tag of the XML elements that each hold one entity.
+    pub record_element: Option<String>,
+}
+
+/// Reads a data file as text. HTML pages are decoded with the charset they
+/// declare, other files must be UTF-8.
+pub fn read(path: &Path) -> Result<String, InputError> {
+    let io_error = |e| InputError::Io(path.to_path_buf(), e);
+    if Format::from_path(path) == Some(Format::Html) {
+        let bytes = std::fs::read(path).map_err(io_error)?;
+        return Ok(crate::chu::decode(&bytes, None));
+    }
+
+    std::fs::read_to_string(path).map_err(io_error)
+}
+
+/// Parses a data file into `(id, value)` records, one per entity.
+///
+/// TOML and JSON files hold a single entity identified by the file stem.
+/// JSON Lines files hold one entity per line, identified by the file stem
+/// and the line number. CSV files hold one entity per row, as an object keyed
+/// by the header, identified by the id column or else the file stem and the
+/// row number. XML files hold a single entity, or one entity per record
+/// element identified by the file stem and the record number. HTML files hold
+/// a single entity built from the page by chu.
+pub fn parse(
+    path: &Path,
+    contents: &str,
+    options: &Options,
+) -> Result<Vec<(String, Val)>, InputError> {
+    let stem = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or_default();
+    let format =
+        Format::from_path(path).ok_or_else(|| InputError::UnsupportedFormat(path.to_path_buf()))?;
+
+    match format {
+        Format::Toml => {
+            let val = jaq_json::toml::parse(contents)
+                .map_err(|e| InputError::Toml(path.to_path_buf(), e))?;
+            Ok(vec![(stem.to_string(), val)])
+        }
+        Format::Json => {
+            let val = jaq_json::json::parse_single(contents.as_bytes())
+                .map_err(|e| InputError::Json(path.to_path_buf(), e.to_string()))?;
+            Ok(vec![(stem.to_string(), val)])
+        }
+        Format::JsonLines => json_lines(path, stem, contents.as_bytes()).collect(),
+        Format::Csv => csv_records(path, stem, contents.as_bytes(), options)?.collect(),
+        Format::Html => Ok(vec![(stem.to_string(), html::document(contents))]),
+        Format::Xml => {
+            let roots = jaq_json::xml::parse_many(contents)
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| InputError::Xml(path.to_path_buf(), e))?;
+            let records = xml::records(roots, options.record_element.as_deref());
+            Ok(match &options.record_element {
+                Some(_) => records
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, val)| (format!("{}-{}", stem, index + 1), val))
+                    .collect(),
+                None => records
+                    .into_iter()
+                    .map(|val| (stem.to_string(), val))
+                    .collect(),
+            })
+        }
+    }
+}
+
+/// Reads the records of a JSON Lines or CSV file one at a time, so that large
+/// files need not be held in memory. Other formats are read whole by [`parse`].
+pub fn stream<'a>(
+    path: &'a Path,
+    reader: impl BufRead + 'a,
+    options: &Options,
+) -> Result<Box<dyn Iterator<Item = Record> + 'a>, InputError> {
+    let stem = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or_default();
+    match Format::from_path(path) {
+        Some(Format::JsonLines) => Ok(Box::new(json_lines(path, stem, reader))),
+        Some(Format::Csv) => Ok(Box::new(csv_records(path, stem, reader, options)?)),
+        _ => Err(InputError::UnsupportedFormat(path.to_path_buf())),
+    }
+}
+
+fn json_lines<'a>(
+    path: &'a Path,
+    stem: &'a str,
+    reader: impl BufRead + 'a,
+) -> impl Iterator<Item = Record> + 'a {
+    reader.lines().enumerate().filter_map(move |(index, line)| {
+        let line = match line {
+            Ok(line) => line,
+            Err(e) => return Some(Err(InputError::Io(path.to_path_buf(), e))),
+        };
+        if line.trim().is_empty() {
+            return None;
+        }
+        let record = jaq_json::json::parse_single(line.as_bytes())
+            .map(|val| (format!("{}-{}", stem, index + 1), val))
+            .map_err(|e| InputError::JsonLine(path.to_path_buf(), index + 1, e.to_string()));
+        Some(record)
+    })
+}
+
+fn csv_records<'a>(
+    path: &'a Path,
+    stem: &'a str,
+    reader: impl Read + 'a,
+    options: &Options,
+) -> Result<impl Iterator<Item = Record> + 'a, InputError> {
+    let csv_error = |e| InputError::Csv(path.to_path_buf(), e);
+    // rows of the wrong length are reported with their row number below
+    let mut reader = csv::ReaderBuilder::new().flexible(true).from_reader(reader);
+    let headers = reader.headers().map_err(csv_error)?.clone();
+    let id_index = match &options.id_column {
+        Some(column) => Some(
+            headers
+                .iter()
+                .position(|header| header == column)
+                .ok_or_else(|| InputError::MissingIdColumn(path.to_path_buf(), column.clone()))?,
+        ),
+        None => None,
+    };
+
+    Ok(reader.into_records().enumerate().map(move |(index, result)| {
+        let row = result.map_err(csv_error)?;
+        if row.len() != headers.len() {
+            return Err(InputError::RowLength(
+                path.to_path_buf(),
+                index + 1,
+                row.len(),
+                headers.len(),
+            ));
+        }
+        let id = match id_index {
+            Some(i) => row[i].to_string(),
+            None => format!("{}-{}", stem, index + 1),
+        };
+        let mut map = Map::default();
+        for (header, value) in headers.iter().zip(row.iter()) {
+            map.insert(
+                Val::utf8_str(header.to_string()),
+                Val::utf8_str(value.to_string()),
+            );
+        }
+        Ok((id, Val::obj(map)))
+    }))
+}
@@ -0,0 +1,98 @@
+use jaq_json::{Map, Val};
+
+/// Converts the parsed roots of an XML document into mapping-friendly values.
+///
+/// An element becomes an object with its attributes under `@name` keys and
+/// its child elements under their tag names (as an array when a tag repeats),
+/// with any text under `#text`. An element holding nothing but text becomes
+/// that text. When `record` is given, every element with that tag becomes a
+/// value of its own; otherwise the root element does.
+pub fn records(roots: Vec<Val>, record: Option<&str>) -> Vec<Val> {
+    let mut records = Vec::new();
+    for root in roots.iter().filter(|root| tag(root).is_some()) {
+        match record {
+            Some(record) => find(root, record, &mut records),
+            None => records.push(convert(root)),
+        }
+    }
+
+    records
+}
+
+fn find(element: &Val, record: &str, records: &mut Vec<Val>) {
+    if tag(element) == Some(record) {
+        records.push(convert(element));
+        return;
+    }
+    for child in children(element) {
+        if tag(child).is_some() {
+            find(child, record, records);
+        }
+    }
+}
+
+pub(crate) fn field<'a>(val: &'a Val, key: &str) -> Option<&'a Val> {
+    match val {
+        Val::Obj(map) => map.get(&Val::utf8_str(key.to_string())),
+        _ => None,
+    }
+}
+
+pub(crate) fn as_str(val: &Val) -> Option<&str> {
+    match val {
+        Val::Str(s, _) => std::str::from_utf8(s).ok(),
+        _ => None,
+    }
+}
+
+fn tag(val: &Val) -> Option<&str> {
+    field(val, "t").and_then(as_str)
+}
+
+fn children(val: &Val) -> &[Val] {
+    match field(val, "c") {
+        Some(Val::Arr(children)) => children,
+        _ => &[],
+    }
+}
+
+fn convert(element: &Val) -> Val {
+    let mut map = Map::default();
+    if let Some(Val::Obj(attrs)) = field(element, "a") {
+        for (name, value) in attrs.iter() {
+            let name = as_str(name).unwrap_or_default();
+            map.insert(Val::utf8_str(format!("@{}", name)), value.clone());
+        }
+    }
+
+    let mut text = String::new();
+    for child in children(element) {
+        if let Some(tag) = tag(child) {
+            let key = Val::utf8_str(tag.to_string());
+            let value = convert(child);
+            match map.get_mut(&key) {
+                Some(Val::Arr(values)) => jaq_json::Rc::make_mut(values).push(value),
+                Some(existing) => *existing = Val::Arr(vec![existing.clone(), value].into()),
+                None => {
+                    map.insert(key, value);
+                }
+            }
+        } else if let Some(s) = as_str(child).or_else(|| field(child, "cdata").and_then(as_str)) {
+            text.push_str(s);
+        }
+    }
+
+    let text = text.trim();
+    if map.is_empty() {
+        return if text.is_empty() {
+            Val::Null
+        } else {
+            Val::utf8_str(text.to_string())
+        };
+    }
+    if !text.is_empty() {
+        map.insert(Val::utf8_str("#text".to_string()), Val::utf8_str(text.to_string()));
+    }
+
+    Val::obj(map)
+}
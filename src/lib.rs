@@ -1,8 +1,25 @@
 pub mod init;
 pub mod schema;
+pub mod delete;
 pub mod import;
+pub mod mapping_check;
+pub mod input;
 pub mod parsedir;
+pub mod progress;
+pub mod provenance;
+pub mod rdf;
 pub mod mapper;
+pub mod merge;
+pub mod migrate;
 pub mod serve;
+pub mod shell;
+pub mod source;
+pub mod stat;
+pub mod table;
 pub mod store;
-pub mod chu;
\ No newline at end of file
+pub mod triples;
+pub mod chu;
+pub mod config;
+pub mod validate;
+pub mod watch;
+pub mod wikidata;
\ No newline at end of file
@@ -1,18 +1,55 @@
 use anyhow::Result;
-use clap::{Parser, Subcommand};
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser, Subcommand};
 use pika::chu;
+use pika::config::Config;
+use pika::delete;
 use pika::import;
 use pika::init;
+use pika::input;
+use pika::mapping_check;
+use pika::merge;
+use pika::progress;
+use pika::provenance;
+use pika::rdf;
+use pika::schema;
 use pika::serve;
+use pika::shell;
+use pika::source;
+use pika::stat;
+use pika::triples;
+use pika::validate;
+use pika::watch;
+use pika::wikidata;
 use tracing::Level;
-use tracing_subscriber::FmtSubscriber;
-use std::path::PathBuf;
+use tracing_subscriber::{EnvFilter, FmtSubscriber};
+use std::{ffi::OsString, path::PathBuf, sync::Arc};
 
 #[derive(Parser)]
 #[command(version)]
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+    /// The database of commands not given one, when not set by PIKA_DB or in
+    /// ~/.config/pika/config.toml
+    #[arg(long = "db", global = true, env = "PIKA_DB")]
+    default_db: Option<PathBuf>,
+    /// Log debug messages, or everything when given twice
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
+    verbose: u8,
+    /// Log only warnings, or only errors when given twice
+    #[arg(short, long, global = true, action = clap::ArgAction::Count, conflicts_with = "verbose")]
+    quiet: u8,
+    #[arg(long, global = true, value_enum, default_value_t)]
+    log_format: LogFormat,
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+enum LogFormat {
+    /// A line of text per message
+    #[default]
+    Text,
+    /// A JSON object per message, for log aggregation
+    Json,
 }
 
 #[derive(Subcommand)]
@@ -25,37 +62,530 @@ enum Commands {
         db: PathBuf,
         data: PathBuf,
         mapping: PathBuf,
+        /// Warn instead of failing on properties that do not match the schema
+        #[arg(long)]
+        lenient: bool,
+        /// Print what would be imported without writing to the database
+        #[arg(long)]
+        dry_run: bool,
+        /// Re-import data files that have not changed since the last import
+        #[arg(long)]
+        force: bool,
+        /// Remove previously imported entities that are no longer in the data
+        #[arg(long)]
+        sync: bool,
+        /// Column holding the entity id in CSV data files
+        #[arg(long)]
+        id_column: Option<String>,
+        /// Tag of the elements holding one entity each in XML data files
+        #[arg(long)]
+        record_element: Option<String>,
+        /// Import only the mapping of this schema
+        #[arg(long)]
+        schema: Option<String>,
+    },
+    Mapping {
+        #[command(subcommand)]
+        command: MappingCommands,
+    },
+    Schema {
+        #[command(subcommand)]
+        command: SchemaCommands,
+    },
+    /// Merge duplicate entities and find candidates to merge
+    Entity {
+        #[command(subcommand)]
+        command: EntityCommands,
+    },
+    /// Delete the values of a property of an entity, or a whole entity
+    Delete {
+        db: PathBuf,
+        schema: String,
+        id: String,
+        /// The property as `schema.name`, or `name` for a property of the entity's schema
+        #[arg(required_unless_present = "all")]
+        property: Option<String>,
+        /// Delete the entity and all its values
+        #[arg(long, conflicts_with = "property")]
+        all: bool,
+    },
+    /// Check every entity against its schema, failing if any is invalid
+    Validate {
+        db: PathBuf,
+        #[arg(long, value_enum, default_value_t)]
+        format: schema::inspect::Format,
+    },
+    /// Print how many entities, values, sources and documents the database holds
+    Stat {
+        db: PathBuf,
+        /// Print the number of values, distinct values, average value size and
+        /// last modification of each attribute instead
+        #[arg(long)]
+        attributes: bool,
+        #[arg(long, value_enum, default_value_t)]
+        format: schema::inspect::Format,
+    },
+    /// Print the values of an attribute of an entity with where each came from
+    Provenance {
+        db: PathBuf,
+        /// The entity as `schema/id`
+        entity: String,
+        /// The attribute as `schema.name`
+        attribute: String,
+    },
+    /// Write every property value of a database to stdout as JSON Lines triples or RDF
+    Export {
+        db: PathBuf,
+        #[arg(long, value_enum, default_value_t)]
+        format: ExportFormat,
+        /// The IRI that entity and attribute IRIs start with
+        #[arg(long, default_value = "urn:pika:")]
+        base: String,
+        /// A `name=IRI` prefix for Turtle
+        #[arg(long = "prefix", value_parser = rdf::parse_prefix)]
+        prefixes: Vec<(String, String)>,
+    },
+    /// Fetch Wikidata items into a data directory and import it
+    Wikidata {
+        db: PathBuf,
+        data: PathBuf,
+        mapping: PathBuf,
+        /// The directory under the data directory to write the items to
+        #[arg(long)]
+        dir: String,
+        /// The language of labels, descriptions and aliases
+        #[arg(long, default_value = "en")]
+        language: String,
+        /// Warn instead of failing on properties that do not match the schema
+        #[arg(long)]
+        lenient: bool,
+        /// Item ids such as Q42
+        #[arg(required = true)]
+        items: Vec<String>,
+    },
+    /// Write JSON Lines triples, as written by export, to a database
+    ImportJsonl {
+        db: PathBuf,
+        /// The triples to read, or `-` for stdin
+        file: PathBuf,
+    },
+    /// Print the property values added to and removed from a database as it changes
+    Watch {
+        db: PathBuf,
+        /// Seconds between checks for changes
+        #[arg(long, default_value_t = 1)]
+        interval: u64,
+        /// Print changes as JSON Lines
+        #[arg(long)]
+        json: bool,
+    },
+    /// Read, write and query a database interactively
+    Shell {
+        db: PathBuf,
+    },
+    /// Crawl the sources that are due without serving
+    Crawl {
+        db: PathBuf,
+        /// Keep crawling every interval until interrupted
+        #[arg(long)]
+        daemon: bool,
+        /// Seconds between crawls with --daemon, varied by up to a tenth
+        #[arg(long, default_value_t = 3600)]
+        interval: u64,
+    },
+    /// Export and import the sources crawled by serve
+    Source {
+        #[command(subcommand)]
+        command: SourceCommands,
     },
     Serve {
         db: PathBuf,
+        /// The port to serve on, 8080 unless set in the configuration file
+        #[arg(long)]
+        port: Option<u16>,
+        /// A directory whose `templates` and `static` directories override
+        /// the built-in ones, file by file
+        #[arg(long)]
+        theme: Option<PathBuf>,
+    },
+    /// Extract the tables and text of an HTML page
+    #[command(alias = "chu")]
+    Extract {
+        /// The page to read, or stdin when missing or `-`
+        file: Option<PathBuf>,
+        #[arg(long, value_enum, default_value_t)]
+        format: chu::Format,
+        /// A `name = CSS selector` to extract instead of every table
+        #[arg(long = "selector", value_parser = chu::NamedSelector::parse)]
+        selectors: Vec<chu::NamedSelector>,
     },
-    Chu,
 }
 
-fn main() -> Result<()> {
-    // a builder for `FmtSubscriber`.
-    let subscriber = FmtSubscriber::builder()
-        // all spans/events with a level higher than TRACE (e.g, debug, info, warn, etc.)
-        // will be written to stdout.
-        .with_max_level(Level::TRACE)
-        // completes the builder.
-        .finish();
+#[derive(Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+enum ExportFormat {
+    /// A JSON triple per line, as read by import-jsonl
+    #[default]
+    Jsonl,
+    Turtle,
+    #[value(name = "ntriples")]
+    NTriples,
+}
+
+#[derive(Subcommand)]
+enum SchemaCommands {
+    /// Add schemas and properties that are new in the schema directory to an existing database
+    Apply { db: PathBuf, schema: PathBuf },
+    /// List the schemas of a database
+    List {
+        db: PathBuf,
+        #[arg(long, value_enum, default_value_t)]
+        format: schema::inspect::Format,
+    },
+    /// Show a schema with its own and inherited properties
+    Show {
+        db: PathBuf,
+        name: String,
+        #[arg(long, value_enum, default_value_t)]
+        format: schema::inspect::Format,
+    },
+    /// Write the schemas of a database as TOML files
+    Export {
+        db: PathBuf,
+        dir: PathBuf,
+        /// Write JSON Schema files instead
+        #[arg(long)]
+        json_schema: bool,
+    },
+    /// Convert a JSON Schema file into a schema directory
+    Import {
+        #[arg(long)]
+        json_schema: PathBuf,
+        dir: PathBuf,
+    },
+}
+
+#[derive(Subcommand)]
+enum EntityCommands {
+    /// Move the values of the loser to the winner and delete the loser
+    Merge {
+        db: PathBuf,
+        /// The entity to keep, as `schema/id`
+        winner: String,
+        /// The entity to merge into the winner, as `schema/id`
+        loser: String,
+    },
+    /// List entities of a schema with the same name
+    Duplicates {
+        db: PathBuf,
+        /// The name of the properties compared
+        #[arg(long, default_value = "name")]
+        property: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum SourceCommands {
+    /// Write the sources and how they are crawled to stdout
+    Export {
+        db: PathBuf,
+        #[arg(long, value_enum, default_value_t)]
+        format: source::Format,
+    },
+    /// Add the sources of a TOML or OPML file, updating those that exist
+    Import {
+        db: PathBuf,
+        /// The sources to read, or `-` for stdin
+        file: PathBuf,
+    },
+}
 
-    tracing::subscriber::set_global_default(subscriber).expect("setting default subscriber failed");
+#[derive(Subcommand)]
+enum MappingCommands {
+    /// Run mappings against fixture inputs and compare with the expected outputs next to them
+    Test {
+        mapping: PathBuf,
+        fixtures: PathBuf,
+        /// Column holding the entity id in CSV fixtures
+        #[arg(long)]
+        id_column: Option<String>,
+        /// Tag of the elements holding one entity each in XML fixtures
+        #[arg(long)]
+        record_element: Option<String>,
+    },
+}
+
+/// Logs to stderr, keeping stdout for the output of commands. RUST_LOG, when
+/// set, takes precedence over `-v` and `-q`.
+fn init_logging(args: &Cli) {
+    let level = match (args.verbose, args.quiet) {
+        (0, 0) => Level::INFO,
+        (1, _) => Level::DEBUG,
+        (_, 0) => Level::TRACE,
+        (_, 1) => Level::WARN,
+        _ => Level::ERROR,
+    };
+    let filter =
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(level.as_str()));
+    let builder = FmtSubscriber::builder()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr);
+
+    let result = match args.log_format {
+        LogFormat::Text => tracing::subscriber::set_global_default(builder.finish()),
+        LogFormat::Json => tracing::subscriber::set_global_default(builder.json().finish()),
+    };
+    result.expect("setting default subscriber failed");
+}
+
+/// The matches of the innermost subcommand.
+fn leaf(matches: &ArgMatches) -> &ArgMatches {
+    match matches.subcommand() {
+        Some((_, matches)) => leaf(matches),
+        None => matches,
+    }
+}
 
-    let args = Cli::parse();
+/// Parses the command line. Commands take the database as their first
+/// argument, which may be left out when --db, PIKA_DB or the configuration
+/// file give one.
+fn parse() -> Result<Cli> {
+    let argv: Vec<OsString> = std::env::args_os().collect();
+    let command = Cli::command();
+    // a database is a file, so a directory first is the argument after it
+    let names_database = |matches: &ArgMatches| {
+        leaf(matches)
+            .try_get_one::<PathBuf>("db")
+            .ok()
+            .flatten()
+            .is_none_or(|db| !db.is_dir())
+    };
+    let given = match command.clone().try_get_matches_from(&argv) {
+        Ok(matches) if names_database(&matches) => return Ok(Cli::from_arg_matches(&matches)?),
+        Ok(matches) => Ok(matches),
+        Err(e) if !e.use_stderr() => e.exit(),
+        Err(e) => Err(e),
+    };
+
+    let partial = command
+        .clone()
+        .ignore_errors(true)
+        .try_get_matches_from(&argv)?;
+    let default_db = match partial.get_one::<PathBuf>("default_db") {
+        Some(db) => Some(db.clone()),
+        None => Config::load()?.db,
+    };
+    let Some(default_db) = default_db else {
+        return Ok(Cli::from_arg_matches(&given.unwrap_or_else(|e| e.exit()))?);
+    };
+    // the database goes after the names of the subcommands
+    let mut index = 0;
+    let mut matches = &partial;
+    while let Some((name, subcommand)) = matches.subcommand() {
+        index += argv[index + 1..]
+            .iter()
+            .position(|arg| arg == name)
+            .map_or(0, |position| position + 1);
+        matches = subcommand;
+    }
+    let mut with_default = argv.clone();
+    with_default.insert(index + 1, default_db.into());
+    match (command.try_get_matches_from(&with_default), given) {
+        (Ok(matches), _) | (Err(_), Ok(matches)) => Ok(Cli::from_arg_matches(&matches)?),
+        (Err(e), Err(_)) => e.exit(),
+    }
+}
+
+fn main() -> Result<()> {
+    let args = parse()?;
+    init_logging(&args);
+
+    // progress bars are hidden along with info messages
+    let show_progress = args.quiet == 0;
 
     match args.command {
         Commands::Init {
-            db: db_path,
+            db,
             schema: schema_path,
-        } => init::run(&db_path, schema_path),
+        } => init::run(&db, schema_path),
         Commands::Import {
-            db: db_path,
+            db,
+            data: data_path,
+            mapping: mapping_path,
+            lenient,
+            dry_run,
+            force,
+            sync,
+            id_column,
+            record_element,
+            schema,
+        } => import::run(
+            &db,
+            data_path,
+            mapping_path,
+            &import::Options {
+                lenient,
+                dry_run,
+                force,
+                sync,
+                input: input::Options {
+                    id_column,
+                    record_element,
+                },
+                progress: show_progress
+                    .then(|| Arc::new(progress::Bar::new()) as Arc<dyn progress::Progress>),
+                schema,
+                ..Default::default()
+            },
+        ),
+        Commands::Mapping {
+            command:
+                MappingCommands::Test {
+                    mapping: mapping_path,
+                    fixtures: fixtures_path,
+                    id_column,
+                    record_element,
+                },
+        } => mapping_check::run(
+            mapping_path,
+            fixtures_path,
+            &input::Options {
+                id_column,
+                record_element,
+            },
+        ),
+        Commands::Schema {
+            command: SchemaCommands::Apply {
+                db,
+                schema: schema_path,
+            },
+        } => schema::apply::run(&db, schema_path),
+        Commands::Schema {
+            command: SchemaCommands::List { db, format },
+        } => schema::inspect::list(&db, format),
+        Commands::Schema {
+            command: SchemaCommands::Show { db, name, format },
+        } => schema::inspect::show(&db, &name, format),
+        Commands::Schema {
+            command: SchemaCommands::Export { db, dir, json_schema },
+        } => {
+            if json_schema {
+                schema::export::json_schema(&db, dir)
+            } else {
+                schema::export::run(&db, dir)
+            }
+        }
+        Commands::Schema {
+            command: SchemaCommands::Import { json_schema, dir },
+        } => schema::json_schema::import(&json_schema, dir),
+        Commands::Entity {
+            command: EntityCommands::Merge { db, winner, loser },
+        } => merge::run(&db, &winner, &loser),
+        Commands::Entity {
+            command: EntityCommands::Duplicates { db, property },
+        } => merge::print_duplicates(&db, &property),
+        Commands::Delete {
+            db,
+            schema,
+            id,
+            property,
+            all,
+        } => delete::run(&db, &schema, &id, property.as_deref(), all),
+        Commands::Validate { db, format } => validate::run(&db, format),
+        Commands::Stat { db, attributes, format } => {
+            stat::run(&db, attributes, format, std::io::stdout().lock())
+        }
+        Commands::Provenance { db, entity, attribute } => provenance::run(
+            &db,
+            &entity,
+            &attribute,
+            std::io::stdout().lock(),
+        ),
+        Commands::Export {
+            db,
+            format,
+            base,
+            prefixes,
+        } => {
+            let syntax = match format {
+                ExportFormat::Jsonl => {
+                    return triples::export(&db, std::io::stdout().lock());
+                }
+                ExportFormat::Turtle => rdf::Syntax::Turtle,
+                ExportFormat::NTriples => rdf::Syntax::NTriples,
+            };
+            let iris = rdf::Iris { base, prefixes };
+            rdf::export(&db, syntax, &iris, std::io::stdout().lock())
+        }
+        Commands::Wikidata {
+            db,
             data: data_path,
             mapping: mapping_path,
-        } => import::run(&db_path, data_path, mapping_path),
-        Commands::Serve { db: db_path } => serve::run(db_path),
-        Commands::Chu => chu::run(),
+            dir,
+            language,
+            lenient,
+            items,
+        } => wikidata::run(
+            &db,
+            data_path,
+            mapping_path,
+            &dir,
+            &items,
+            &language,
+            &import::Options {
+                lenient,
+                progress: show_progress
+                    .then(|| Arc::new(progress::Bar::new()) as Arc<dyn progress::Progress>),
+                ..Default::default()
+            },
+        ),
+        Commands::ImportJsonl { db, file } => {
+            let progress: Box<dyn progress::Progress> = if show_progress {
+                Box::new(progress::Bar::new())
+            } else {
+                Box::new(progress::NoProgress)
+            };
+            triples::import(&db, &file, progress.as_ref())
+        }
+        Commands::Watch { db, interval, json } => watch::run(
+            &db,
+            std::time::Duration::from_secs(interval),
+            json,
+            std::io::stdout().lock(),
+        ),
+        Commands::Shell { db } => shell::run(&db),
+        Commands::Crawl { db, daemon, interval } => {
+            let config = Config::load()?;
+            serve::crawl(
+                serve::AppState {
+                    db_path: db,
+                    webhooks: config.serve.webhooks,
+                    embedder: config.serve.embedding,
+                    pipelines: config.serve.pipelines,
+                },
+                daemon.then(|| std::time::Duration::from_secs(interval)),
+            )
+        }
+        Commands::Source {
+            command: SourceCommands::Export { db, format },
+        } => source::export(&db, format, std::io::stdout().lock()),
+        Commands::Source {
+            command: SourceCommands::Import { db, file },
+        } => source::import(&db, &file),
+        Commands::Serve { db, port, theme } => {
+            let config = Config::load()?;
+            serve::run(
+                db,
+                port.or(config.serve.port).unwrap_or(8080),
+                config.serve.webhooks,
+                config.serve.embedding,
+                config.serve.pipelines,
+                theme.or(config.serve.theme),
+            )
+        }
+        Commands::Extract {
+            file,
+            format,
+            selectors,
+        } => chu::run(file.as_deref(), format, &selectors),
     }
 }
@@ -0,0 +1,129 @@
+use std::cell::RefCell;
+
+use chrono::{NaiveDate, NaiveDateTime};
+use jaq_core::{Error, Exn, Native, RunPtr, ValR, ValXs, box_iter::box_once, data, load};
+use scraper::{Html, Selector};
+use jaq_json::Val;
+use jaq_std::{Filter, run, unary, v};
+
+type D = data::JustLut<Val>;
+
+/// Definitions of pika functions that are written in jq.
+pub fn defs() -> impl Iterator<Item = load::parse::Def<&'static str>> {
+    load::parse(include_str!("defs.jq"), |p| p.defs())
+        .unwrap()
+        .into_iter()
+}
+
+/// Native pika functions available to every mapping filter.
+pub fn funs() -> impl Iterator<Item = Filter<Native<D>>> {
+    let funs: Box<[Filter<RunPtr<D>>]> = Box::new([
+        ("slugify", v(0), |cv| bome(slugify(&cv.1))),
+        ("to_number", v(0), |cv| bome(to_number(cv.1))),
+        ("parse_date", v(1), |cv| unary(cv, |v, fmt| parse_date(&v, &fmt))),
+        ("css", v(1), |mut cv| {
+            let selector = cv.0.pop_var();
+            match css(&cv.1, &selector) {
+                Ok(texts) => Box::new(texts.into_iter().map(Ok)),
+                Err(e) => bome(Err(e)),
+            }
+        }),
+    ]);
+    funs.into_vec().into_iter().map(run::<D>)
+}
+
+fn bome<'a>(r: ValR<Val>) -> ValXs<'a, Val> {
+    box_once(r.map_err(Exn::from))
+}
+
+fn as_str(v: &Val) -> Result<&str, Error<Val>> {
+    match v {
+        Val::Str(s, _) => {
+            std::str::from_utf8(s).map_err(|_| Error::typ(v.clone(), "UTF-8 string"))
+        }
+        _ => Err(Error::typ(v.clone(), "string")),
+    }
+}
+
+/// Lowercases a string and joins its alphanumeric runs with `-`.
+fn slugify(v: &Val) -> ValR<Val> {
+    let mut slug = String::new();
+    for word in as_str(v)?.split(|c: char| !c.is_alphanumeric()) {
+        if word.is_empty() {
+            continue;
+        }
+        if !slug.is_empty() {
+            slug.push('-');
+        }
+        slug.extend(word.chars().flat_map(char::to_lowercase));
+    }
+
+    Ok(Val::utf8_str(slug))
+}
+
+/// Converts a string such as `" 1,234.5 "` to a number, leaving numbers untouched.
+fn to_number(v: Val) -> ValR<Val> {
+    if let Val::Num(_) = v {
+        return Ok(v);
+    }
+    let s: String = as_str(&v)?
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != ',' && *c != '_')
+        .collect();
+    if let Ok(i) = s.parse::<isize>() {
+        Ok(Val::from(i))
+    } else if let Ok(f) = s.parse::<f64>() {
+        Ok(Val::from(f))
+    } else {
+        Err(Error::str(format!("cannot convert {} to a number", v)))
+    }
+}
+
+/// Parses a date (or date and time) with a chrono format string into ISO 8601.
+fn parse_date(v: &Val, fmt: &Val) -> ValR<Val> {
+    let (s, fmt) = (as_str(v)?, as_str(fmt)?);
+    let iso = if let Ok(dt) = NaiveDateTime::parse_from_str(s, fmt) {
+        dt.format("%Y-%m-%dT%H:%M:%S").to_string()
+    } else {
+        NaiveDate::parse_from_str(s, fmt)
+            .map_err(|e| Error::str(format!("cannot parse {} as a date with {}: {}", v, fmt, e)))?
+            .format("%Y-%m-%d")
+            .to_string()
+    };
+
+    Ok(Val::utf8_str(iso))
+}
+
+thread_local! {
+    /// The HTML string last queried on this thread, parsed, as every record of
+    /// a page queries the same one.
+    static PARSED: RefCell<Option<(String, Html)>> = const { RefCell::new(None) };
+}
+
+/// Keeps a parsed HTML string for `css` to query, so a page parsed to be read
+/// is not parsed again for its records.
+pub(crate) fn cache_html(text: String, html: Html) {
+    PARSED.set(Some((text, html)));
+}
+
+/// Yields the text of every element of an HTML string matching a CSS selector.
+fn css(v: &Val, selector: &Val) -> Result<Vec<Val>, Error<Val>> {
+    let selector = Selector::parse(as_str(selector)?)
+        .map_err(|e| Error::str(format!("invalid CSS selector {}: {}", selector, e)))?;
+    let text = as_str(v)?;
+    PARSED.with_borrow_mut(|parsed| {
+        if parsed.as_ref().is_none_or(|(cached, _)| cached != text) {
+            *parsed = Some((text.to_string(), Html::parse_document(text)));
+        }
+        let (_, html) = parsed.as_ref().expect("parsed above");
+        let texts = html
+            .select(&selector)
+            .map(|element| {
+                let text = element.text().collect::<String>();
+                Val::utf8_str(text.split_whitespace().collect::<Vec<_>>().join(" "))
+            })
+            .collect();
+
+        Ok(texts)
+    })
+}
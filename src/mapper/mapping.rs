@@ -1,8 +1,44 @@
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
 
 use serde::Deserialize;
 
 #[derive(Deserialize)]
 pub struct Mapping {
+    /// jq files whose definitions are shared by all filters, relative to the
+    /// mapping directory.
+    #[serde(default)]
+    pub include: Vec<PathBuf>,
+    /// jq definitions shared by all filters.
+    #[serde(default)]
+    pub defs: String,
+    /// Directory under the data path holding the input, if not named after the schema.
+    pub data: Option<String>,
+    /// Filter that must be truthy for an input to be mapped into this schema.
+    pub when: Option<String>,
+    /// Filter computing the entity id of an input, instead of the file stem.
+    pub id: Option<String>,
+    #[serde(default)]
     pub properties: HashMap<String, HashMap<String, String>>,
+    /// CSS selectors whose matching text in HTML data becomes the property value.
+    #[serde(default)]
+    pub selectors: HashMap<String, HashMap<String, String>>,
+}
+
+impl Mapping {
+    /// Reads the included jq files, relative to `dir`, into the shared definitions.
+    pub fn resolve_includes(&mut self, dir: &Path) -> io::Result<()> {
+        let mut defs = String::new();
+        for path in self.include.drain(..) {
+            defs.push_str(&fs::read_to_string(dir.join(path))?);
+            defs.push('\n');
+        }
+        defs.push_str(&self.defs);
+        self.defs = defs;
+
+        Ok(())
+    }
 }
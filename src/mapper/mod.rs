@@ -1,73 +1,350 @@
+mod funs;
 mod mapping;
 
 use jaq_core::{
     Ctx, Filter,
-    data,
-    load::{Arena, File, Loader},
+    compile, data,
+    load::{self, Arena, File, Lexer, Loader, Parser, lex, parse::Def},
 };
 use jaq_json::Val;
+use std::collections::HashMap;
 
 
-use mapping::Mapping;
+pub(crate) use funs::cache_html;
+pub use mapping::Mapping;
 
 #[derive(thiserror::Error, Debug)]
 pub enum MapperError {
-    #[error("could not load jaq program: {0}")]
-    JaqLoadError(String),
-    #[error("could not compile jaq filter: {0}")]
-    JaqCompileError(String),
-    #[error("jaq filter execution error: {0}")]
-    JaqRunError(String),
+    #[error("could not load filter for {schema}.{property}: {errors}")]
+    JaqLoadError {
+        schema: String,
+        property: String,
+        errors: String,
+    },
+    #[error("could not compile filter for {schema}.{property}: {errors}")]
+    JaqCompileError {
+        schema: String,
+        property: String,
+        errors: String,
+    },
+    #[error("could not compile `{key}` filter: {errors}")]
+    JaqRecordCompileError { key: &'static str, errors: String },
+    #[error("`{key}` filter `{filter}` failed: {error}")]
+    JaqRecordRunError {
+        key: &'static str,
+        filter: String,
+        error: String,
+    },
+    #[error("`id` filter `{filter}` produced {value} instead of a string or number")]
+    InvalidId { filter: String, value: String },
+    #[error("could not parse shared definitions: {errors}")]
+    JaqDefsError { errors: String },
+    #[error("includes must be resolved before creating a mapper: {0:?}")]
+    UnresolvedIncludes(Vec<std::path::PathBuf>),
+    #[error("filter `{filter}` for {schema}.{property} failed: {error}")]
+    JaqRunError {
+        schema: String,
+        property: String,
+        filter: String,
+        error: String,
+    },
+}
+
+/// Describes where `part` starts within `code` as a line and column.
+fn position(code: &str, part: &str) -> String {
+    let offset = (part.as_ptr() as usize).wrapping_sub(code.as_ptr() as usize);
+    if offset >= code.len() {
+        return String::from("end of filter");
+    }
+    let before = &code[..offset];
+    let line = before.matches('\n').count() + 1;
+    let column = before.rsplit('\n').next().unwrap_or_default().chars().count() + 1;
+
+    format!("line {}, column {}", line, column)
+}
+
+fn load_errors(errs: load::Errors<&str, ()>) -> String {
+    let mut messages = Vec::new();
+    for (file, err) in errs {
+        match err {
+            load::Error::Io(errs) => {
+                for (path, e) in errs {
+                    messages.push(format!("could not load module {}: {}", path, e));
+                }
+            }
+            load::Error::Lex(errs) => {
+                for (expect, at) in errs {
+                    messages.push(lex_error(file.code, expect, at));
+                }
+            }
+            load::Error::Parse(errs) => {
+                for (expect, found) in errs {
+                    messages.push(parse_error(file.code, expect.as_str(), found));
+                }
+            }
+        }
+    }
+
+    messages.join("; ")
+}
+
+fn lex_error(code: &str, expect: lex::Expect<&str>, at: &str) -> String {
+    format!("expected {} at {}", expect.as_str(), position(code, at))
+}
+
+fn parse_error(code: &str, expect: &str, found: &str) -> String {
+    format!(
+        "expected {} but found `{}` at {}",
+        expect,
+        found,
+        position(code, found)
+    )
+}
+
+/// Shortens the lifetime of a built-in definition so it can be loaded next to
+/// the definitions of a mapping.
+fn shorten<'s>(def: Def<&'static str>) -> Def<&'s str> {
+    def
+}
+
+/// Parses the definitions shared by all filters of a mapping.
+fn shared_defs(code: &str) -> Result<Vec<Def<&str>>, MapperError> {
+    let tokens = Lexer::new(code).lex().map_err(|errs| MapperError::JaqDefsError {
+        errors: errs
+            .into_iter()
+            .map(|(expect, at)| lex_error(code, expect, at))
+            .collect::<Vec<_>>()
+            .join("; "),
+    })?;
+    Parser::new(&tokens)
+        .parse(|p| p.defs())
+        .map_err(|errs| MapperError::JaqDefsError {
+            errors: errs
+                .into_iter()
+                .map(|(expect, found)| {
+                    parse_error(code, expect.as_str(), found.map_or("", |token| token.as_str()))
+                })
+                .collect::<Vec<_>>()
+                .join("; "),
+        })
+}
+
+fn compile_errors(errs: compile::Errors<&str, ()>) -> String {
+    let mut messages = Vec::new();
+    for (file, errs) in errs {
+        for (name, undefined) in errs {
+            messages.push(format!(
+                "undefined {} {} at {}",
+                undefined.as_str(),
+                name,
+                position(file.code, name)
+            ));
+        }
+    }
+
+    messages.join("; ")
 }
 
 pub struct PropertyFilter {
     pub schema: String,
     pub name: String,
-    pub filter: Filter<data::JustLut<Val>>,
+    pub code: String,
+    pub filter: JqFilter,
+}
+
+/// A filter run once per input record rather than once per property.
+struct RecordFilter {
+    key: &'static str,
+    code: String,
+    filter: JqFilter,
+}
+
+impl RecordFilter {
+    fn first(&self, val: &Val) -> Result<Option<Val>, MapperError> {
+        let ctx = Ctx::<data::JustLut<Val>>::new(&self.filter.lut, jaq_core::Vars::new([]));
+        self.filter
+            .id
+            .run((ctx, val.clone()))
+            .map(jaq_core::unwrap_valr)
+            .next()
+            .transpose()
+            .map_err(|e| MapperError::JaqRecordRunError {
+                key: self.key,
+                filter: self.code.clone(),
+                error: e.to_string(),
+            })
+    }
 }
 
 pub struct Mapper {
+    condition: Option<RecordFilter>,
+    id: Option<RecordFilter>,
     property_filters: Vec<PropertyFilter>,
 }
 
+/// Builds the jq filter that selects text matching a CSS selector from HTML data.
+fn selector_filter(selector: &str) -> String {
+    format!(
+        ".html | css(\"{}\")",
+        selector.replace('\\', "\\\\").replace('"', "\\\"")
+    )
+}
+
+type JqFilter = Filter<data::JustLut<Val>>;
+
+enum CompileError {
+    Load(String),
+    Compile(String),
+}
+
+impl CompileError {
+    fn into_message(self) -> String {
+        match self {
+            CompileError::Load(errors) | CompileError::Compile(errors) => errors,
+        }
+    }
+}
+
+/// Compiles a filter with the jq standard library, the pika functions and the
+/// shared definitions of its mapping.
+fn compile<'s>(
+    arena: &'s Arena,
+    code: &'s str,
+    defs: Vec<Def<&'s str>>,
+) -> Result<JqFilter, CompileError> {
+    let program = File { code, path: () };
+    let prelude = jaq_std::defs()
+        .chain(jaq_json::defs())
+        .chain(funs::defs())
+        .map(shorten)
+        .chain(defs);
+    let loader = Loader::new(prelude);
+    let modules = loader
+        .load(arena, program)
+        .map_err(|e| CompileError::Load(load_errors(e)))?;
+    jaq_core::Compiler::default()
+        .with_funs(jaq_std::funs().chain(jaq_json::funs()).chain(funs::funs()))
+        .compile(modules)
+        .map_err(|e| CompileError::Compile(compile_errors(e)))
+}
+
 impl Mapper {
     pub fn new(mapping: Mapping) -> Result<Self, MapperError> {
+        if !mapping.include.is_empty() {
+            return Err(MapperError::UnresolvedIncludes(mapping.include));
+        }
         let mut property_filters = Vec::new();
         let arena = Arena::default();
 
-        for (schema_name, properties_map) in mapping.properties {
+        let record_filter = |key, code: &Option<String>| -> Result<_, MapperError> {
+            let Some(code) = code else {
+                return Ok(None);
+            };
+            let filter = compile(&arena, code, shared_defs(&mapping.defs)?).map_err(|e| {
+                MapperError::JaqRecordCompileError {
+                    key,
+                    errors: e.into_message(),
+                }
+            })?;
+            Ok(Some(RecordFilter {
+                key,
+                code: code.clone(),
+                filter,
+            }))
+        };
+        let condition = record_filter("when", &mapping.when)?;
+        let id = record_filter("id", &mapping.id)?;
+
+        let selector_filters = mapping.selectors.into_iter().map(|(schema_name, selectors)| {
+            let filters = selectors
+                .into_iter()
+                .map(|(property_name, selector)| (property_name, selector_filter(&selector)))
+                .collect::<HashMap<_, _>>();
+            (schema_name, filters)
+        });
+
+        for (schema_name, properties_map) in mapping.properties.into_iter().chain(selector_filters) {
             for (property_name, filter_string) in properties_map {
-                let program = File {
-                    code: filter_string.as_str(),
-                    path: (),
-                };
-                let loader = Loader::new([]); // Correctly placed inside the loop
-                let modules = loader.load(&arena, program)
-                    .map_err(|e| MapperError::JaqLoadError(format!("{:?}", e)))?;
-                let filter = jaq_core::Compiler::default().compile(modules)
-                    .map_err(|e| MapperError::JaqCompileError(format!("{:?}", e)))?;
+                let filter = compile(&arena, &filter_string, shared_defs(&mapping.defs)?)
+                    .map_err(|e| match e {
+                        CompileError::Load(errors) => MapperError::JaqLoadError {
+                            schema: schema_name.clone(),
+                            property: property_name.clone(),
+                            errors,
+                        },
+                        CompileError::Compile(errors) => MapperError::JaqCompileError {
+                            schema: schema_name.clone(),
+                            property: property_name.clone(),
+                            errors,
+                        },
+                    })?;
 
                 property_filters.push(PropertyFilter {
                     schema: schema_name.clone(),
                     name: property_name,
+                    code: filter_string,
                     filter,
                 });
             }
         }
 
-        Ok(Self { property_filters })
+        Ok(Self {
+            condition,
+            id,
+            property_filters,
+        })
     }
 
+    /// Whether the `when` filter of the mapping, if any, accepts the input.
+    ///
+    /// The input is accepted when the first output of the filter is neither
+    /// `false` nor `null`.
+    pub fn matches(&self, val: &Val) -> Result<bool, MapperError> {
+        let Some(condition) = &self.condition else {
+            return Ok(true);
+        };
+        Ok(!matches!(
+            condition.first(val)?,
+            None | Some(Val::Null | Val::Bool(false))
+        ))
+    }
+
+    /// The entity id computed by the `id` filter of the mapping, if any.
+    pub fn id(&self, val: &Val) -> Result<Option<String>, MapperError> {
+        let Some(id) = &self.id else {
+            return Ok(None);
+        };
+        match id.first(val)? {
+            Some(Val::Str(s, _)) if !s.is_empty() => {
+                Ok(Some(String::from_utf8_lossy(&s).into_owned()))
+            }
+            Some(value @ Val::Num(_)) => Ok(Some(value.to_string())),
+            value => Err(MapperError::InvalidId {
+                filter: id.code.clone(),
+                value: value.map_or_else(|| String::from("no output"), |v| v.to_string()),
+            }),
+        }
+    }
+
+    /// Runs the property filters on an input. Each output of a filter is one
+    /// value of its property, so a filter can give a property many values.
     pub fn run<'a>(&'a self, val: Val) -> impl Iterator<Item = Result<Property, MapperError>> + 'a {
         self.property_filters.iter().flat_map(move |pf| {
             let ctx = Ctx::<data::JustLut<Val>>::new(&pf.filter.lut, jaq_core::Vars::new([]));
             pf.filter.id.run((ctx, val.clone())).map(move |r| {
-                r.map(|value| Property {
-                    schema: pf.schema.clone(),
-                    name: pf.name.clone(),
-                    value,
-                })
-                .map_err(|e| MapperError::JaqRunError(format!("{:?}", e)))
+                jaq_core::unwrap_valr(r)
+                    .map(|value| Property {
+                        schema: pf.schema.clone(),
+                        name: pf.name.clone(),
+                        filter: pf.code.clone(),
+                        value,
+                    })
+                    .map_err(|e| MapperError::JaqRunError {
+                        schema: pf.schema.clone(),
+                        property: pf.name.clone(),
+                        filter: pf.code.clone(),
+                        error: e.to_string(),
+                    })
             })
         })
     }
@@ -76,5 +353,6 @@ impl Mapper {
 pub struct Property {
     pub schema: String,
     pub name: String,
+    pub filter: String,
     pub value: Val,
 }
\ No newline at end of file
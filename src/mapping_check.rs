@@ -0,0 +1,170 @@
+use crate::{input, mapper, parsedir};
+use anyhow::{Context, Result, bail};
+use jaq_json::Val;
+use mapper::{Mapper, Mapping};
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Property values keyed by entity id, property schema and property name.
+type Properties = BTreeMap<(String, String, String), Val>;
+
+/// Runs each mapping against the fixture inputs in its data directory under
+/// `fixtures_path` and compares the produced properties with the expected
+/// output stored next to each input as `<stem>.expected.toml` or
+/// `<stem>.expected.json`.
+///
+/// An expected output is a table of entities by id, each a table of property
+/// schemas, each a table of property values by name. A property given several
+/// values by its filter is expected as an array of them. Inputs are read with
+/// the same options as an import.
+pub fn run(mapping_path: PathBuf, fixtures_path: PathBuf, options: &input::Options) -> Result<()> {
+    let mut cases = 0;
+    let mut failures = 0;
+
+    for result in parsedir::parse(&mapping_path, |s| toml::from_str(s))? {
+        let (schema_name, mut mapping): (String, Mapping) = result?;
+        mapping
+            .resolve_includes(&mapping_path)
+            .with_context(|| format!("could not read includes of mapping {}", schema_name))?;
+
+        let schema_fixtures_path =
+            fixtures_path.join(mapping.data.as_deref().unwrap_or(&schema_name));
+        if !schema_fixtures_path.is_dir() {
+            continue;
+        }
+        let mapper = Mapper::new(mapping)
+            .with_context(|| format!("could not create mapper for schema {}", schema_name))?;
+
+        for path in parsedir::files(&schema_fixtures_path)? {
+            let Some(expected_path) = expected_path(&path) else {
+                continue;
+            };
+            cases += 1;
+
+            let actual = map(&mapper, &path, options)?;
+            let expected = expected(&expected_path)?;
+            let mismatches = compare(&expected, &actual);
+            if mismatches.is_empty() {
+                println!("ok {}", path.display());
+            } else {
+                failures += 1;
+                println!("FAILED {}", path.display());
+                for mismatch in mismatches {
+                    println!("  {}", mismatch);
+                }
+            }
+        }
+    }
+
+    println!("{} passed, {} failed", cases - failures, failures);
+    if failures > 0 {
+        bail!("{} of {} mapping tests failed", failures, cases);
+    }
+
+    Ok(())
+}
+
+/// The expected output for a fixture input, if it has one. Expected outputs
+/// are not inputs themselves.
+fn expected_path(path: &Path) -> Option<PathBuf> {
+    let stem = path.file_stem()?.to_str()?;
+    if stem.ends_with(".expected") {
+        return None;
+    }
+    ["toml", "json"]
+        .into_iter()
+        .map(|extension| path.with_file_name(format!("{}.expected.{}", stem, extension)))
+        .find(|expected_path| expected_path.is_file())
+}
+
+fn map(mapper: &Mapper, path: &Path, options: &input::Options) -> Result<Properties> {
+    let contents = input::read(path)?;
+    let records = input::parse(path, &contents, options)
+        .with_context(|| format!("could not parse {}", path.display()))?;
+
+    let mut values: BTreeMap<(String, String, String), Vec<Val>> = BTreeMap::new();
+    for (id, data) in records {
+        let context = || format!("could not run mapper on {} (id {})", path.display(), id);
+        if !mapper.matches(&data).with_context(context)? {
+            continue;
+        }
+        let computed_id = mapper.id(&data).with_context(context)?;
+        let id = computed_id.as_deref().unwrap_or(&id);
+        for result in mapper.run(data) {
+            let property = result.with_context(context)?;
+            values
+                .entry((id.to_string(), property.schema, property.name))
+                .or_default()
+                .push(property.value);
+        }
+    }
+
+    Ok(values
+        .into_iter()
+        .map(|(key, mut values)| match values.len() {
+            1 => (key, values.remove(0)),
+            _ => (key, Val::Arr(values.into())),
+        })
+        .collect())
+}
+
+fn expected(path: &Path) -> Result<Properties> {
+    let contents =
+        fs::read_to_string(path).with_context(|| format!("could not read {}", path.display()))?;
+    let (_, value) = input::parse(path, &contents, &input::Options::default())
+        .with_context(|| format!("could not parse {}", path.display()))?
+        .into_iter()
+        .next()
+        .with_context(|| format!("no expected output in {}", path.display()))?;
+
+    let mut properties = Properties::new();
+    for (id, schemas) in entries(&value, path)? {
+        for (schema, values) in entries(&schemas, path)? {
+            for (name, value) in entries(&values, path)? {
+                properties.insert((id.clone(), schema.clone(), name), value);
+            }
+        }
+    }
+
+    Ok(properties)
+}
+
+/// The entries of an object in an expected output.
+fn entries(value: &Val, path: &Path) -> Result<Vec<(String, Val)>> {
+    let Val::Obj(map) = value else {
+        bail!("expected a table but found {} in {}", value, path.display());
+    };
+    map.iter()
+        .map(|(key, value)| match key {
+            Val::Str(s, _) => Ok((String::from_utf8_lossy(s).into_owned(), value.clone())),
+            _ => bail!("expected a string key but found {} in {}", key, path.display()),
+        })
+        .collect()
+}
+
+fn compare(expected: &Properties, actual: &Properties) -> Vec<String> {
+    let mut mismatches = Vec::new();
+    for ((id, schema, name), expected_value) in expected {
+        match actual.get(&(id.clone(), schema.clone(), name.clone())) {
+            None => mismatches.push(format!(
+                "{} {}.{}: expected {} but it was not produced",
+                id, schema, name, expected_value
+            )),
+            Some(value) if value != expected_value => mismatches.push(format!(
+                "{} {}.{}: expected {} but got {}",
+                id, schema, name, expected_value, value
+            )),
+            Some(_) => {}
+        }
+    }
+    for ((id, schema, name), value) in actual {
+        if !expected.contains_key(&(id.clone(), schema.clone(), name.clone())) {
+            mismatches.push(format!("{} {}.{}: unexpected {}", id, schema, name, value));
+        }
+    }
+
+    mismatches
+}
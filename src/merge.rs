@@ -0,0 +1,181 @@
+use anyhow::{Context, Result, bail};
+use aykroyd::rusqlite::{Client, Transaction};
+use serde::Serialize;
+use std::{collections::HashMap, path::Path};
+use tracing::{info, instrument};
+
+use crate::{
+    store::{
+        audit::AuditInsert,
+        entity::{
+            DuplicatesQuery, EntityAliasInsert, EntityAliasesUpdate, EntityDelete,
+            EntityEditsDelete, InsertEntityStatement, PropertiesForEntityDelete,
+            PropertyForEntityQuery, PropertyForEntitySchemaInsert, PropertyValuesUpdate,
+        },
+        import::{ImportEntityDelete, ImportEntityRename},
+    },
+    validate::Validator,
+};
+
+/// Splits an entity written as `schema/id`.
+pub fn parse_entity(entity: &str) -> Result<(&str, &str)> {
+    entity
+        .split_once('/')
+        .with_context(|| format!("expected an entity as schema/id but found {}", entity))
+}
+
+/// Merges the entity `loser` into `winner` of the same schema and returns how
+/// many values were moved.
+///
+/// The values of the loser are added to those of the winner, except for a
+/// property holding one value that the winner already has. Values naming the
+/// loser as `schema/id` are made to name the winner, and the loser's id is
+/// kept as an alias of the winner.
+pub fn merge(
+    txn: &mut Transaction,
+    validator: &Validator,
+    schema: &str,
+    winner: &str,
+    loser: &str,
+) -> Result<usize> {
+    if winner == loser {
+        bail!("cannot merge {}/{} into itself", schema, winner);
+    }
+    if txn.execute(&InsertEntityStatement {
+        schema_name: schema,
+        id: winner,
+    })? > 0
+    {
+        bail!("no entity {}/{}", schema, winner);
+    }
+
+    let mut values: HashMap<(String, String), Vec<String>> = HashMap::new();
+    for row in txn.query(&PropertyForEntityQuery { schema, id: winner })? {
+        values
+            .entry((row.property_schema_name, row.property_name))
+            .or_default()
+            .push(row.value);
+    }
+    let mut moved = 0;
+    for row in txn.query(&PropertyForEntityQuery { schema, id: loser })? {
+        let many = validator.many(schema, &row.property_schema_name, &row.property_name);
+        let key = (row.property_schema_name, row.property_name);
+        let winner_values = values.entry(key.clone()).or_default();
+        if winner_values.contains(&row.value) || (!many && !winner_values.is_empty()) {
+            continue;
+        }
+        txn.execute(&PropertyForEntitySchemaInsert {
+            schema,
+            id: winner,
+            property_schema: &key.0,
+            name: &key.1,
+            value: &row.value,
+            position: winner_values.len() as i64,
+            provenance: &row.provenance,
+        })?;
+        winner_values.push(row.value);
+        moved += 1;
+    }
+
+    txn.execute(&PropertiesForEntityDelete { schema, id: loser })?;
+    txn.execute(&EntityEditsDelete { schema, id: loser })?;
+    txn.execute(&ImportEntityRename {
+        schema_name: schema,
+        entity_id: loser,
+        new_id: winner,
+    })?;
+    txn.execute(&ImportEntityDelete {
+        schema_name: schema,
+        entity_id: loser,
+    })?;
+    txn.execute(&PropertyValuesUpdate {
+        value: &format!("{}/{}", schema, loser),
+        new_value: &format!("{}/{}", schema, winner),
+    })?;
+    txn.execute(&EntityAliasesUpdate {
+        schema,
+        id: loser,
+        new_id: winner,
+    })?;
+    if txn.execute(&EntityDelete {
+        schema_name: schema,
+        id: loser,
+    })? == 0
+    {
+        bail!("no entity {}/{}", schema, loser);
+    }
+    txn.execute(&EntityAliasInsert {
+        schema,
+        alias: loser,
+        id: winner,
+    })?;
+
+    Ok(moved)
+}
+
+/// Merges the entity `loser` into `winner`, both written as `schema/id`.
+#[instrument(skip(db_path))]
+pub fn run(db_path: &Path, winner: &str, loser: &str) -> Result<()> {
+    let (schema, winner_id) = parse_entity(winner)?;
+    let (loser_schema, loser_id) = parse_entity(loser)?;
+    if schema != loser_schema {
+        bail!("cannot merge {} into {} of another schema", loser, winner);
+    }
+
+    let mut db = Client::open(db_path)?;
+    let validator = Validator::load(&mut db).context("could not load schemas")?;
+    let mut txn = db.transaction()?;
+    let moved = merge(&mut txn, &validator, schema, winner_id, loser_id)?;
+    txn.execute(&AuditInsert {
+        actor: "cli",
+        action: "merge",
+        target: winner,
+        detail: Some(loser),
+    })?;
+    txn.commit()?;
+    info!("merged {} into {}, moving {} values", loser, winner, moved);
+
+    Ok(())
+}
+
+/// Entities of a schema that may be the same, having the same name.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct Duplicate {
+    pub schema: String,
+    pub value: String,
+    pub ids: Vec<String>,
+}
+
+/// The entities of each schema whose values for properties called `property`
+/// are the same, ignoring case and surrounding space.
+pub fn duplicates(db: &mut Client, property: &str) -> Result<Vec<Duplicate>> {
+    let rows = db.query(&DuplicatesQuery { name: property })?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| {
+            let mut ids: Vec<String> = row.ids.lines().map(String::from).collect();
+            ids.sort();
+            Duplicate {
+                schema: row.schema_name,
+                value: row.value,
+                ids,
+            }
+        })
+        .collect())
+}
+
+/// Prints the candidate duplicates of a database, a line per name.
+pub fn print_duplicates(db_path: &Path, property: &str) -> Result<()> {
+    let mut db = Client::open(db_path)?;
+    for duplicate in duplicates(&mut db, property)? {
+        let entities: Vec<String> = duplicate
+            .ids
+            .iter()
+            .map(|id| format!("{}/{}", duplicate.schema, id))
+            .collect();
+        println!("{}: {}", duplicate.value, entities.join(" "));
+    }
+
+    Ok(())
+}
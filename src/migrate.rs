@@ -0,0 +1,33 @@
+use anyhow::{Context, Result};
+use rusqlite::Connection;
+use tracing::info;
+
+/// The changes to the tables of a database since the first release, in order.
+/// A database records how many it has had as its `user_version`.
+const MIGRATIONS: [&str; 6] = [
+    include_str!("migrations/1_schema.sql"),
+    include_str!("migrations/2_entity.sql"),
+    include_str!("migrations/3_source.sql"),
+    include_str!("migrations/4_document.sql"),
+    include_str!("migrations/5_tables.sql"),
+    include_str!("migrations/6_watch.sql"),
+];
+
+/// The version of the tables created by `schema.sql`.
+pub const VERSION: usize = MIGRATIONS.len();
+
+/// Brings the tables of a database up to [`VERSION`], one migration per
+/// transaction.
+pub fn run(connection: &mut Connection) -> Result<()> {
+    let version: usize = connection.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    for (index, sql) in MIGRATIONS.iter().enumerate().skip(version) {
+        let txn = connection.transaction()?;
+        txn.execute_batch(sql)
+            .with_context(|| format!("could not migrate database to version {}", index + 1))?;
+        txn.pragma_update(None, "user_version", index + 1)?;
+        txn.commit()?;
+        info!("migrated database to version {}", index + 1);
+    }
+
+    Ok(())
+}
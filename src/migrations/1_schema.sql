@@ -0,0 +1,27 @@
+-- [schema] properties gain their constraints and display, enums their
+-- values, and a schema can extend several others
+ALTER TABLE schema_property ADD COLUMN required INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE schema_property ADD COLUMN cardinality TEXT NOT NULL DEFAULT 'one';
+ALTER TABLE schema_property ADD COLUMN label TEXT;
+ALTER TABLE schema_property ADD COLUMN description TEXT;
+ALTER TABLE schema_property ADD COLUMN display_order INTEGER;
+CREATE TABLE schema_property_value (
+    schema_name TEXT NOT NULL,
+    property_name TEXT NOT NULL,
+    value TEXT NOT NULL,
+    PRIMARY KEY(schema_name, property_name, value) FOREIGN KEY(schema_name, property_name) REFERENCES schema_property(schema_name, name)
+);
+CREATE TABLE schema_extend_new (
+    schema_name TEXT NOT NULL,
+    extends TEXT NOT NULL,
+    PRIMARY KEY(schema_name, extends) FOREIGN KEY(schema_name) REFERENCES schema(name)
+);
+INSERT INTO schema_extend_new (schema_name, extends) SELECT schema_name, extends FROM schema_extend;
+DROP TABLE schema_extend;
+ALTER TABLE schema_extend_new RENAME TO schema_extend;
+CREATE TABLE schema_override (
+    schema_name TEXT NOT NULL,
+    property_name TEXT NOT NULL,
+    from_schema TEXT NOT NULL,
+    PRIMARY KEY(schema_name, property_name) FOREIGN KEY(schema_name) REFERENCES schema(name)
+);
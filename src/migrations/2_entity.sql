@@ -0,0 +1,40 @@
+-- [property] a property can have several values, in order, each recording
+-- where it came from, and edits and merges are kept
+CREATE TABLE entity_property_new (
+    entity_schema_name TEXT NOT NULL,
+    entity_id TEXT NOT NULL,
+    property_schema_name TEXT NOT NULL,
+    property_name TEXT NOT NULL,
+    position INTEGER NOT NULL DEFAULT 0,
+    value TEXT NOT NULL,
+    provenance TEXT NOT NULL DEFAULT '',
+    provenance_date TEXT,
+    PRIMARY KEY(
+        entity_schema_name,
+        entity_id,
+        property_schema_name,
+        property_name,
+        position
+    ) FOREIGN KEY(entity_schema_name, entity_id) REFERENCES entity(schema_name, id) FOREIGN KEY(property_schema_name, property_name) REFERENCES schema_property(schema_name, name)
+);
+INSERT INTO entity_property_new (entity_schema_name, entity_id, property_schema_name, property_name, value)
+SELECT entity_schema_name, entity_id, property_schema_name, property_name, value FROM entity_property;
+DROP TABLE entity_property;
+ALTER TABLE entity_property_new RENAME TO entity_property;
+CREATE TABLE entity_edit (
+    id INTEGER,
+    entity_schema_name TEXT NOT NULL,
+    entity_id TEXT NOT NULL,
+    property_schema_name TEXT NOT NULL,
+    edit_date TEXT NOT NULL,
+    previous TEXT NOT NULL,
+    undone BOOLEAN NOT NULL DEFAULT FALSE,
+    PRIMARY KEY(id) FOREIGN KEY(entity_schema_name, entity_id) REFERENCES entity(schema_name, id)
+);
+CREATE TABLE entity_alias (
+    schema_name TEXT NOT NULL,
+    alias TEXT NOT NULL,
+    id TEXT NOT NULL,
+    merge_date TEXT NOT NULL,
+    PRIMARY KEY(schema_name, alias) FOREIGN KEY(schema_name, id) REFERENCES entity(schema_name, id)
+);
@@ -0,0 +1,10 @@
+-- [source] sources are crawled for their main content, over several pages
+-- and with named selectors
+ALTER TABLE source ADD COLUMN main_content BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE source ADD COLUMN max_pages INTEGER NOT NULL DEFAULT 1;
+CREATE TABLE source_selector (
+    source_id INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    selector TEXT NOT NULL,
+    PRIMARY KEY(source_id, name) FOREIGN KEY(source_id) REFERENCES source(id)
+);
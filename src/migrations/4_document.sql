@@ -0,0 +1,58 @@
+-- [document] documents keep their structured content, language and
+-- embeddings, and are indexed by the tokenizer that suits their language.
+-- Documents from before have no language, so they are only indexed by words.
+ALTER TABLE document ADD COLUMN structured TEXT;
+ALTER TABLE document ADD COLUMN language TEXT;
+CREATE TABLE document_embedding (
+    document_id INTEGER NOT NULL,
+    model TEXT NOT NULL,
+    vector BLOB NOT NULL,
+    PRIMARY KEY(document_id, model) FOREIGN KEY(document_id) REFERENCES document(id)
+);
+DROP TABLE fts_document;
+CREATE VIRTUAL TABLE fts_document USING fts5(
+    title,
+    content,
+    content=document,
+    content_rowid=id,
+    tokenize='unicode61 remove_diacritics 2'
+);
+INSERT INTO fts_document(fts_document) VALUES('rebuild');
+CREATE VIRTUAL TABLE fts_document_english USING fts5(
+    title,
+    content,
+    content=document,
+    content_rowid=id,
+    tokenize='porter unicode61 remove_diacritics 2'
+);
+CREATE VIRTUAL TABLE fts_document_cjk USING fts5(
+    title,
+    content,
+    content=document,
+    content_rowid=id,
+    tokenize='trigram'
+);
+CREATE TRIGGER document_english_ai AFTER INSERT ON document WHEN new.language = 'eng' BEGIN
+  INSERT INTO fts_document_english(rowid, title, content) VALUES (new.id, new.title, new.content);
+END;
+CREATE TRIGGER document_english_ad AFTER DELETE ON document WHEN old.language = 'eng' BEGIN
+  INSERT INTO fts_document_english(fts_document_english, rowid, title, content) VALUES('delete', old.id, old.title, old.content);
+END;
+CREATE TRIGGER document_english_au_delete AFTER UPDATE ON document WHEN old.language = 'eng' BEGIN
+  INSERT INTO fts_document_english(fts_document_english, rowid, title, content) VALUES('delete', old.id, old.title, old.content);
+END;
+CREATE TRIGGER document_english_au_insert AFTER UPDATE ON document WHEN new.language = 'eng' BEGIN
+  INSERT INTO fts_document_english(rowid, title, content) VALUES (new.id, new.title, new.content);
+END;
+CREATE TRIGGER document_cjk_ai AFTER INSERT ON document WHEN new.language IN ('cmn', 'jpn', 'kor') BEGIN
+  INSERT INTO fts_document_cjk(rowid, title, content) VALUES (new.id, new.title, new.content);
+END;
+CREATE TRIGGER document_cjk_ad AFTER DELETE ON document WHEN old.language IN ('cmn', 'jpn', 'kor') BEGIN
+  INSERT INTO fts_document_cjk(fts_document_cjk, rowid, title, content) VALUES('delete', old.id, old.title, old.content);
+END;
+CREATE TRIGGER document_cjk_au_delete AFTER UPDATE ON document WHEN old.language IN ('cmn', 'jpn', 'kor') BEGIN
+  INSERT INTO fts_document_cjk(fts_document_cjk, rowid, title, content) VALUES('delete', old.id, old.title, old.content);
+END;
+CREATE TRIGGER document_cjk_au_insert AFTER UPDATE ON document WHEN new.language IN ('cmn', 'jpn', 'kor') BEGIN
+  INSERT INTO fts_document_cjk(rowid, title, content) VALUES (new.id, new.title, new.content);
+END;
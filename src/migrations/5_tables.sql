@@ -0,0 +1,46 @@
+-- tables for imports, schema versions, the audit log, pipelines and saved
+-- searches
+CREATE TABLE import_file (
+    schema_name TEXT NOT NULL,
+    path TEXT NOT NULL,
+    hash TEXT NOT NULL,
+    PRIMARY KEY(schema_name, path) FOREIGN KEY(schema_name) REFERENCES schema(name)
+);
+CREATE TABLE import_entity (
+    schema_name TEXT NOT NULL,
+    path TEXT NOT NULL,
+    entity_id TEXT NOT NULL,
+    PRIMARY KEY(schema_name, path, entity_id) FOREIGN KEY(schema_name, path) REFERENCES import_file(schema_name, path)
+);
+CREATE TABLE schema_version (
+    version INTEGER,
+    applied_date TEXT NOT NULL,
+    PRIMARY KEY(version)
+);
+CREATE TABLE audit (
+    id INTEGER,
+    audit_date TEXT NOT NULL,
+    actor TEXT NOT NULL,
+    action TEXT NOT NULL,
+    target TEXT NOT NULL,
+    detail TEXT,
+    PRIMARY KEY(id)
+);
+CREATE TABLE pipeline_run (
+    id INTEGER,
+    pipeline TEXT NOT NULL,
+    run_date TEXT NOT NULL,
+    document_id INTEGER NOT NULL,
+    error TEXT,
+    PRIMARY KEY(id) FOREIGN KEY(document_id) REFERENCES document(id)
+);
+CREATE TABLE saved_search (
+    name TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    query TEXT NOT NULL,
+    language TEXT,
+    attribute TEXT,
+    notify BOOLEAN NOT NULL DEFAULT FALSE,
+    last_document_id INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY(name)
+);
@@ -0,0 +1,37 @@
+-- the watches of a database, which changes are recorded for until they
+-- expire, with the last change each has seen
+CREATE TABLE watcher (
+    id INTEGER,
+    last_change INTEGER NOT NULL,
+    expire_date TEXT NOT NULL,
+    PRIMARY KEY(id)
+);
+-- changes to property values, recorded while a database is watched; ids are
+-- not reused once the changes are seen and removed
+CREATE TABLE entity_property_change (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    change_date TEXT NOT NULL,
+    op TEXT NOT NULL,
+    entity_schema_name TEXT NOT NULL,
+    entity_id TEXT NOT NULL,
+    property_schema_name TEXT NOT NULL,
+    property_name TEXT NOT NULL,
+    value TEXT NOT NULL
+);
+CREATE TRIGGER entity_property_change_ai AFTER INSERT ON entity_property
+WHEN EXISTS (SELECT 1 FROM watcher WHERE expire_date > strftime('%Y-%m-%dT%H:%M:%SZ', 'now')) BEGIN
+  INSERT INTO entity_property_change (change_date, op, entity_schema_name, entity_id, property_schema_name, property_name, value)
+  VALUES (strftime('%Y-%m-%dT%H:%M:%SZ', 'now'), 'add', new.entity_schema_name, new.entity_id, new.property_schema_name, new.property_name, new.value);
+END;
+CREATE TRIGGER entity_property_change_ad AFTER DELETE ON entity_property
+WHEN EXISTS (SELECT 1 FROM watcher WHERE expire_date > strftime('%Y-%m-%dT%H:%M:%SZ', 'now')) BEGIN
+  INSERT INTO entity_property_change (change_date, op, entity_schema_name, entity_id, property_schema_name, property_name, value)
+  VALUES (strftime('%Y-%m-%dT%H:%M:%SZ', 'now'), 'remove', old.entity_schema_name, old.entity_id, old.property_schema_name, old.property_name, old.value);
+END;
+CREATE TRIGGER entity_property_change_au AFTER UPDATE ON entity_property
+WHEN EXISTS (SELECT 1 FROM watcher WHERE expire_date > strftime('%Y-%m-%dT%H:%M:%SZ', 'now')) BEGIN
+  INSERT INTO entity_property_change (change_date, op, entity_schema_name, entity_id, property_schema_name, property_name, value)
+  VALUES (strftime('%Y-%m-%dT%H:%M:%SZ', 'now'), 'remove', old.entity_schema_name, old.entity_id, old.property_schema_name, old.property_name, old.value);
+  INSERT INTO entity_property_change (change_date, op, entity_schema_name, entity_id, property_schema_name, property_name, value)
+  VALUES (strftime('%Y-%m-%dT%H:%M:%SZ', 'now'), 'add', new.entity_schema_name, new.entity_id, new.property_schema_name, new.property_name, new.value);
+END;
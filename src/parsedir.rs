@@ -1,4 +1,4 @@
-use std::{fs::{self, ReadDir}, io, path::{Path, PathBuf}};
+use std::{convert::Infallible, fs::{self, ReadDir}, io, path::{Path, PathBuf}};
 
 #[derive(thiserror::Error, Debug)]
 pub enum ParseDirError<E> {
@@ -18,6 +18,9 @@ pub enum ParseDirError<E> {
     StemError(PathBuf),
 }
 
+/// A parsed file, identified by its file stem.
+pub type ParseDirItem<T, E> = Result<(String, T), ParseDirError<E>>;
+
 // An iterator that lazily reads and parses files from a directory using a provided parser function.
 pub struct ParseDirIterator<T, F>
 {
@@ -31,10 +34,10 @@ pub struct ParseDirIterator<T, F>
 
 impl<T, F, E> Iterator for ParseDirIterator<T, F>
 where
-    F: Fn(&str) -> Result<T, E>,
+    F: Fn(&Path, &str) -> Result<T, E>,
 {
     // The item is a Result, allowing the user to handle parsing errors file-by-file
-    type Item = Result<(String, T), ParseDirError<E>>;
+    type Item = ParseDirItem<T, E>;
 
     fn next(&mut self) -> Option<Self::Item> {
         // Loop until a valid file is found and parsed, or the directory ends
@@ -55,11 +58,11 @@ where
                         };
                         
                         // Attempt to read and parse the file using the provided parser
-                        let contents = match fs::read_to_string(path) {
+                        let contents = match fs::read_to_string(&path) {
                             Ok(contents) => contents,
                             Err(e) => return Some(Err(ParseDirError::Io(e))),
                         };
-                        match (self.parser)(&contents) {
+                        match (self.parser)(&path, &contents) {
                             Ok(data) => return Some(Ok((file_stem, data))), // Success! Return the parsed data
                             Err(e) => return Some(Err(ParseDirError::FileParse(e))), // Parsing error on this file
                         }
@@ -75,9 +78,21 @@ where
 }
 
 /// Returns an iterator over the parsed configurations in a directory.
-pub fn parse<T, F, E>(dir_path: &Path, parser: F) -> Result<ParseDirIterator<T, F>, ParseDirError<E>>
+pub fn parse<T, F, E>(
+    dir_path: &Path,
+    parser: F,
+) -> Result<impl Iterator<Item = ParseDirItem<T, E>>, ParseDirError<E>>
 where
     F: Fn(&str) -> Result<T, E>,
+{
+    parse_files(dir_path, move |_: &Path, contents: &str| parser(contents))
+}
+
+/// Like [`parse`], but the parser is also given the path of each file, so it
+/// can dispatch on the file extension.
+pub fn parse_files<T, F, E>(dir_path: &Path, parser: F) -> Result<ParseDirIterator<T, F>, ParseDirError<E>>
+where
+    F: Fn(&Path, &str) -> Result<T, E>,
 {
     if !dir_path.is_dir() {
         return Err(ParseDirError::PathError(format!(
@@ -93,4 +108,25 @@ where
         parser,
         _marker: std::marker::PhantomData,
     })
-}
\ No newline at end of file
+}
+
+/// Lists the files of a directory in name order, without reading them.
+pub fn files(dir_path: &Path) -> Result<Vec<PathBuf>, ParseDirError<Infallible>> {
+    if !dir_path.is_dir() {
+        return Err(ParseDirError::PathError(format!(
+            "Path '{}' is not a directory.",
+            dir_path.display()
+        )));
+    }
+
+    let mut paths = Vec::new();
+    for entry in fs::read_dir(dir_path)? {
+        let path = entry?.path();
+        if path.is_file() {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+
+    Ok(paths)
+}
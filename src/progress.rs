@@ -0,0 +1,70 @@
+use indicatif::{ProgressBar, ProgressStyle};
+
+/// Receives the progress of a long operation, such as an import, one stage at
+/// a time. Library users can implement it to report progress their own way.
+pub trait Progress: Send + Sync {
+    /// Starts a stage of `total` items, or of an unknown number of them.
+    fn start(&self, stage: &str, total: Option<u64>);
+    /// Records that `count` more items of the stage are done.
+    fn advance(&self, count: u64);
+    /// Ends the stage.
+    fn finish(&self);
+}
+
+/// Ignores progress.
+pub struct NoProgress;
+
+impl Progress for NoProgress {
+    fn start(&self, _stage: &str, _total: Option<u64>) {}
+
+    fn advance(&self, _count: u64) {}
+
+    fn finish(&self) {}
+}
+
+/// Draws a progress bar on stderr, when it is a terminal.
+pub struct Bar {
+    bar: ProgressBar,
+}
+
+impl Bar {
+    pub fn new() -> Self {
+        Bar {
+            bar: ProgressBar::new(0),
+        }
+    }
+}
+
+impl Default for Bar {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Progress for Bar {
+    fn start(&self, stage: &str, total: Option<u64>) {
+        let template = match total {
+            Some(total) => {
+                self.bar.set_length(total);
+                "{msg} [{bar:40}] {pos}/{len} ({per_sec}, eta {eta})"
+            }
+            None => {
+                self.bar.unset_length();
+                "{spinner} {msg} {pos} ({per_sec})"
+            }
+        };
+        if let Ok(style) = ProgressStyle::with_template(template) {
+            self.bar.set_style(style.progress_chars("=> "));
+        }
+        self.bar.set_message(stage.to_string());
+        self.bar.reset();
+    }
+
+    fn advance(&self, count: u64) {
+        self.bar.inc(count);
+    }
+
+    fn finish(&self) {
+        self.bar.finish_and_clear();
+    }
+}
@@ -0,0 +1,51 @@
+use anyhow::{Context, Result, bail};
+use aykroyd::rusqlite::Client;
+use std::{io::Write, path::Path};
+
+use crate::store::entity::PropertyForEntitySchemaQuery;
+
+/// Writes the values of an attribute of an entity, a line each with where the
+/// value came from and when it was written.
+///
+/// The entity is given as `schema/id` and the attribute as `schema.name`.
+pub fn run(db_path: &Path, entity: &str, attribute: &str, mut out: impl Write) -> Result<()> {
+    let (schema, id) = entity
+        .split_once('/')
+        .with_context(|| format!("expected an entity as schema/id but found {}", entity))?;
+    let (property_schema, name) = attribute.split_once('.').with_context(|| {
+        format!(
+            "expected an attribute as schema.name but found {}",
+            attribute
+        )
+    })?;
+
+    let mut db = Client::open(db_path)?;
+    let rows: Vec<_> = db
+        .query(&PropertyForEntitySchemaQuery {
+            schema,
+            id,
+            property_schema,
+        })?
+        .into_iter()
+        .filter(|row| row.property_name == name)
+        .collect();
+    if rows.is_empty() {
+        bail!("{} has no values for {}", entity, attribute);
+    }
+    for row in rows {
+        let provenance = if row.provenance.is_empty() {
+            "unknown"
+        } else {
+            &row.provenance
+        };
+        writeln!(
+            out,
+            "{}\t{}\t{}",
+            row.value,
+            provenance,
+            row.provenance_date.as_deref().unwrap_or("")
+        )?;
+    }
+
+    Ok(())
+}
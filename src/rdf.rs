@@ -0,0 +1,212 @@
+use anyhow::{Context, Result};
+use aykroyd::rusqlite::Client;
+use std::{
+    collections::HashSet,
+    fmt::Write as _,
+    io::{self, Write},
+    path::Path,
+};
+
+use crate::{store::schema::SchemaNamesQuery, triples};
+
+/// The RDF syntaxes a database can be exported as.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Syntax {
+    /// Triples grouped by subject, with prefixed names.
+    Turtle,
+    /// One triple per line, with full IRIs.
+    NTriples,
+}
+
+/// How entities and attributes are named as IRIs.
+///
+/// The entity `schema/id` becomes `<base>schema/id`, typed as `<base>schema`,
+/// and the attribute `schema.name` becomes `<base>schema#name`.
+pub struct Iris {
+    pub base: String,
+    /// Prefix names and the IRIs they stand for, used for Turtle.
+    pub prefixes: Vec<(String, String)>,
+}
+
+impl Default for Iris {
+    fn default() -> Self {
+        Iris {
+            base: "urn:pika:".to_string(),
+            prefixes: Vec::new(),
+        }
+    }
+}
+
+/// Parses a `name=IRI` prefix.
+pub fn parse_prefix(text: &str) -> Result<(String, String)> {
+    let (name, iri) = text
+        .split_once('=')
+        .with_context(|| format!("expected a prefix as name=IRI but found {}", text))?;
+
+    Ok((name.trim().to_string(), iri.trim().to_string()))
+}
+
+/// Escapes the characters that may not appear in an IRI.
+fn escape_iri(text: &str) -> String {
+    let mut escaped = String::new();
+    for c in text.chars() {
+        if c <= ' ' || "<>\"{}|^`\\%".contains(c) {
+            let mut bytes = [0; 4];
+            for byte in c.encode_utf8(&mut bytes).bytes() {
+                let _ = write!(escaped, "%{:02X}", byte);
+            }
+        } else {
+            escaped.push(c);
+        }
+    }
+
+    escaped
+}
+
+fn escape_literal(text: &str) -> String {
+    let mut escaped = String::new();
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+/// A local name that can be written after a prefix without escaping.
+fn is_simple_local(local: &str) -> bool {
+    local
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_alphanumeric() || c == '_')
+        && !local.ends_with('.')
+        && local
+            .chars()
+            .all(|c| c.is_alphanumeric() || "_-.".contains(c))
+}
+
+impl Iris {
+    fn entity(&self, entity: &str) -> String {
+        format!("{}{}", self.base, escape_iri(entity))
+    }
+
+    fn schema(&self, entity: &str) -> String {
+        let schema = entity.split_once('/').map_or(entity, |(schema, _)| schema);
+        format!("{}{}", self.base, escape_iri(schema))
+    }
+
+    fn attribute(&self, attribute: &str) -> String {
+        let attribute = escape_iri(attribute);
+        match attribute.split_once('.') {
+            Some((schema, name)) => format!("{}{}#{}", self.base, schema, name),
+            None => format!("{}{}", self.base, attribute),
+        }
+    }
+
+    /// An IRI as a Turtle prefixed name when a prefix covers it.
+    fn turtle(&self, iri: &str) -> String {
+        for (name, prefix) in &self.prefixes {
+            if let Some(local) = iri.strip_prefix(prefix.as_str())
+                && is_simple_local(local)
+            {
+                return format!("{}:{}", name, local);
+            }
+        }
+
+        format!("<{}>", iri)
+    }
+}
+
+/// Writes every property value of a database as RDF. Values written as
+/// `schema/id` for a schema of the database are the IRIs of the entities
+/// they name, and others are literals.
+pub fn export(db_path: &Path, syntax: Syntax, iris: &Iris, out: impl Write) -> Result<()> {
+    let mut db = Client::open(db_path)?;
+    let schemas: HashSet<String> = db
+        .query(&SchemaNamesQuery)?
+        .into_iter()
+        .map(|row| row.name)
+        .collect();
+    let is_reference = |value: &str| {
+        value
+            .split_once('/')
+            .is_some_and(|(schema, _)| schemas.contains(schema))
+    };
+    let mut out = io::BufWriter::new(out);
+    match syntax {
+        Syntax::NTriples => {
+            let mut subject = None;
+            triples::for_each(&db, |triple| {
+                if subject.as_ref() != Some(&triple.e) {
+                    writeln!(
+                        out,
+                        "<{}> <http://www.w3.org/1999/02/22-rdf-syntax-ns#type> <{}> .",
+                        iris.entity(&triple.e),
+                        iris.schema(&triple.e)
+                    )?;
+                    subject = Some(triple.e.clone());
+                }
+                let object = if is_reference(&triple.v) {
+                    format!("<{}>", iris.entity(&triple.v))
+                } else {
+                    format!("\"{}\"", escape_literal(&triple.v))
+                };
+                writeln!(
+                    out,
+                    "<{}> <{}> {} .",
+                    iris.entity(&triple.e),
+                    iris.attribute(&triple.a),
+                    object
+                )?;
+                Ok(())
+            })?;
+        }
+        Syntax::Turtle => {
+            for (name, prefix) in &iris.prefixes {
+                writeln!(out, "@prefix {}: <{}> .", name, prefix)?;
+            }
+            if !iris.prefixes.is_empty() {
+                writeln!(out)?;
+            }
+            let mut subject: Option<String> = None;
+            triples::for_each(&db, |triple| {
+                if subject.as_ref() != Some(&triple.e) {
+                    if subject.is_some() {
+                        writeln!(out, " .\n")?;
+                    }
+                    write!(
+                        out,
+                        "{} a {}",
+                        iris.turtle(&iris.entity(&triple.e)),
+                        iris.turtle(&iris.schema(&triple.e))
+                    )?;
+                    subject = Some(triple.e.clone());
+                }
+                let object = if is_reference(&triple.v) {
+                    iris.turtle(&iris.entity(&triple.v))
+                } else {
+                    format!("\"{}\"", escape_literal(&triple.v))
+                };
+                write!(
+                    out,
+                    " ;\n    {} {}",
+                    iris.turtle(&iris.attribute(&triple.a)),
+                    object
+                )?;
+                Ok(())
+            })?;
+            if subject.is_some() {
+                writeln!(out, " .")?;
+            }
+        }
+    }
+    out.flush()?;
+
+    Ok(())
+}
@@ -8,12 +8,29 @@ CREATE TABLE schema_property (
     schema_name TEXT NOT NULL,
     name TEXT NOT NULL,
     type TEXT NOT NULL,
+    required INTEGER NOT NULL DEFAULT 0,
+    cardinality TEXT NOT NULL DEFAULT 'one',
+    label TEXT,
+    description TEXT,
+    display_order INTEGER,
     PRIMARY KEY(schema_name, name) FOREIGN KEY(schema_name) REFERENCES schema(name)
 );
+CREATE TABLE schema_property_value (
+    schema_name TEXT NOT NULL,
+    property_name TEXT NOT NULL,
+    value TEXT NOT NULL,
+    PRIMARY KEY(schema_name, property_name, value) FOREIGN KEY(schema_name, property_name) REFERENCES schema_property(schema_name, name)
+);
 CREATE TABLE schema_extend (
     schema_name TEXT NOT NULL,
     extends TEXT NOT NULL,
-    PRIMARY KEY(schema_name) FOREIGN KEY(schema_name) REFERENCES schema(name)
+    PRIMARY KEY(schema_name, extends) FOREIGN KEY(schema_name) REFERENCES schema(name)
+);
+CREATE TABLE schema_override (
+    schema_name TEXT NOT NULL,
+    property_name TEXT NOT NULL,
+    from_schema TEXT NOT NULL,
+    PRIMARY KEY(schema_name, property_name) FOREIGN KEY(schema_name) REFERENCES schema(name)
 );
 -- [entity]
 CREATE TABLE entity (
@@ -27,22 +44,88 @@ CREATE TABLE entity_property (
     entity_id TEXT NOT NULL,
     property_schema_name TEXT NOT NULL,
     property_name TEXT NOT NULL,
+    position INTEGER NOT NULL DEFAULT 0,
     value TEXT NOT NULL,
+    provenance TEXT NOT NULL DEFAULT '',
+    provenance_date TEXT,
     PRIMARY KEY(
         entity_schema_name,
         entity_id,
         property_schema_name,
-        property_name
+        property_name,
+        position
     ) FOREIGN KEY(entity_schema_name, entity_id) REFERENCES entity(schema_name, id) FOREIGN KEY(property_schema_name, property_name) REFERENCES schema_property(schema_name, name)
 );
+-- the watches of a database, which changes are recorded for until they
+-- expire, with the last change each has seen
+CREATE TABLE watcher (
+    id INTEGER,
+    last_change INTEGER NOT NULL,
+    expire_date TEXT NOT NULL,
+    PRIMARY KEY(id)
+);
+-- changes to property values, recorded while a database is watched; ids are
+-- not reused once the changes are seen and removed
+CREATE TABLE entity_property_change (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    change_date TEXT NOT NULL,
+    op TEXT NOT NULL,
+    entity_schema_name TEXT NOT NULL,
+    entity_id TEXT NOT NULL,
+    property_schema_name TEXT NOT NULL,
+    property_name TEXT NOT NULL,
+    value TEXT NOT NULL
+);
+CREATE TRIGGER entity_property_change_ai AFTER INSERT ON entity_property
+WHEN EXISTS (SELECT 1 FROM watcher WHERE expire_date > strftime('%Y-%m-%dT%H:%M:%SZ', 'now')) BEGIN
+  INSERT INTO entity_property_change (change_date, op, entity_schema_name, entity_id, property_schema_name, property_name, value)
+  VALUES (strftime('%Y-%m-%dT%H:%M:%SZ', 'now'), 'add', new.entity_schema_name, new.entity_id, new.property_schema_name, new.property_name, new.value);
+END;
+CREATE TRIGGER entity_property_change_ad AFTER DELETE ON entity_property
+WHEN EXISTS (SELECT 1 FROM watcher WHERE expire_date > strftime('%Y-%m-%dT%H:%M:%SZ', 'now')) BEGIN
+  INSERT INTO entity_property_change (change_date, op, entity_schema_name, entity_id, property_schema_name, property_name, value)
+  VALUES (strftime('%Y-%m-%dT%H:%M:%SZ', 'now'), 'remove', old.entity_schema_name, old.entity_id, old.property_schema_name, old.property_name, old.value);
+END;
+CREATE TRIGGER entity_property_change_au AFTER UPDATE ON entity_property
+WHEN EXISTS (SELECT 1 FROM watcher WHERE expire_date > strftime('%Y-%m-%dT%H:%M:%SZ', 'now')) BEGIN
+  INSERT INTO entity_property_change (change_date, op, entity_schema_name, entity_id, property_schema_name, property_name, value)
+  VALUES (strftime('%Y-%m-%dT%H:%M:%SZ', 'now'), 'remove', old.entity_schema_name, old.entity_id, old.property_schema_name, old.property_name, old.value);
+  INSERT INTO entity_property_change (change_date, op, entity_schema_name, entity_id, property_schema_name, property_name, value)
+  VALUES (strftime('%Y-%m-%dT%H:%M:%SZ', 'now'), 'add', new.entity_schema_name, new.entity_id, new.property_schema_name, new.property_name, new.value);
+END;
+CREATE TABLE entity_edit (
+    id INTEGER,
+    entity_schema_name TEXT NOT NULL,
+    entity_id TEXT NOT NULL,
+    property_schema_name TEXT NOT NULL,
+    edit_date TEXT NOT NULL,
+    previous TEXT NOT NULL,
+    undone BOOLEAN NOT NULL DEFAULT FALSE,
+    PRIMARY KEY(id) FOREIGN KEY(entity_schema_name, entity_id) REFERENCES entity(schema_name, id)
+);
+CREATE TABLE entity_alias (
+    schema_name TEXT NOT NULL,
+    alias TEXT NOT NULL,
+    id TEXT NOT NULL,
+    merge_date TEXT NOT NULL,
+    PRIMARY KEY(schema_name, alias) FOREIGN KEY(schema_name, id) REFERENCES entity(schema_name, id)
+);
 -- [source]
 CREATE TABLE source (
     id INTEGER,
     url TEXT NOT NULL,
     crawl_date TEXT,
     force_crawl BOOLEAN,
+    main_content BOOLEAN NOT NULL DEFAULT FALSE,
+    max_pages INTEGER NOT NULL DEFAULT 1,
     PRIMARY KEY(id) UNIQUE(url)
 );
+CREATE TABLE source_selector (
+    source_id INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    selector TEXT NOT NULL,
+    PRIMARY KEY(source_id, name) FOREIGN KEY(source_id) REFERENCES source(id)
+);
 -- [document]
 CREATE TABLE document (
     id INTEGER,
@@ -52,13 +135,39 @@ CREATE TABLE document (
     etag TEXT,
     title TEXT,
     content TEXT NOT NULL,
+    structured TEXT,
+    language TEXT,
     PRIMARY KEY(id) FOREIGN KEY(source_id) REFERENCES source(id)
 );
+CREATE TABLE document_embedding (
+    document_id INTEGER NOT NULL,
+    model TEXT NOT NULL,
+    vector BLOB NOT NULL,
+    PRIMARY KEY(document_id, model) FOREIGN KEY(document_id) REFERENCES document(id)
+);
+-- documents are indexed by the tokenizer that suits their language: every
+-- document by words without diacritics, English ones also by word stems and
+-- Chinese, Japanese and Korean ones, written without spaces, by trigrams
 CREATE VIRTUAL TABLE fts_document USING fts5(
     title,
     content,
     content=document,
-    content_rowid=id
+    content_rowid=id,
+    tokenize='unicode61 remove_diacritics 2'
+);
+CREATE VIRTUAL TABLE fts_document_english USING fts5(
+    title,
+    content,
+    content=document,
+    content_rowid=id,
+    tokenize='porter unicode61 remove_diacritics 2'
+);
+CREATE VIRTUAL TABLE fts_document_cjk USING fts5(
+    title,
+    content,
+    content=document,
+    content_rowid=id,
+    tokenize='trigram'
 );
 CREATE TRIGGER document_ai AFTER INSERT ON document BEGIN
   INSERT INTO fts_document(rowid, title, content) VALUES (new.id, new.title, new.content);
@@ -70,3 +179,76 @@ CREATE TRIGGER document_au AFTER UPDATE ON document BEGIN
   INSERT INTO fts_document(fts_document, rowid, title, content) VALUES('delete', old.id, old.title, old.content);
   INSERT INTO fts_document(rowid, title, content) VALUES (new.id, new.title, new.content);
 END;
+CREATE TRIGGER document_english_ai AFTER INSERT ON document WHEN new.language = 'eng' BEGIN
+  INSERT INTO fts_document_english(rowid, title, content) VALUES (new.id, new.title, new.content);
+END;
+CREATE TRIGGER document_english_ad AFTER DELETE ON document WHEN old.language = 'eng' BEGIN
+  INSERT INTO fts_document_english(fts_document_english, rowid, title, content) VALUES('delete', old.id, old.title, old.content);
+END;
+CREATE TRIGGER document_english_au_delete AFTER UPDATE ON document WHEN old.language = 'eng' BEGIN
+  INSERT INTO fts_document_english(fts_document_english, rowid, title, content) VALUES('delete', old.id, old.title, old.content);
+END;
+CREATE TRIGGER document_english_au_insert AFTER UPDATE ON document WHEN new.language = 'eng' BEGIN
+  INSERT INTO fts_document_english(rowid, title, content) VALUES (new.id, new.title, new.content);
+END;
+CREATE TRIGGER document_cjk_ai AFTER INSERT ON document WHEN new.language IN ('cmn', 'jpn', 'kor') BEGIN
+  INSERT INTO fts_document_cjk(rowid, title, content) VALUES (new.id, new.title, new.content);
+END;
+CREATE TRIGGER document_cjk_ad AFTER DELETE ON document WHEN old.language IN ('cmn', 'jpn', 'kor') BEGIN
+  INSERT INTO fts_document_cjk(fts_document_cjk, rowid, title, content) VALUES('delete', old.id, old.title, old.content);
+END;
+CREATE TRIGGER document_cjk_au_delete AFTER UPDATE ON document WHEN old.language IN ('cmn', 'jpn', 'kor') BEGIN
+  INSERT INTO fts_document_cjk(fts_document_cjk, rowid, title, content) VALUES('delete', old.id, old.title, old.content);
+END;
+CREATE TRIGGER document_cjk_au_insert AFTER UPDATE ON document WHEN new.language IN ('cmn', 'jpn', 'kor') BEGIN
+  INSERT INTO fts_document_cjk(rowid, title, content) VALUES (new.id, new.title, new.content);
+END;
+-- [import]
+CREATE TABLE import_file (
+    schema_name TEXT NOT NULL,
+    path TEXT NOT NULL,
+    hash TEXT NOT NULL,
+    PRIMARY KEY(schema_name, path) FOREIGN KEY(schema_name) REFERENCES schema(name)
+);
+CREATE TABLE import_entity (
+    schema_name TEXT NOT NULL,
+    path TEXT NOT NULL,
+    entity_id TEXT NOT NULL,
+    PRIMARY KEY(schema_name, path, entity_id) FOREIGN KEY(schema_name, path) REFERENCES import_file(schema_name, path)
+);
+-- [schema version]
+CREATE TABLE schema_version (
+    version INTEGER,
+    applied_date TEXT NOT NULL,
+    PRIMARY KEY(version)
+);
+-- [audit]
+CREATE TABLE audit (
+    id INTEGER,
+    audit_date TEXT NOT NULL,
+    actor TEXT NOT NULL,
+    action TEXT NOT NULL,
+    target TEXT NOT NULL,
+    detail TEXT,
+    PRIMARY KEY(id)
+);
+-- [pipeline]
+CREATE TABLE pipeline_run (
+    id INTEGER,
+    pipeline TEXT NOT NULL,
+    run_date TEXT NOT NULL,
+    document_id INTEGER NOT NULL,
+    error TEXT,
+    PRIMARY KEY(id) FOREIGN KEY(document_id) REFERENCES document(id)
+);
+-- [saved search]
+CREATE TABLE saved_search (
+    name TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    query TEXT NOT NULL,
+    language TEXT,
+    attribute TEXT,
+    notify BOOLEAN NOT NULL DEFAULT FALSE,
+    last_document_id INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY(name)
+);
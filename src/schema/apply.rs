@@ -0,0 +1,180 @@
+use crate::{
+    init, migrate,
+    store::{
+        audit::AuditInsert,
+        schema::{
+            InsertSchemaVersionStatement, SchemaExtendsQuery, UpdateSchemaPropertyDisplayStatement, SchemaPropertiesQuery,
+            SchemaOverridesQuery, SchemaPropertyRow, SchemaPropertyValuesQuery, SchemaVersionQuery,
+            SchemasQuery,
+        },
+    },
+};
+use anyhow::{Context, Result};
+use aykroyd::rusqlite::Client;
+use rusqlite::Connection;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+use tracing::{info, instrument, warn};
+
+/// Brings the schema tables of a database in line with a schema directory,
+/// after migrating the tables of a database created by an earlier version.
+///
+/// Schemas, properties, enum values, extends and overrides that are new in the directory
+/// are added, and the labels, descriptions and order of properties are
+/// updated. Anything else removed or changed in the directory is only warned
+/// about, so that no data is lost. A new schema version is recorded when
+/// something was added.
+#[instrument(name = "apply", skip_all, fields(schema = %schema_path.display()))]
+pub fn run(db_path: &Path, schema_path: PathBuf) -> Result<()> {
+    let mut connection = Connection::open(db_path)?;
+    migrate::run(&mut connection)?;
+    let mut db: Client = connection.into();
+
+    let schemas: HashMap<String, bool> = db
+        .query(&SchemasQuery)?
+        .into_iter()
+        .map(|row| (row.name, row.abstrct))
+        .collect();
+    let properties: HashMap<(String, String), SchemaPropertyRow> = db
+        .query(&SchemaPropertiesQuery)?
+        .into_iter()
+        .map(|row| ((row.schema_name.clone(), row.name.clone()), row))
+        .collect();
+    let mut values: HashMap<(String, String), Vec<String>> = HashMap::new();
+    for row in db.query(&SchemaPropertyValuesQuery)? {
+        values
+            .entry((row.schema_name, row.property_name))
+            .or_default()
+            .push(row.value);
+    }
+    let mut extends: HashMap<String, Vec<String>> = HashMap::new();
+    for row in db.query(&SchemaExtendsQuery)? {
+        extends.entry(row.schema_name).or_default().push(row.extends);
+    }
+    let overrides: HashMap<(String, String), String> = db
+        .query(&SchemaOverridesQuery)?
+        .into_iter()
+        .map(|row| ((row.schema_name, row.property_name), row.from_schema))
+        .collect();
+
+    let desired = init::load(&schema_path)?;
+    let mut txn = db.transaction()?;
+    let mut changes = 0;
+    for (schema_name, schema) in &desired {
+        let Some(abstrct) = schemas.get(schema_name) else {
+            info!("adding schema {}", schema_name);
+            init::insert_schema(&mut txn, schema_name, schema)?;
+            changes += 1;
+            continue;
+        };
+        if *abstrct != schema.abstrct {
+            warn!("not changing whether schema {} is abstract", schema_name);
+        }
+
+        let schema_properties = schema.properties.iter().flatten();
+        for (name, property) in schema_properties.clone() {
+            let key = (schema_name.clone(), name.clone());
+            let Some(row) = properties.get(&key) else {
+                info!("adding property {}.{}", schema_name, name);
+                init::insert_property(&mut txn, schema_name, name, property)?;
+                changes += 1;
+                continue;
+            };
+            if row.typ != property.typ
+                || row.required != property.required
+                || row.cardinality != property.cardinality
+            {
+                warn!("not changing the definition of property {}.{}", schema_name, name);
+            }
+            if row.label != property.label
+                || row.description != property.description
+                || row.display_order != property.order
+            {
+                info!("updating the display of property {}.{}", schema_name, name);
+                txn.execute(&UpdateSchemaPropertyDisplayStatement {
+                    schema_name,
+                    name,
+                    label: property.label.as_deref(),
+                    description: property.description.as_deref(),
+                    display_order: property.order,
+                })
+                .with_context(|| format!("could not update property {}.{}", schema_name, name))?;
+                changes += 1;
+            }
+
+            let existing = values.get(&key).map_or(&[][..], Vec::as_slice);
+            let wanted = property.values.as_deref().unwrap_or_default();
+            for value in wanted.iter().filter(|value| !existing.contains(value)) {
+                info!("adding value {} to property {}.{}", value, schema_name, name);
+                init::insert_property_value(&mut txn, schema_name, name, value)?;
+                changes += 1;
+            }
+            for value in existing.iter().filter(|value| !wanted.contains(value)) {
+                warn!(
+                    "not removing value {} of property {}.{}",
+                    value, schema_name, name
+                );
+            }
+        }
+        for (property_schema, name) in properties.keys() {
+            if property_schema == schema_name
+                && !schema_properties.clone().any(|(wanted, _)| wanted == name)
+            {
+                warn!("not removing property {}.{}", schema_name, name);
+            }
+        }
+
+        let existing = extends.get(schema_name).map_or(&[][..], Vec::as_slice);
+        let wanted = schema.extends.as_deref().unwrap_or_default();
+        for parent in wanted.iter().filter(|parent| !existing.contains(parent)) {
+            info!("making schema {} extend {}", schema_name, parent);
+            init::insert_extends(&mut txn, schema_name, parent)?;
+            changes += 1;
+        }
+        for parent in existing.iter().filter(|parent| !wanted.contains(parent)) {
+            warn!("not removing extends {} from schema {}", parent, schema_name);
+        }
+
+        for (property_name, from_schema) in &schema.overrides {
+            match overrides.get(&(schema_name.clone(), property_name.clone())) {
+                None => {
+                    info!(
+                        "making schema {} use property {} of {}",
+                        schema_name, property_name, from_schema
+                    );
+                    init::insert_override(&mut txn, schema_name, property_name, from_schema)?;
+                    changes += 1;
+                }
+                Some(existing) if existing != from_schema => warn!(
+                    "not changing the override of property {} for schema {}",
+                    property_name, schema_name
+                ),
+                Some(_) => {}
+            }
+        }
+    }
+    for schema_name in schemas.keys() {
+        if !desired.iter().any(|(wanted, _)| wanted == schema_name) {
+            warn!("not removing schema {}", schema_name);
+        }
+    }
+
+    if changes > 0 {
+        txn.execute(&InsertSchemaVersionStatement)
+            .context("could not record schema version")?;
+        txn.execute(&AuditInsert {
+            actor: "cli",
+            action: "schema_apply",
+            target: &schema_path.display().to_string(),
+            detail: Some(&format!("{} changes", changes)),
+        })?;
+    }
+    txn.commit()?;
+
+    let version = db.query_one(&SchemaVersionQuery)?.version.unwrap_or_default();
+    info!("applied {} changes, schema version {}", changes, version);
+
+    Ok(())
+}
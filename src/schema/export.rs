@@ -0,0 +1,46 @@
+use anyhow::{Context, Result};
+use aykroyd::rusqlite::Client;
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use super::{Schema, json_schema};
+
+/// Writes the schemas of a database as TOML files, one per schema, in the
+/// layout read by `init` and `schema apply`.
+pub fn run(db_path: &Path, dir: PathBuf) -> Result<()> {
+    let mut db = Client::open(db_path)?;
+    let schemas = super::read(&mut db).context("could not read schemas")?;
+
+    write_toml(&dir, &schemas)
+}
+
+/// Writes the schemas of a database as JSON Schema files, one per schema.
+pub fn json_schema(db_path: &Path, dir: PathBuf) -> Result<()> {
+    let mut db = Client::open(db_path)?;
+    let schemas = super::read(&mut db).context("could not read schemas")?;
+
+    fs::create_dir_all(&dir).with_context(|| format!("could not create {}", dir.display()))?;
+    for (name, schema) in schemas {
+        let path = dir.join(json_schema::file_name(&name));
+        let contents = serde_json::to_string_pretty(&json_schema::to_json_schema(&name, &schema))
+            .with_context(|| format!("could not serialize schema {}", name))?;
+        fs::write(&path, contents).with_context(|| format!("could not write {}", path.display()))?;
+    }
+
+    Ok(())
+}
+
+pub(crate) fn write_toml(dir: &Path, schemas: &BTreeMap<String, Schema>) -> Result<()> {
+    fs::create_dir_all(dir).with_context(|| format!("could not create {}", dir.display()))?;
+    for (name, schema) in schemas {
+        let path = dir.join(format!("{}.toml", name));
+        let contents = toml::to_string(schema)
+            .with_context(|| format!("could not serialize schema {}", name))?;
+        fs::write(&path, contents).with_context(|| format!("could not write {}", path.display()))?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,158 @@
+use anyhow::{Context, Result, bail};
+use aykroyd::rusqlite::Client;
+use serde::Serialize;
+use std::{collections::HashSet, path::Path};
+
+use super::{Cardinality, SchemaProperty, Type};
+
+/// The ways the schemas of a database can be printed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    /// Text for reading.
+    #[default]
+    Plain,
+    /// JSON for scripts.
+    Json,
+}
+
+#[derive(Serialize)]
+struct ListedSchema<'a> {
+    name: &'a str,
+    #[serde(rename = "abstract")]
+    abstrct: bool,
+}
+
+#[derive(Serialize)]
+struct ShownSchema<'a> {
+    name: &'a str,
+    #[serde(rename = "abstract")]
+    abstrct: bool,
+    extends: &'a [String],
+    properties: Vec<ShownProperty<'a>>,
+}
+
+#[derive(Serialize)]
+struct ShownProperty<'a> {
+    schema: &'a str,
+    name: &'a str,
+    #[serde(rename = "type")]
+    typ: Type,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    values: Option<&'a [String]>,
+    cardinality: Cardinality,
+    required: bool,
+}
+
+/// Prints the names of the schemas of a database.
+pub fn list(db_path: &Path, format: Format) -> Result<()> {
+    let mut db = Client::open(db_path)?;
+    let schemas = super::read(&mut db).context("could not read schemas")?;
+    if format == Format::Json {
+        let listed: Vec<ListedSchema> = schemas
+            .iter()
+            .map(|(name, schema)| ListedSchema {
+                name,
+                abstrct: schema.abstrct,
+            })
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&listed)?);
+        return Ok(());
+    }
+
+    for (name, schema) in schemas {
+        if schema.abstrct {
+            println!("{} (abstract)", name);
+        } else {
+            println!("{}", name);
+        }
+    }
+
+    Ok(())
+}
+
+/// Prints a schema with its own properties and those it inherits from the
+/// schemas it extends, leaving out definitions it overrides.
+pub fn show(db_path: &Path, name: &str, format: Format) -> Result<()> {
+    let mut db = Client::open(db_path)?;
+    let schemas = super::read(&mut db).context("could not read schemas")?;
+    let Some(root) = schemas.get(name) else {
+        bail!("unknown schema {}", name);
+    };
+
+    // own properties first, then those of each ancestor
+    let mut properties = Vec::new();
+    let mut seen = HashSet::new();
+    let mut pending = vec![name];
+    while let Some(schema_name) = pending.pop() {
+        if !seen.insert(schema_name) {
+            continue;
+        }
+        let Some(schema) = schemas.get(schema_name) else {
+            continue;
+        };
+        for (property_name, property) in schema.properties.iter().flatten() {
+            if root.overrides.get(property_name).is_some_and(|from| from != schema_name) {
+                continue;
+            }
+            properties.push((schema_name, property_name.as_str(), property));
+        }
+        for parent in schema.extends.iter().flatten().rev() {
+            pending.push(parent);
+        }
+    }
+
+    if format == Format::Json {
+        let shown = ShownSchema {
+            name,
+            abstrct: root.abstrct,
+            extends: root.extends.as_deref().unwrap_or_default(),
+            properties: properties
+                .into_iter()
+                .map(|(schema, name, property)| ShownProperty {
+                    schema,
+                    name,
+                    typ: property.typ,
+                    values: property.values.as_deref(),
+                    cardinality: property.cardinality,
+                    required: property.required,
+                })
+                .collect(),
+        };
+        println!("{}", serde_json::to_string_pretty(&shown)?);
+        return Ok(());
+    }
+
+    print!("{}", name);
+    if root.abstrct {
+        print!(" (abstract)");
+    }
+    if let Some(extends) = &root.extends {
+        print!(" extends {}", extends.join(", "));
+    }
+    println!();
+    for (schema_name, property_name, property) in properties {
+        println!(
+            "  {}.{}: {}",
+            schema_name,
+            property_name,
+            describe(property)
+        );
+    }
+
+    Ok(())
+}
+
+fn describe(property: &SchemaProperty) -> String {
+    let mut description = property.typ.to_string();
+    if let Some(values) = &property.values {
+        description.push_str(&format!(" ({})", values.join(", ")));
+    }
+    if property.cardinality == Cardinality::Many {
+        description.push_str(", many");
+    }
+    if property.required {
+        description.push_str(", required");
+    }
+
+    description
+}
@@ -0,0 +1,194 @@
+use anyhow::{Context, Result, bail};
+use serde_json::{Map, Value, json};
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+use tracing::warn;
+
+use super::{Cardinality, Schema, SchemaProperty, Type};
+
+const DIALECT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+/// The file a schema is exported to, which is also how schemas that extend it
+/// refer to it.
+pub(crate) fn file_name(name: &str) -> String {
+    format!("{}.schema.json", name)
+}
+
+/// Converts a schema to a JSON Schema describing one entity as an object.
+///
+/// Name properties become strings and enum properties strings with an `enum`,
+/// held in an array when the property has many values. Extends become `allOf`
+/// references to the exported files of the parents.
+pub fn to_json_schema(name: &str, schema: &Schema) -> Value {
+    let mut properties = Map::new();
+    let mut required = Vec::new();
+    for (property_name, property) in schema.properties.iter().flatten() {
+        let mut value = match property.typ {
+            Type::Name => json!({ "type": "string" }),
+            Type::Enum => json!({
+                "type": "string",
+                "enum": property.values.clone().unwrap_or_default(),
+            }),
+        };
+        if property.cardinality == Cardinality::Many {
+            value = json!({ "type": "array", "items": value });
+        }
+        if let Some(label) = &property.label {
+            value["title"] = json!(label);
+        }
+        if let Some(description) = &property.description {
+            value["description"] = json!(description);
+        }
+        properties.insert(property_name.clone(), value);
+        if property.required {
+            required.push(property_name.clone());
+        }
+    }
+
+    let mut value = json!({
+        "$schema": DIALECT,
+        "$id": file_name(name),
+        "title": name,
+        "type": "object",
+        "properties": properties,
+    });
+    if !required.is_empty() {
+        value["required"] = json!(required);
+    }
+    if let Some(extends) = &schema.extends {
+        value["allOf"] = extends
+            .iter()
+            .map(|parent| json!({ "$ref": file_name(parent) }))
+            .collect();
+    }
+
+    value
+}
+
+/// Converts a JSON Schema document to schemas.
+///
+/// The document is one schema, named by its `title` or else `default_name`,
+/// and each entry of its `$defs` or `definitions` is another. `allOf`
+/// references become extends. String properties become name properties, or
+/// enum properties when they list their values, and arrays of them have many
+/// values. Properties of other types have no pika equivalent and are left out
+/// with a warning.
+pub fn from_json_schema(default_name: &str, document: &Value) -> Result<BTreeMap<String, Schema>> {
+    let mut schemas = BTreeMap::new();
+    for key in ["$defs", "definitions"] {
+        let definitions = document.get(key).and_then(Value::as_object);
+        for (name, value) in definitions.into_iter().flatten() {
+            schemas.insert(name.clone(), convert(name, value)?);
+        }
+    }
+    let name = document
+        .get("title")
+        .and_then(Value::as_str)
+        .unwrap_or(default_name);
+    schemas.insert(name.to_string(), convert(name, document)?);
+
+    Ok(schemas)
+}
+
+fn convert(name: &str, value: &Value) -> Result<Schema> {
+    let Some(object) = value.as_object() else {
+        bail!("JSON Schema for {} is not an object", name);
+    };
+
+    let required: Vec<&str> = object
+        .get("required")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+        .collect();
+    let mut properties = BTreeMap::new();
+    let object_properties = object.get("properties").and_then(Value::as_object);
+    for (property_name, property) in object_properties.into_iter().flatten() {
+        match convert_property(property) {
+            Some(mut converted) => {
+                converted.required = required.contains(&property_name.as_str());
+                properties.insert(property_name.clone(), converted);
+            }
+            None => warn!(
+                "leaving out property {}.{} as its type has no equivalent",
+                name, property_name
+            ),
+        }
+    }
+
+    let extends: Vec<String> = object
+        .get("allOf")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.get("$ref").and_then(Value::as_str))
+        .map(reference_name)
+        .collect();
+
+    Ok(Schema {
+        abstrct: false,
+        extends: (!extends.is_empty()).then_some(extends),
+        properties: (!properties.is_empty()).then_some(properties),
+        overrides: BTreeMap::new(),
+    })
+}
+
+fn convert_property(value: &Value) -> Option<SchemaProperty> {
+    let (item, cardinality) = match value.get("type").and_then(Value::as_str) {
+        Some("array") => (value.get("items")?, Cardinality::Many),
+        _ => (value, Cardinality::One),
+    };
+    let values: Option<Vec<String>> = item.get("enum").and_then(Value::as_array).map(|values| {
+        values
+            .iter()
+            .filter_map(Value::as_str)
+            .map(String::from)
+            .collect()
+    });
+    let typ = match (item.get("type").and_then(Value::as_str), &values) {
+        (Some("string") | None, Some(_)) => Type::Enum,
+        (Some("string"), None) => Type::Name,
+        _ => return None,
+    };
+
+    Some(SchemaProperty {
+        typ,
+        required: false,
+        values,
+        cardinality,
+        label: value.get("title").and_then(Value::as_str).map(String::from),
+        description: value.get("description").and_then(Value::as_str).map(String::from),
+        order: None,
+    })
+}
+
+/// The schema a `$ref` points to, either a definition of the same document or
+/// another exported file.
+fn reference_name(reference: &str) -> String {
+    let name = reference.rsplit(['/', '#']).next().unwrap_or(reference);
+    name.trim_end_matches(".json")
+        .trim_end_matches(".schema")
+        .to_string()
+}
+
+/// Converts a JSON Schema file to a schema directory that `init` and
+/// `schema apply` can read.
+pub fn import(json_schema_path: &Path, dir: PathBuf) -> Result<()> {
+    let contents = fs::read_to_string(json_schema_path)
+        .with_context(|| format!("could not read {}", json_schema_path.display()))?;
+    let document: Value = serde_json::from_str(&contents)
+        .with_context(|| format!("could not parse {}", json_schema_path.display()))?;
+    let default_name = reference_name(
+        &json_schema_path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+    );
+
+    let schemas = from_json_schema(&default_name, &document)?;
+    super::export::write_toml(&dir, &schemas)
+}
@@ -0,0 +1,188 @@
+pub mod apply;
+pub mod export;
+pub mod inspect;
+pub mod json_schema;
+
+use std::{collections::BTreeMap, fmt};
+
+use anyhow::Result;
+use aykroyd::rusqlite::Client;
+
+use rusqlite::{
+    ToSql,
+    types::{FromSql, FromSqlError, FromSqlResult, ValueRef},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::store::schema::{
+    SchemaExtendsQuery, SchemaOverridesQuery, SchemaPropertiesQuery, SchemaPropertyValuesQuery,
+    SchemasQuery,
+};
+
+#[derive(Deserialize, Serialize)]
+pub struct Schema {
+    #[serde(rename = "abstract")]
+    pub abstrct: bool,
+    
+    pub extends: Option<Vec<String>>,
+    pub properties: Option<BTreeMap<String, SchemaProperty>>,
+    /// For properties inherited with conflicting definitions, the schema whose
+    /// definition is used, by property name.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub overrides: BTreeMap<String, String>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct SchemaProperty {
+    #[serde(rename = "type")]
+    pub typ: Type,
+    /// Whether every entity with this property's schema must have a value for it.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub required: bool,
+    /// The values allowed for an enum property.
+    pub values: Option<Vec<String>>,
+    /// Whether an entity holds one value of this property or many.
+    #[serde(default, skip_serializing_if = "Cardinality::is_one")]
+    pub cardinality: Cardinality,
+    /// The name shown for the property instead of its key.
+    pub label: Option<String>,
+    /// Help text shown with the property.
+    pub description: Option<String>,
+    /// Where the property is shown among the properties of its schema, lowest
+    /// first. Properties without an order come last, by name.
+    pub order: Option<i64>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Type {
+    Name,
+    Enum,
+}
+
+impl Type {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Type::Name => "name",
+            Type::Enum => "enum",
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Cardinality {
+    #[default]
+    One,
+    Many,
+}
+
+impl Cardinality {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Cardinality::One => "one",
+            Cardinality::Many => "many",
+        }
+    }
+
+    fn is_one(&self) -> bool {
+        *self == Cardinality::One
+    }
+}
+
+impl ToSql for Cardinality {
+    fn to_sql(&self) -> rusqlite::Result<rusqlite::types::ToSqlOutput<'_>> {
+        Ok(self.as_str().into())
+    }
+}
+
+impl FromSql for Cardinality {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        match value.as_str()? {
+            "one" => Ok(Cardinality::One),
+            "many" => Ok(Cardinality::Many),
+            other => Err(FromSqlError::Other(
+                format!("unknown property cardinality: {}", other).into(),
+            )),
+        }
+    }
+}
+
+impl ToSql for Type {
+    fn to_sql(&self) -> rusqlite::Result<rusqlite::types::ToSqlOutput<'_>> {
+        Ok(self.as_str().into())
+    }
+}
+
+impl FromSql for Type {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        match value.as_str()? {
+            "name" => Ok(Type::Name),
+            "enum" => Ok(Type::Enum),
+            other => Err(FromSqlError::Other(
+                format!("unknown property type: {}", other).into(),
+            )),
+        }
+    }
+}
+
+/// Reads the schemas stored in a database, by name.
+pub fn read(db: &mut Client) -> Result<BTreeMap<String, Schema>> {
+    let mut schemas: BTreeMap<String, Schema> = db
+        .query(&SchemasQuery)?
+        .into_iter()
+        .map(|row| {
+            let schema = Schema {
+                abstrct: row.abstrct,
+                extends: None,
+                properties: None,
+                overrides: BTreeMap::new(),
+            };
+            (row.name, schema)
+        })
+        .collect();
+
+    let mut values: BTreeMap<(String, String), Vec<String>> = BTreeMap::new();
+    for row in db.query(&SchemaPropertyValuesQuery)? {
+        values
+            .entry((row.schema_name, row.property_name))
+            .or_default()
+            .push(row.value);
+    }
+    for row in db.query(&SchemaPropertiesQuery)? {
+        let Some(schema) = schemas.get_mut(&row.schema_name) else {
+            continue;
+        };
+        let property = SchemaProperty {
+            typ: row.typ,
+            required: row.required,
+            values: values.remove(&(row.schema_name, row.name.clone())),
+            cardinality: row.cardinality,
+            label: row.label,
+            description: row.description,
+            order: row.display_order,
+        };
+        schema
+            .properties
+            .get_or_insert_default()
+            .insert(row.name, property);
+    }
+    for row in db.query(&SchemaExtendsQuery)? {
+        if let Some(schema) = schemas.get_mut(&row.schema_name) {
+            schema.extends.get_or_insert_default().push(row.extends);
+        }
+    }
+    for row in db.query(&SchemaOverridesQuery)? {
+        if let Some(schema) = schemas.get_mut(&row.schema_name) {
+            schema.overrides.insert(row.property_name, row.from_schema);
+        }
+    }
+
+    Ok(schemas)
+}
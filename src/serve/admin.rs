@@ -0,0 +1,47 @@
+use std::sync::Arc;
+
+use axum::{extract, response::Html};
+use serde::Deserialize;
+
+use crate::{
+    serve::{AppError, AppState, template_new},
+    store::audit::{AuditActionsQuery, AuditQuery},
+};
+
+#[derive(Deserialize)]
+pub struct AuditFilter {
+    action: Option<String>,
+    target: Option<String>,
+}
+
+/// Text left empty in the filter form matches everything.
+fn non_empty(text: &Option<String>) -> Option<&str> {
+    text.as_deref().map(str::trim).filter(|text| !text.is_empty())
+}
+
+#[axum::debug_handler]
+pub async fn audit(
+    extract::State(state): extract::State<Arc<AppState>>,
+    extract::Query(filter): extract::Query<AuditFilter>,
+) -> Result<Html<String>, AppError> {
+    let mut db = state.db()?;
+    let entries = db.query(&AuditQuery {
+        action: non_empty(&filter.action),
+        target: non_empty(&filter.target),
+    })?;
+    let actions: Vec<String> = db
+        .query(&AuditActionsQuery)?
+        .into_iter()
+        .map(|row| row.action)
+        .collect();
+
+    let tera = template_new()?;
+    let mut context = tera::Context::new();
+    context.insert("entries", &entries);
+    context.insert("actions", &actions);
+    context.insert("action", &non_empty(&filter.action));
+    context.insert("target", &non_empty(&filter.target));
+    let body = tera.render("admin/audit.html", &context)?;
+
+    Ok(Html(body))
+}
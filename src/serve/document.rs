@@ -1,32 +1,101 @@
 use std::sync::Arc;
 
-use axum::{extract, response::Html};
-use serde::Deserialize;
+use axum::{
+    extract,
+    http::{StatusCode, header},
+    response::{Html, IntoResponse, Response},
+};
+use aykroyd::rusqlite::Client;
+use serde::{Deserialize, Serialize};
 
-use crate::{serve::{AppError, AppState, template_new}, store::document::{GetContent, SearchDocuments}};
+use crate::{
+    serve::{AppError, AppState, embedding, template_new},
+    store::document::{
+        DocumentEmbeddings, DocumentLanguages, GetContent, GetSearchDocument, GetStructured,
+        SearchCjkDocuments, SearchDocumentRow, SearchDocuments, SearchDocumentsInLanguage,
+        SearchEnglishDocuments,
+    },
+};
+
+/// How many documents semantic search returns.
+const SEMANTIC_RESULTS: usize = 10;
+
+#[derive(Serialize)]
+struct Language {
+    code: String,
+    name: &'static str,
+}
+
+/// A search to show when the page loads, as linked from a saved search.
+#[derive(Deserialize)]
+pub struct SearchFormQuery {
+    #[serde(default)]
+    search: String,
+    #[serde(default)]
+    language: String,
+}
 
 #[axum::debug_handler]
-pub async fn search_form() -> Result<Html<String>, AppError> {
+pub async fn search_form(
+    extract::State(state): extract::State<Arc<AppState>>,
+    extract::Query(query): extract::Query<SearchFormQuery>,
+) -> Result<Html<String>, AppError> {
+    let languages: Vec<Language> = state
+        .db()?
+        .query(&DocumentLanguages)?
+        .into_iter()
+        .map(|row| Language {
+            name: whatlang::Lang::from_code(&row.0).map_or("Unknown", whatlang::Lang::eng_name),
+            code: row.0,
+        })
+        .collect();
+
     let tera = template_new()?;
-    let context = tera::Context::new();
+    let mut context = tera::Context::new();
+    context.insert("action", "./search");
+    context.insert("languages", &languages);
+    context.insert("search", &query.search);
+    context.insert("selected_language", &query.language);
+    context.insert("saveable", &true);
     let body = tera.render("document/search.html", &context)?;
 
     Ok(Html(body))
 }
 
+/// Searches the documents in a language, with the index that suits it, or
+/// in any language when it is empty.
+pub(crate) fn search_documents(
+    db: &mut Client,
+    search: &str,
+    language: &str,
+) -> anyhow::Result<Vec<SearchDocumentRow>> {
+    let documents = match language {
+        "" => db.query(&SearchDocuments(search))?,
+        "eng" => db.query(&SearchEnglishDocuments(search))?,
+        "cmn" | "jpn" | "kor" => db.query(&SearchCjkDocuments(search, language))?,
+        language => db.query(&SearchDocumentsInLanguage(search, language))?,
+    };
+
+    Ok(documents)
+}
+
 #[derive(Deserialize)]
 pub struct Query {
     search: String,
+    /// The ISO 639-3 code of the language to search documents in, or any
+    /// language when empty.
+    #[serde(default)]
+    language: String,
 }
 #[axum::debug_handler]
 pub async fn search(
     extract::State(state): extract::State<Arc<AppState>>,
     extract::Form(query): extract::Form<Query>,
 ) -> Result<Html<String>, AppError> {
-    let documents = if query.search.trim().len() > 0 {
-        state.db()?.query(&SearchDocuments(&query.search))?
-    } else {
+    let documents = if query.search.trim().is_empty() {
         Vec::new()
+    } else {
+        search_documents(&mut state.db()?, &query.search, &query.language)?
     };
     
     let tera = template_new()?;
@@ -45,4 +114,63 @@ pub async fn content(
     let content = state.db()?.query_one(&GetContent(id))?.0;
 
     Ok(content)
-}
\ No newline at end of file
+}
+
+#[axum::debug_handler]
+pub async fn structured(
+    extract::State(state): extract::State<Arc<AppState>>,
+    extract::Path(id): extract::Path<i64>,
+) -> Result<Response, AppError> {
+    // an unknown document, or one crawled without structured content
+    let Some(structured) = state.db()?.query_opt(&GetStructured(id))?.and_then(|row| row.0) else {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    };
+
+    Ok(([(header::CONTENT_TYPE, "application/json")], structured).into_response())
+}
+
+#[axum::debug_handler]
+pub async fn semantic_search_form() -> Result<Html<String>, AppError> {
+    let tera = template_new()?;
+    let mut context = tera::Context::new();
+    context.insert("action", "./semantic-search");
+    let body = tera.render("document/search.html", &context)?;
+
+    Ok(Html(body))
+}
+
+/// Finds the documents whose embeddings are closest to that of the search, by
+/// comparing it with every stored embedding.
+#[axum::debug_handler]
+pub async fn semantic_search(
+    extract::State(state): extract::State<Arc<AppState>>,
+    extract::Form(query): extract::Form<Query>,
+) -> Result<Response, AppError> {
+    let Some(embedder) = &state.embedder else {
+        return Ok((StatusCode::NOT_FOUND, "semantic search is not configured").into_response());
+    };
+    let mut documents = Vec::new();
+    if !query.search.trim().is_empty() {
+        let search = embedder.embed(&query.search).await?;
+        let mut db = state.db()?;
+        let mut scored: Vec<(f32, i64)> = db
+            .query(&DocumentEmbeddings(&embedder.model))?
+            .into_iter()
+            .map(|row| {
+                let vector = embedding::from_blob(&row.vector);
+                (embedding::cosine(&search, &vector), row.document_id)
+            })
+            .collect();
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+        for (_, id) in scored.into_iter().take(SEMANTIC_RESULTS) {
+            documents.extend(db.query(&GetSearchDocument(id))?);
+        }
+    }
+
+    let tera = template_new()?;
+    let mut context = tera::Context::new();
+    context.insert("documents", &documents);
+    let body = tera.render("document/search_result_partial.html", &context)?;
+
+    Ok(Html(body).into_response())
+}
@@ -0,0 +1,84 @@
+use anyhow::{Context, Result, bail};
+use reqwest::header;
+use serde::{Deserialize, Serialize};
+
+/// An OpenAI-compatible embeddings endpoint, such as OpenAI's own or a local
+/// Ollama at `http://localhost:11434/v1/embeddings`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Embedder {
+    pub url: String,
+    pub model: String,
+    /// Sent as a bearer token, for endpoints that need one.
+    pub api_key: Option<String>,
+}
+
+#[derive(Serialize)]
+struct EmbeddingRequest<'a> {
+    model: &'a str,
+    input: &'a str,
+}
+
+#[derive(Deserialize)]
+struct EmbeddingResponse {
+    data: Vec<Embedding>,
+}
+
+#[derive(Deserialize)]
+struct Embedding {
+    embedding: Vec<f32>,
+}
+
+impl Embedder {
+    /// The embedding vector of a text.
+    pub async fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        let body = serde_json::to_string(&EmbeddingRequest {
+            model: &self.model,
+            input: text,
+        })?;
+        let mut request = reqwest::Client::new()
+            .post(&self.url)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(body);
+        if let Some(api_key) = &self.api_key {
+            request = request.bearer_auth(api_key);
+        }
+        let bytes = request
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .with_context(|| format!("could not get embedding from {}", self.url))?
+            .bytes()
+            .await?;
+        let response: EmbeddingResponse = serde_json::from_slice(&bytes)
+            .with_context(|| format!("could not parse embedding from {}", self.url))?;
+        let Some(embedding) = response.data.into_iter().next() else {
+            bail!("no embedding returned by {}", self.url);
+        };
+
+        Ok(embedding.embedding)
+    }
+}
+
+/// A vector as stored in the database, as little-endian floats.
+pub fn to_blob(vector: &[f32]) -> Vec<u8> {
+    vector.iter().flat_map(|x| x.to_le_bytes()).collect()
+}
+
+pub fn from_blob(blob: &[u8]) -> Vec<f32> {
+    blob.chunks_exact(4)
+        .map(|bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .collect()
+}
+
+/// The cosine similarity of two vectors, or 0 if either is zero or they
+/// differ in length.
+pub fn cosine(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() {
+        return 0.0;
+    }
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norms = norm(a) * norm(b);
+    if norms == 0.0 { 0.0 } else { dot / norms }
+}
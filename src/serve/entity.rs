@@ -1,32 +1,164 @@
 pub(crate) use anyhow::Result;
-use axum::{extract, response::Html};
-use std::{collections::HashMap, sync::Arc};
+use aykroyd::rusqlite::Transaction;
+use axum::{
+    extract,
+    response::{Html, Redirect},
+};
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
 
 use crate::{
+    merge,
+    schema::{self, Cardinality, Schema},
     serve::{AppError, AppState, template_new},
-    store::entity::{PropertyForEntityQuery, PropertyForEntitySchemaDelete, PropertyForEntitySchemaInsert, PropertyForEntitySchemaQuery, PropertyRow, PropertyForSchemaRow},
+    validate::Validator,
+    store::{
+        audit::AuditInsert,
+        entity::{
+        EntitiesWithValueQuery, EntityEditInsert, EntityEditUndone, EntityEditsQuery, PropertyForEntityQuery,
+        PropertyForEntitySchemaDelete, PropertyForEntitySchemaInsert,
+        PropertyForEntitySchemaQuery, PropertyForSchemaRow, PropertyRow,
+        },
+    },
 };
 
+/// A property as shown in the entity templates.
+#[derive(Serialize)]
+struct Field {
+    name: String,
+    label: String,
+    description: Option<String>,
+    required: bool,
+    options: Option<Vec<String>>,
+    values: Vec<String>,
+    /// Where each value came from and when, as shown when viewing.
+    provenance: Vec<String>,
+}
+
+/// Where a value came from and when it was written, or nothing for a value
+/// written before provenance was recorded.
+fn describe_provenance(provenance: String, date: Option<String>) -> String {
+    match date {
+        Some(date) if !provenance.is_empty() => format!("{} on {}", provenance, date),
+        _ => provenance,
+    }
+}
+
+/// The fields for the properties of one property schema, in display order.
+///
+/// When editing, every property of the schema gets a field, with an empty
+/// value for a property that has none and for another value of a property
+/// that holds many.
+fn fields(
+    schemas: &BTreeMap<String, Schema>,
+    property_schema: &str,
+    mut values: HashMap<String, Vec<String>>,
+    mut provenance: HashMap<String, Vec<String>>,
+    editing: bool,
+) -> Vec<Field> {
+    let declared = schemas
+        .get(property_schema)
+        .and_then(|schema| schema.properties.as_ref());
+
+    let mut fields = Vec::new();
+    for (name, property) in declared.into_iter().flatten() {
+        let mut field_values = values.remove(name).unwrap_or_default();
+        if !editing && field_values.is_empty() {
+            continue;
+        }
+        if editing && (field_values.is_empty() || property.cardinality == Cardinality::Many) {
+            field_values.push(String::new());
+        }
+        let field = Field {
+            name: name.clone(),
+            label: property.label.clone().unwrap_or_else(|| name.clone()),
+            description: property.description.clone(),
+            required: property.required,
+            options: property.values.clone(),
+            values: field_values,
+            provenance: provenance.remove(name).unwrap_or_default(),
+        };
+        fields.push((property.order, field));
+    }
+    // properties the schema does not declare are still shown
+    for (name, values) in values {
+        let field = Field {
+            label: name.clone(),
+            provenance: provenance.remove(&name).unwrap_or_default(),
+            name,
+            description: None,
+            required: false,
+            options: None,
+            values,
+        };
+        fields.push((None, field));
+    }
+
+    fields.sort_by(|(a_order, a), (b_order, b)| {
+        (a_order.is_none(), a_order, &a.name).cmp(&(b_order.is_none(), b_order, &b.name))
+    });
+    fields.into_iter().map(|(_, field)| field).collect()
+}
+
+/// Groups the values of properties by property name, in order.
+fn group_values(rows: Vec<PropertyForSchemaRow>) -> HashMap<String, Vec<String>> {
+    let mut properties: HashMap<String, Vec<String>> = HashMap::new();
+    for row in rows {
+        properties.entry(row.property_name).or_default().push(row.value);
+    }
+    properties
+}
+
+/// Groups where the values of properties came from by property name, in the
+/// order of the values.
+fn group_provenance(rows: &[PropertyForSchemaRow]) -> HashMap<String, Vec<String>> {
+    let mut provenance: HashMap<String, Vec<String>> = HashMap::new();
+    for row in rows {
+        provenance
+            .entry(row.property_name.clone())
+            .or_default()
+            .push(describe_provenance(row.provenance.clone(), row.provenance_date.clone()));
+    }
+    provenance
+}
+
 #[axum::debug_handler]
 pub async fn edit(
     extract::State(state): extract::State<Arc<AppState>>,
     extract::Path((schema, id)): extract::Path<(String, String)>,
 ) -> Result<Html<String>, AppError> {
+    let mut db = state.db()?;
     let properties_vec: Vec<PropertyRow> =
-        state.db()?.query(&PropertyForEntityQuery { schema: &schema, id: &id })?;
-    let mut properties: HashMap<String, HashMap<String, String>> = HashMap::new();
+        db.query(&PropertyForEntityQuery { schema: &schema, id: &id })?;
+    type Grouped = HashMap<String, Vec<String>>;
+    let mut properties: BTreeMap<String, (Grouped, Grouped)> = BTreeMap::new();
     for row in properties_vec {
-        properties
-            .entry(row.property_schema_name)
+        let (values, provenance) = properties.entry(row.property_schema_name).or_default();
+        values.entry(row.property_name.clone()).or_default().push(row.value);
+        provenance
+            .entry(row.property_name)
             .or_default()
-            .insert(row.property_name, row.value);
+            .push(describe_provenance(row.provenance, row.provenance_date));
     }
 
+    let schemas = schema::read(&mut db)?;
+    let property_schemas: BTreeMap<String, Vec<Field>> = properties
+        .into_iter()
+        .map(|(property_schema, (values, provenance))| {
+            let fields = fields(&schemas, &property_schema, values, provenance, false);
+            (property_schema, fields)
+        })
+        .collect();
+
     let tera = template_new()?;
     let mut context = tera::Context::new();
     context.insert("schema", &schema);
     context.insert("id", &id);
-    context.insert("properties", &properties);
+    context.insert("property_schemas", &property_schemas);
     let body = tera.render("entity/edit.html", &context)?;
 
     Ok(Html(body))
@@ -37,27 +169,56 @@ pub async fn properties_edit_partial(
     extract::State(state): extract::State<Arc<AppState>>,
     extract::Path((schema, id, property_schema)): extract::Path<(String, String, String)>,
 ) -> Result<Html<String>, AppError> {
-    let properties_vec: Vec<PropertyForSchemaRow> = state
-        .db()?
-        .query(&PropertyForEntitySchemaQuery {
-            schema: &schema,
-            id: &id,
-            property_schema: &property_schema,
-        })?;
-    let mut properties: HashMap<String, String> = HashMap::new();
-    for row in properties_vec {
-        properties.insert(row.property_name, row.value);
-    }
+    let mut db = state.db()?;
+    let properties_vec: Vec<PropertyForSchemaRow> = db.query(&PropertyForEntitySchemaQuery {
+        schema: &schema,
+        id: &id,
+        property_schema: &property_schema,
+    })?;
+    let properties = group_values(properties_vec);
+
+    let schemas = schema::read(&mut db)?;
+    let body = render_properties_edit(&schemas, &schema, &id, &property_schema, properties, &[])?;
 
+    Ok(Html(body))
+}
+
+fn render_properties_edit(
+    schemas: &BTreeMap<String, Schema>,
+    schema: &str,
+    id: &str,
+    property_schema: &str,
+    properties: HashMap<String, Vec<String>>,
+    errors: &[String],
+) -> Result<String> {
     let tera = template_new()?;
     let mut context = tera::Context::new();
-    context.insert("schema", &schema);
-    context.insert("id", &id);
-    context.insert("property_schema", &property_schema);
-    context.insert("properties", &properties);
-    let body = tera.render("entity/properties_edit_partial.html", &context)?;
+    context.insert("schema", schema);
+    context.insert("id", id);
+    context.insert("property_schema", property_schema);
+    context.insert("fields", &fields(schemas, property_schema, properties, HashMap::new(), true));
+    context.insert("errors", errors);
 
-    Ok(Html(body))
+    Ok(tera.render("entity/properties_edit_partial.html", &context)?)
+}
+
+fn render_properties_view(
+    schemas: &BTreeMap<String, Schema>,
+    schema: &str,
+    id: &str,
+    property_schema: &str,
+    rows: Vec<PropertyForSchemaRow>,
+) -> Result<String> {
+    let provenance = group_provenance(&rows);
+    let properties = group_values(rows);
+    let tera = template_new()?;
+    let mut context = tera::Context::new();
+    context.insert("schema", schema);
+    context.insert("id", id);
+    context.insert("property_schema", property_schema);
+    context.insert("fields", &fields(schemas, property_schema, properties, provenance, false));
+
+    Ok(tera.render("entity/properties_view_partial.html", &context)?)
 }
 
 #[axum::debug_handler]
@@ -65,23 +226,15 @@ pub async fn properties_view_partial(
     extract::State(state): extract::State<Arc<AppState>>,
     extract::Path((schema, id, property_schema)): extract::Path<(String, String, String)>,
 ) -> Result<Html<String>, AppError> {
-    let properties_vec: Vec<PropertyForSchemaRow> = state.db()?.query(&PropertyForEntitySchemaQuery {
+    let mut db = state.db()?;
+    let properties_vec: Vec<PropertyForSchemaRow> = db.query(&PropertyForEntitySchemaQuery {
         schema: &schema,
         id: &id,
         property_schema: &property_schema,
     })?;
-    let mut properties: HashMap<String, String> = HashMap::new();
-    for row in properties_vec {
-        properties.insert(row.property_name, row.value);
-    }
 
-    let tera = template_new()?;
-    let mut context = tera::Context::new();
-    context.insert("schema", &schema);
-    context.insert("id", &id);
-    context.insert("property_schema", &property_schema);
-    context.insert("properties", &properties);
-    let body = tera.render("entity/properties_view_partial.html", &context)?;
+    let schemas = schema::read(&mut db)?;
+    let body = render_properties_view(&schemas, &schema, &id, &property_schema, properties_vec)?;
 
     Ok(Html(body))
 }
@@ -89,29 +242,255 @@ pub async fn properties_view_partial(
 pub async fn properties_save_partial(
     extract::State(state): extract::State<Arc<AppState>>,
     extract::Path((schema, id, property_schema)): extract::Path<(String, String, String)>,
-    extract::Form(properties_form): extract::Form<HashMap<String, String>>,
+    extract::Form(properties_form): extract::Form<Vec<(String, String)>>,
 ) -> Result<Html<String>, AppError> {
     let mut db = state.db()?;
-    let mut txn = db.transaction()?;
-    txn.execute(&PropertyForEntitySchemaDelete { schema: &schema, id: &id, property_schema: &property_schema })?;
+    let validator = Validator::load(&mut db)?;
+    let schemas = schema::read(&mut db)?;
+
+    // fields left empty are not saved
+    let mut properties: HashMap<String, Vec<String>> = HashMap::new();
     for (name, value) in properties_form {
-        txn.execute(&PropertyForEntitySchemaInsert { schema: &schema, id: &id, property_schema: &property_schema, name: &name, value: &value })?;
+        let values = properties.entry(name).or_default();
+        if !value.trim().is_empty() {
+            values.push(value);
+        }
+    }
+
+    let mut errors: Vec<String> = validator
+        .required(&schema, &property_schema)
+        .filter(|name| properties.get(*name).is_none_or(Vec::is_empty))
+        .map(|name| format!("{} is required", name))
+        .collect();
+    for (name, values) in &properties {
+        for value in values {
+            if let Err(e) = validator.validate_text(&schema, &property_schema, name, value) {
+                errors.push(e.to_string());
+            }
+        }
+    }
+    if !errors.is_empty() {
+        let body = render_properties_edit(
+            &schemas,
+            &schema,
+            &id,
+            &property_schema,
+            properties,
+            &errors,
+        )?;
+        return Ok(Html(body));
     }
+
+    let mut txn = db.transaction()?;
+    // keep the values being replaced, so that the edit can be undone
+    let previous: BTreeMap<String, Vec<String>> = group_values(txn.query(&PropertyForEntitySchemaQuery { schema: &schema, id: &id, property_schema: &property_schema })?)
+        .into_iter()
+        .collect();
+    txn.execute(&EntityEditInsert {
+        schema: &schema,
+        id: &id,
+        property_schema: &property_schema,
+        edit_date: &Local::now().to_rfc3339(),
+        previous: &serde_json::to_string(&previous)?,
+    })?;
+    replace_values(&mut txn, &schema, &id, &property_schema, properties)?;
+    txn.execute(&AuditInsert {
+        actor: "web",
+        action: "entity_save",
+        target: &format!("{}/{}", schema, id),
+        detail: Some(&property_schema),
+    })?;
     txn.commit()?;
 
     let properties_vec: Vec<PropertyForSchemaRow> = db.query(&PropertyForEntitySchemaQuery { schema: &schema, id: &id, property_schema: &property_schema })?;
-    let mut properties: HashMap<String, String> = HashMap::new();
-    for row in properties_vec {
-        properties.insert(row.property_name, row.value);
+    let body = render_properties_view(&schemas, &schema, &id, &property_schema, properties_vec)?;
+
+    Ok(Html(body))
+}
+/// Replaces the values of the properties of one property schema of an entity.
+///
+/// Values that were already there keep where they came from, others are
+/// recorded as edited on the web.
+fn replace_values(
+    txn: &mut Transaction,
+    schema: &str,
+    id: &str,
+    property_schema: &str,
+    properties: impl IntoIterator<Item = (String, Vec<String>)>,
+) -> Result<()> {
+    let mut provenances: HashMap<(String, String), String> = HashMap::new();
+    for row in txn.query(&PropertyForEntitySchemaQuery { schema, id, property_schema })? {
+        provenances.insert((row.property_name, row.value), row.provenance);
     }
+    txn.execute(&PropertyForEntitySchemaDelete { schema, id, property_schema })?;
+    for (name, values) in properties {
+        for (position, value) in values.iter().enumerate() {
+            let provenance = provenances
+                .remove(&(name.clone(), value.clone()))
+                .unwrap_or_else(|| "edit:web".to_string());
+            txn.execute(&PropertyForEntitySchemaInsert {
+                schema,
+                id,
+                property_schema,
+                name: &name,
+                value,
+                position: position as i64,
+                provenance: &provenance,
+            })?;
+        }
+    }
+
+    Ok(())
+}
+
+/// An edit of an entity as shown in its audit page.
+#[derive(Serialize)]
+struct Edit {
+    property_schema: String,
+    edit_date: String,
+    previous: BTreeMap<String, Vec<String>>,
+    undone: bool,
+}
+
+#[axum::debug_handler]
+pub async fn audit(
+    extract::State(state): extract::State<Arc<AppState>>,
+    extract::Path((schema, id)): extract::Path<(String, String)>,
+) -> Result<Html<String>, AppError> {
+    let mut db = state.db()?;
+    let edits = db
+        .query(&EntityEditsQuery { schema: &schema, id: &id })?
+        .into_iter()
+        .map(|row| {
+            Ok(Edit {
+                property_schema: row.property_schema_name,
+                edit_date: row.edit_date,
+                previous: serde_json::from_str(&row.previous)?,
+                undone: row.undone,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
 
     let tera = template_new()?;
     let mut context = tera::Context::new();
     context.insert("schema", &schema);
     context.insert("id", &id);
-    context.insert("property_schema", &property_schema);
-    context.insert("properties", &properties);
-    let body = tera.render("entity/properties_view_partial.html", &context)?;
+    context.insert("edits", &edits);
+    let body = tera.render("entity/audit.html", &context)?;
+
+    Ok(Html(body))
+}
+
+/// Puts back the values replaced by the latest edit of an entity that has not
+/// been undone yet.
+#[axum::debug_handler]
+pub async fn undo(
+    extract::State(state): extract::State<Arc<AppState>>,
+    extract::Path((schema, id)): extract::Path<(String, String)>,
+) -> Result<Redirect, AppError> {
+    let mut db = state.db()?;
+    let mut txn = db.transaction()?;
+    let edits = txn.query(&EntityEditsQuery { schema: &schema, id: &id })?;
+    if let Some(edit) = edits.into_iter().find(|edit| !edit.undone) {
+        let previous: BTreeMap<String, Vec<String>> = serde_json::from_str(&edit.previous)?;
+        replace_values(&mut txn, &schema, &id, &edit.property_schema_name, previous)?;
+        txn.execute(&EntityEditUndone(edit.id))?;
+        txn.execute(&AuditInsert {
+            actor: "web",
+            action: "entity_undo",
+            target: &format!("{}/{}", schema, id),
+            detail: Some(&edit.property_schema_name),
+        })?;
+    }
+    txn.commit()?;
+
+    Ok(Redirect::to("./edit"))
+}
+
+#[derive(Deserialize)]
+pub struct DuplicatesQuery {
+    /// The name of the properties compared, `name` when not given.
+    property: Option<String>,
+}
+
+#[axum::debug_handler]
+pub async fn duplicates(
+    extract::State(state): extract::State<Arc<AppState>>,
+    extract::Query(query): extract::Query<DuplicatesQuery>,
+) -> Result<Html<String>, AppError> {
+    let property = query.property.as_deref().unwrap_or("name");
+    let duplicates = merge::duplicates(&mut state.db()?, property)?;
+
+    let tera = template_new()?;
+    let mut context = tera::Context::new();
+    context.insert("property", property);
+    context.insert("duplicates", &duplicates);
+    let body = tera.render("entity/duplicates.html", &context)?;
+
+    Ok(Html(body))
+}
+
+#[derive(Deserialize)]
+pub struct Merge {
+    schema: String,
+    winner: String,
+    loser: String,
+}
+
+#[axum::debug_handler]
+pub async fn merge(
+    extract::State(state): extract::State<Arc<AppState>>,
+    extract::Form(form): extract::Form<Merge>,
+) -> Result<Redirect, AppError> {
+    let mut db = state.db()?;
+    let validator = Validator::load(&mut db)?;
+    let mut txn = db.transaction()?;
+    merge::merge(&mut txn, &validator, &form.schema, &form.winner, &form.loser)?;
+    txn.execute(&AuditInsert {
+        actor: "web",
+        action: "merge",
+        target: &format!("{}/{}", form.schema, form.winner),
+        detail: Some(&format!("{}/{}", form.schema, form.loser)),
+    })?;
+    txn.commit()?;
+
+    Ok(Redirect::to(&format!("/entity/{}/{}/edit", form.schema, form.winner)))
+}
+
+#[derive(Deserialize)]
+pub struct FilterQuery {
+    /// The property to filter on as `schema.name`.
+    #[serde(default)]
+    attribute: String,
+    #[serde(default)]
+    value: String,
+}
+
+#[axum::debug_handler]
+pub async fn filter(
+    extract::State(state): extract::State<Arc<AppState>>,
+    extract::Query(query): extract::Query<FilterQuery>,
+) -> Result<Html<String>, AppError> {
+    let entities: Vec<String> = match query.attribute.split_once('.') {
+        Some((property_schema, name)) if !query.value.is_empty() => state
+            .db()?
+            .query(&EntitiesWithValueQuery {
+                property_schema,
+                name,
+                value: &query.value,
+            })?
+            .into_iter()
+            .map(|row| format!("{}/{}", row.schema_name, row.id))
+            .collect(),
+        _ => Vec::new(),
+    };
+
+    let tera = template_new()?;
+    let mut context = tera::Context::new();
+    context.insert("attribute", &query.attribute);
+    context.insert("value", &query.value);
+    context.insert("entities", &entities);
+    let body = tera.render("entity/filter.html", &context)?;
 
     Ok(Html(body))
 }
@@ -1,5 +1,11 @@
+pub mod admin;
 pub mod document;
+pub mod embedding;
 pub mod entity;
+pub mod notify;
+pub mod pipeline;
+pub mod search;
+pub mod table;
 pub mod source;
 
 use anyhow::{Context, Result};
@@ -13,9 +19,17 @@ use aykroyd::rusqlite::Client;
 use mime_guess::from_path;
 use reqwest::header;
 use rust_embed::Embed;
-use std::{path::PathBuf, sync::Arc};
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Component, Path, PathBuf},
+    sync::{Arc, OnceLock},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 use tera::Tera;
-use tracing::info;
+use tracing::{info, warn};
+
+use crate::store::search::SavedSearches;
 
 #[derive(Embed)]
 #[folder = "$CARGO_MANIFEST_DIR/templates/"]
@@ -25,8 +39,18 @@ struct Templates;
 #[folder = "$CARGO_MANIFEST_DIR/static/"]
 struct StaticFiles;
 
+/// The directory whose `templates` and `static` directories hold files used
+/// instead of the embedded ones of the same name, when one is configured.
+static THEME: OnceLock<PathBuf> = OnceLock::new();
+
 pub struct AppState {
     pub db_path: PathBuf,
+    /// Where crawl events are posted.
+    pub webhooks: Vec<String>,
+    /// Embeds crawled documents for semantic search, when configured.
+    pub embedder: Option<embedding::Embedder>,
+    /// Import sources with mappings after they are crawled.
+    pub pipelines: Vec<pipeline::Pipeline>,
 }
 
 impl AppState {
@@ -58,11 +82,31 @@ where
 }
 
 #[tokio::main]
-pub async fn run(db_path: PathBuf) -> Result<()> {
-    let state = AppState { db_path };
+pub async fn run(
+    db_path: PathBuf,
+    port: u16,
+    webhooks: Vec<String>,
+    embedder: Option<embedding::Embedder>,
+    pipelines: Vec<pipeline::Pipeline>,
+    theme: Option<PathBuf>,
+) -> Result<()> {
+    if let Some(theme) = theme {
+        info!("using templates and static files of {}", theme.display());
+        let _ = THEME.set(theme);
+    }
+    let state = AppState {
+        db_path,
+        webhooks,
+        embedder,
+        pipelines,
+    };
     let app = Router::new()
         .route("/", get(index))
+        .route("/entity/duplicates", get(entity::duplicates))
+        .route("/entity/merge", post(entity::merge))
         .route("/entity/{schema}/{id}/edit", get(entity::edit))
+        .route("/entity/{schema}/{id}/audit", get(entity::audit))
+        .route("/entity/{schema}/{id}/undo", post(entity::undo))
         .route(
             "/entity/{schema}/{id}/{property_schema}",
             get(entity::properties_view_partial),
@@ -79,46 +123,185 @@ pub async fn run(db_path: PathBuf) -> Result<()> {
         .route("/source", post(source::add))
         .route("/source/add", get(source::add_form))
         .route("/source/list", get(source::list))
+        .route("/source/export", get(source::export))
+        .route("/source/import", get(source::import_form))
+        .route("/source/import", post(source::import))
         .route("/source/crawl", post(source::crawl))
         .route("/document/search", get(document::search_form))
         .route("/document/search", post(document::search))
+        .route("/document/semantic-search", get(document::semantic_search_form))
+        .route("/document/semantic-search", post(document::semantic_search))
         .route("/document/content/{id}", get(document::content))
+        .route("/document/structured/{id}", get(document::structured))
+        .route("/pipeline", get(pipeline::index))
+        .route("/import/table", get(table::upload_form))
+        .route("/import/table/preview", post(table::preview))
+        .route("/import/table", post(table::import))
+        .route("/entity/filter", get(entity::filter))
+        .route("/search/save", post(search::save))
+        .route("/search/delete", post(search::delete))
+        .route("/admin/audit", get(admin::audit))
         .route("/static/{*path}", get(static_file))
         .with_state(Arc::new(state));
-    let addr = format!("0.0.0.0:{}", 8080);
+    let addr = format!("0.0.0.0:{}", port);
     let listener = tokio::net::TcpListener::bind(&addr)
         .await
         .with_context(|| format!("could not listen on {}", addr))?;
 
     info!("Serving at http://{}/", addr);
+    // requests in flight, such as crawls and imports started from the web
+    // UI, are finished before the server stops
     axum::serve(listener, app)
+        .with_graceful_shutdown(async {
+            shutdown_signal().await;
+            info!("stopping once requests in flight are finished");
+        })
         .await
         .with_context(|| "could not start server")?;
+    info!("stopped serving");
+
+    Ok(())
+}
+
+/// Waits for ctrl-c or, on Unix, SIGTERM, as sent by container runtimes to
+/// stop a service.
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            warn!("could not listen for ctrl-c: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                warn!("could not listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+}
+
+/// An interval lengthened or shortened by up to a tenth, so that crawlers
+/// started together do not keep fetching at the same moment.
+fn jittered(interval: Duration) -> Duration {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |now| now.subsec_nanos());
+    // a fraction from -0.1 to 0.1
+    let fraction = (nanos as f64 / 1e9 - 0.5) / 5.0;
+
+    interval.mul_f64(1.0 + fraction)
+}
+
+/// Crawls the stale sources without serving, once or, with an interval, until
+/// stopped with ctrl-c or SIGTERM. A crawl in progress when stopped is
+/// finished first. A failed crawl is logged and tried again at the next
+/// interval.
+#[tokio::main]
+pub async fn crawl(state: AppState, interval: Option<Duration>) -> Result<()> {
+    let Some(interval) = interval else {
+        return source::crawl_stale(&state).await;
+    };
+
+    let stop = shutdown_signal();
+    tokio::pin!(stop);
+    loop {
+        let crawl = source::crawl_stale(&state);
+        tokio::pin!(crawl);
+        let mut stopping = false;
+        let result = tokio::select! {
+            result = &mut crawl => result,
+            _ = &mut stop => {
+                info!("stopping once the crawl in progress is finished");
+                stopping = true;
+                crawl.await
+            }
+        };
+        if let Err(e) = result {
+            warn!("crawl failed: {:#}", e);
+        }
+        if stopping {
+            break;
+        }
+        let delay = jittered(interval);
+        info!("next crawl in {}s", delay.as_secs());
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => {}
+            _ = &mut stop => break,
+        }
+    }
+    info!("stopped crawling");
 
     Ok(())
 }
 
 fn template_new() -> Result<Tera> {
-    let mut templates: Vec<(String, String)> = Vec::new();
+    let mut templates: BTreeMap<String, String> = BTreeMap::new();
     // Iterate over the files in the embedded directory.
     for filename in Templates::iter() {
         if let Some(file) = Templates::get(&filename) {
             let bytes = file.data.as_ref();
             let str = String::from_utf8(bytes.to_vec())?;
-            templates.push((String::from(filename), str));
+            templates.insert(String::from(filename), str);
         }
     }
+    // templates of the theme are read on every request, so that they can be
+    // changed without restarting
+    if let Some(theme) = THEME.get() {
+        theme_templates(&theme.join("templates"), "", &mut templates)?;
+    }
 
     let mut tera = Tera::default();
     tera.add_raw_templates(templates)
-        .with_context(|| format!("Error loading templates"))?;
+        .context("Error loading templates")?;
     Ok(tera)
 }
 
+/// Reads the templates under a directory of the theme by their path
+/// relative to it, replacing embedded templates of the same name.
+fn theme_templates(dir: &Path, prefix: &str, templates: &mut BTreeMap<String, String>) -> Result<()> {
+    if !dir.is_dir() {
+        return Ok(());
+    }
+    for entry in fs::read_dir(dir).with_context(|| format!("could not read {}", dir.display()))? {
+        let path = entry?.path();
+        let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        let name = format!("{}{}", prefix, name);
+        if path.is_dir() {
+            theme_templates(&path, &format!("{}/", name), templates)?;
+        } else {
+            let text = fs::read_to_string(&path)
+                .with_context(|| format!("could not read {}", path.display()))?;
+            templates.insert(name, text);
+        }
+    }
+
+    Ok(())
+}
+
 #[axum::debug_handler]
-async fn index() -> Result<Html<String>, AppError> {
+async fn index(
+    extract::State(state): extract::State<Arc<AppState>>,
+) -> Result<Html<String>, AppError> {
+    let searches = state.db()?.query(&SavedSearches)?;
+
     let tera = template_new()?;
-    let context = tera::Context::new();
+    let mut context = tera::Context::new();
+    context.insert("searches", &searches);
     let body = tera.render("index.html", &context)?;
 
     Ok(Html(body))
@@ -127,8 +310,21 @@ async fn index() -> Result<Html<String>, AppError> {
 #[axum::debug_handler]
 async fn static_file(uri: extract::Path<String>) -> Response {
     let path = uri.as_str();
+    let mime_type = from_path(path).first_or_octet_stream();
+    // files of the theme are looked up by a path that stays under it
+    let relative = Path::new(path);
+    if let Some(theme) = THEME.get()
+        && relative.components().all(|component| matches!(component, Component::Normal(_)))
+        && let Ok(content) = fs::read(theme.join("static").join(relative))
+    {
+        return (
+            StatusCode::OK,
+            [(header::CONTENT_TYPE, mime_type.as_ref())],
+            content,
+        )
+            .into_response();
+    }
     if let Some(content) = StaticFiles::get(path) {
-        let mime_type = from_path(path).first_or_octet_stream();
         (
             StatusCode::OK,
             [(header::CONTENT_TYPE, mime_type.as_ref())],
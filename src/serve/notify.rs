@@ -0,0 +1,67 @@
+use std::time::Duration;
+
+use reqwest::header;
+use serde::Serialize;
+use tracing::warn;
+
+/// How long a webhook has to answer before it is given up on, so that a slow
+/// one does not hold up the crawl.
+pub const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// What the crawler tells webhooks about.
+#[derive(Serialize, Debug)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event<'a> {
+    /// A source was crawled and its content differs from the last crawl.
+    DocumentChanged {
+        source_id: i64,
+        url: &'a str,
+        hash: &'a str,
+        title: Option<&'a str>,
+    },
+    /// A crawl of the stale sources finished.
+    CrawlFinished { sources: usize, changed: usize },
+    /// A saved search found documents it had not found before.
+    SearchMatched {
+        search: &'a str,
+        documents: &'a [i64],
+    },
+}
+
+/// Posts an event as JSON to each webhook, such as an ntfy topic URL. A
+/// webhook that fails is logged and does not stop the others.
+pub async fn notify(webhooks: &[String], event: &Event<'_>) {
+    if webhooks.is_empty() {
+        return;
+    }
+    let body = match serde_json::to_string(event) {
+        Ok(body) => body,
+        Err(e) => {
+            warn!("could not serialize {:?}: {}", event, e);
+            return;
+        }
+    };
+    let client = match reqwest::Client::builder()
+        .connect_timeout(TIMEOUT)
+        .timeout(TIMEOUT)
+        .build()
+    {
+        Ok(client) => client,
+        Err(e) => {
+            warn!("could not build a client for webhooks: {}", e);
+            return;
+        }
+    };
+    for webhook in webhooks {
+        let result = client
+            .post(webhook)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(body.clone())
+            .send()
+            .await
+            .and_then(|response| response.error_for_status());
+        if let Err(e) = result {
+            warn!("could not notify {}: {}", webhook, e);
+        }
+    }
+}
@@ -0,0 +1,137 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::{Context, Result};
+use axum::{extract, response::Html};
+use serde::{Deserialize, Serialize};
+use tracing::{info, instrument};
+
+use crate::{
+    import,
+    serve::{AppError, AppState, template_new},
+    store::pipeline::LatestPipelineRuns,
+};
+
+/// Imports the pages of a source with a mapping each time it is crawled.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Pipeline {
+    pub name: String,
+    /// The URL of the source.
+    pub source: String,
+    /// The extension the crawled pages are saved with, which chooses how
+    /// they are read: `html`, `json`, `jsonl`, `csv` or `xml`.
+    #[serde(default = "default_format")]
+    pub format: String,
+    /// The data directory the pages are saved under.
+    pub data: PathBuf,
+    /// The mapping directory.
+    pub mapping: PathBuf,
+    /// The schema whose mapping the pages are imported with.
+    pub schema: String,
+    /// The directory under the data directory that the pages are saved to,
+    /// the schema name when not set.
+    pub dir: Option<String>,
+    /// Warn instead of failing on properties that do not match the schema.
+    #[serde(default)]
+    pub lenient: bool,
+}
+
+fn default_format() -> String {
+    "html".to_string()
+}
+
+impl Pipeline {
+    /// Saves the pages of a crawl as `<name>.<format>`, with the page number
+    /// after the name for pages after the first, and imports the schema's
+    /// data directory, where unchanged pages are skipped. Pages saved by an
+    /// earlier crawl that had more are removed. The values are
+    /// recorded as coming from the crawled document.
+    #[instrument(skip_all, fields(pipeline = %self.name))]
+    pub fn run(&self, db_path: &Path, document_id: i64, pages: &[String]) -> Result<()> {
+        let page_path = self.data.join(self.dir.as_deref().unwrap_or(&self.schema));
+        fs::create_dir_all(&page_path)
+            .with_context(|| format!("could not create {}", page_path.display()))?;
+        // a crawl with fewer pages than the last leaves none of its pages behind
+        for entry in fs::read_dir(&page_path)
+            .with_context(|| format!("could not read {}", page_path.display()))?
+        {
+            let path = entry?.path();
+            let Some(file_name) = path.file_name().and_then(|file_name| file_name.to_str()) else {
+                continue;
+            };
+            let number = file_name
+                .strip_prefix(&format!("{}-", self.name))
+                .and_then(|rest| rest.strip_suffix(&format!(".{}", self.format)))
+                .and_then(|number| number.parse::<usize>().ok());
+            if number.is_some_and(|number| number > pages.len()) {
+                fs::remove_file(&path)
+                    .with_context(|| format!("could not remove {}", path.display()))?;
+            }
+        }
+        for (number, page) in pages.iter().enumerate() {
+            let file_name = match number {
+                0 => format!("{}.{}", self.name, self.format),
+                _ => format!("{}-{}.{}", self.name, number + 1, self.format),
+            };
+            let path = page_path.join(file_name);
+            fs::write(&path, page)
+                .with_context(|| format!("could not write {}", path.display()))?;
+        }
+
+        info!("importing {} pages", pages.len());
+        import::run(
+            db_path,
+            self.data.clone(),
+            self.mapping.clone(),
+            &import::Options {
+                lenient: self.lenient,
+                schema: Some(self.schema.clone()),
+                actor: Some("pipeline"),
+                provenance: Some(format!("document:{}", document_id)),
+                ..Default::default()
+            },
+        )
+    }
+}
+
+#[derive(Serialize)]
+struct PipelineStatus<'a> {
+    pipeline: &'a Pipeline,
+    run_date: Option<String>,
+    document_id: Option<i64>,
+    error: Option<String>,
+}
+
+#[axum::debug_handler]
+pub async fn index(
+    extract::State(state): extract::State<Arc<AppState>>,
+) -> Result<Html<String>, AppError> {
+    let mut runs = state.db()?.query(&LatestPipelineRuns)?;
+    let pipelines: Vec<PipelineStatus> = state
+        .pipelines
+        .iter()
+        .map(|pipeline| {
+            let run = runs
+                .iter()
+                .position(|run| run.pipeline == pipeline.name)
+                .map(|index| runs.swap_remove(index));
+            PipelineStatus {
+                pipeline,
+                run_date: run.as_ref().map(|run| run.run_date.clone()),
+                document_id: run.as_ref().map(|run| run.document_id),
+                error: run.and_then(|run| run.error),
+            }
+        })
+        .collect();
+
+    let tera = template_new()?;
+    let mut context = tera::Context::new();
+    context.insert("pipelines", &pipelines);
+    let body = tera.render("pipeline/index.html", &context)?;
+
+    Ok(Html(body))
+}
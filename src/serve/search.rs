@@ -0,0 +1,148 @@
+use std::sync::Arc;
+
+use anyhow::Context;
+use axum::{
+    extract,
+    response::{Html, Redirect},
+};
+use aykroyd::rusqlite::Client;
+use serde::Deserialize;
+use tracing::warn;
+
+use crate::{
+    serve::{
+        AppError, AppState,
+        document::search_documents,
+        notify::{Event, notify},
+        template_new,
+    },
+    store::{
+        audit::AuditInsert,
+        search::{SavedSearchDelete, SavedSearchInsert, SavedSearchSeen, SavedSearches},
+    },
+};
+
+#[derive(Deserialize)]
+pub struct Save {
+    name: String,
+    /// `document` or `entity`.
+    kind: String,
+    /// The full-text search, or the value of the entity filter.
+    search: String,
+    #[serde(default)]
+    language: String,
+    attribute: Option<String>,
+    notify: Option<String>,
+}
+
+/// Text left empty in a form is not stored.
+fn non_empty(text: &str) -> Option<&str> {
+    Some(text.trim()).filter(|text| !text.is_empty())
+}
+
+#[axum::debug_handler]
+pub async fn save(
+    extract::State(state): extract::State<Arc<AppState>>,
+    extract::Form(save): extract::Form<Save>,
+) -> Result<Html<String>, AppError> {
+    let name = save.name.trim();
+    if name.is_empty() || !["document", "entity"].contains(&save.kind.as_str()) {
+        return Err(anyhow::anyhow!("a saved search needs a name and a kind").into());
+    }
+
+    let mut db = state.db()?;
+    let mut txn = db.transaction()?;
+    txn.execute(&SavedSearchInsert {
+        name,
+        kind: &save.kind,
+        query: &save.search,
+        language: non_empty(&save.language),
+        attribute: save.attribute.as_deref().and_then(non_empty),
+        notify: save.notify.is_some(),
+    })?;
+    txn.execute(&AuditInsert {
+        actor: "web",
+        action: "search_save",
+        target: name,
+        detail: Some(&save.search),
+    })?;
+    txn.commit()?;
+
+    let tera = template_new()?;
+    let mut context = tera::Context::new();
+    context.insert("name", name);
+    let body = tera.render("search/saved_partial.html", &context)?;
+
+    Ok(Html(body))
+}
+
+#[derive(Deserialize)]
+pub struct Delete {
+    name: String,
+}
+
+#[axum::debug_handler]
+pub async fn delete(
+    extract::State(state): extract::State<Arc<AppState>>,
+    extract::Form(delete): extract::Form<Delete>,
+) -> Result<Redirect, AppError> {
+    let mut db = state.db()?;
+    let mut txn = db.transaction()?;
+    txn.execute(&SavedSearchDelete(&delete.name))?;
+    txn.execute(&AuditInsert {
+        actor: "web",
+        action: "search_delete",
+        target: &delete.name,
+        detail: None,
+    })?;
+    txn.commit()?;
+
+    Ok(Redirect::to("/"))
+}
+
+/// Tells webhooks of the documents that saved document searches with
+/// notification find and had not found before. A search that cannot run is
+/// logged and does not stop the others.
+pub async fn notify_saved_searches(state: &AppState) -> anyhow::Result<()> {
+    let mut db = Client::open(&state.db_path)?;
+    let searches = db.query(&SavedSearches)?;
+    for saved in searches
+        .into_iter()
+        .filter(|saved| saved.notify && saved.kind == "document")
+    {
+        let language = saved.language.as_deref().unwrap_or_default();
+        let found = search_documents(&mut db, &saved.query, language)
+            .with_context(|| format!("could not run saved search {}", saved.name));
+        let found = match found {
+            Ok(found) => found,
+            Err(e) => {
+                warn!("{:#}", e);
+                continue;
+            }
+        };
+        let mut documents: Vec<i64> = found
+            .into_iter()
+            .map(|document| document.id)
+            .filter(|id| *id > saved.last_document_id)
+            .collect();
+        documents.sort();
+        let Some(&latest) = documents.last() else {
+            continue;
+        };
+
+        notify(
+            &state.webhooks,
+            &Event::SearchMatched {
+                search: &saved.name,
+                documents: &documents,
+            },
+        )
+        .await;
+        db.execute(&SavedSearchSeen {
+            name: &saved.name,
+            last_document_id: latest,
+        })?;
+    }
+
+    Ok(())
+}
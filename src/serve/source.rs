@@ -1,19 +1,33 @@
-use std::sync::Arc;
+use std::{collections::HashSet, sync::Arc};
 
-use axum::{extract, response::Html};
+use axum::{
+    extract,
+    http::StatusCode,
+    response::{Html, IntoResponse, Response},
+};
 use chrono::Local;
 use reqwest::header;
 use serde::Deserialize;
 use sha2::{Digest, Sha256};
-use tracing::{info, warn};
+use tracing::{info, instrument, warn};
 use anyhow::Context;
+use aykroyd::rusqlite::Client;
 
 use crate::{
-    chu,
-    serve::{AppError, AppState, template_new},
+    chu, source as sources,
+    serve::{
+        AppError, AppState, embedding, search,
+        notify::{Event, notify},
+        template_new,
+    },
     store::{
-        document::AddDocument,
-        source::{AddSource, Sources, StaleSources, UpdateCrawlDate},
+        audit::AuditInsert,
+        document::{AddDocument, AddDocumentEmbedding, LatestDocumentHash},
+        pipeline::PipelineRunInsert,
+        source::{
+            AddSourceSelector, SourceSelectors, SourceSelectorsDelete, Sources, StaleSourceRow,
+            StaleSources, UpdateCrawlDate, UpsertSource,
+        },
     },
 };
 
@@ -58,13 +72,58 @@ pub async fn add_form(
 #[derive(Deserialize)]
 pub struct Source {
     url: String,
+    /// One `name = selector` per line, extracted instead of every table.
+    #[serde(default)]
+    selectors: String,
+    /// Whether to store the main text of pages rather than their tables.
+    main_content: Option<String>,
+    /// How many pages to follow `next` links through, including the first.
+    max_pages: i64,
 }
 #[axum::debug_handler]
 pub async fn add(
     extract::State(state): extract::State<Arc<AppState>>,
     extract::Form(source): extract::Form<Source>,
-) -> Result<Html<String>, AppError> {
-    state.db()?.execute(&AddSource(&source.url))?;
+) -> Result<Response, AppError> {
+    let mut selectors: Vec<chu::NamedSelector> = Vec::new();
+    for line in source.selectors.lines().filter(|line| !line.trim().is_empty()) {
+        let named = match chu::NamedSelector::parse(line) {
+            Ok(named) => named,
+            Err(e) => return Ok((StatusCode::BAD_REQUEST, format!("{:#}", e)).into_response()),
+        };
+        if selectors.iter().any(|other| other.name == named.name) {
+            return Ok((
+                StatusCode::BAD_REQUEST,
+                format!("the selector {} is given more than once", named.name),
+            )
+                .into_response());
+        }
+        selectors.push(named);
+    }
+
+    // adding a source that exists replaces how it is crawled
+    let mut db = state.db()?;
+    let mut txn = db.transaction()?;
+    txn.execute(&UpsertSource(
+        &source.url,
+        source.main_content.is_some(),
+        source.max_pages.clamp(1, sources::MAX_PAGES),
+    ))?;
+    txn.execute(&SourceSelectorsDelete(&source.url))?;
+    for named in &selectors {
+        txn.execute(&AddSourceSelector {
+            url: &source.url,
+            name: &named.name,
+            selector: &named.selector,
+        })?;
+    }
+    txn.execute(&AuditInsert {
+        actor: "web",
+        action: "source_add",
+        target: &source.url,
+        detail: None,
+    })?;
+    txn.commit()?;
     
     let sources = state.db()?.query(&Sources)?;
 
@@ -73,57 +132,95 @@ pub async fn add(
     context.insert("sources", &sources);
     let body = tera.render("source/list_partial.html", &context)?;
 
+    Ok(Html(body).into_response())
+}
+
+#[derive(Deserialize)]
+pub struct ExportFormat {
+    #[serde(default)]
+    format: sources::Format,
+}
+
+#[axum::debug_handler]
+pub async fn export(
+    extract::State(state): extract::State<Arc<AppState>>,
+    extract::Query(query): extract::Query<ExportFormat>,
+) -> Result<impl IntoResponse, AppError> {
+    let text = sources::to_string(&sources::read(&mut state.db()?)?, query.format)?;
+    let (content_type, file_name) = match query.format {
+        sources::Format::Toml => ("application/toml", "sources.toml"),
+        sources::Format::Opml => ("text/x-opml", "sources.opml"),
+    };
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, content_type.to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", file_name),
+            ),
+        ],
+        text,
+    ))
+}
+
+#[axum::debug_handler]
+pub async fn import_form(
+) -> Result<Html<String>, AppError> {
+    let tera = template_new()?;
+    let context = tera::Context::new();
+    let body = tera.render("source/import_partial.html", &context)?;
+
     Ok(Html(body))
 }
 
+#[derive(Deserialize)]
+pub struct Import {
+    format: sources::Format,
+    /// The sources as exported.
+    sources: String,
+}
+
 #[axum::debug_handler]
-pub async fn crawl(
+pub async fn import(
     extract::State(state): extract::State<Arc<AppState>>,
+    extract::Form(import): extract::Form<Import>,
 ) -> Result<Html<String>, AppError> {
+    let imported = sources::from_str(&import.sources, import.format)?;
+
     let mut db = state.db()?;
-    let rows = db.query(&StaleSources)?;
+    let mut txn = db.transaction()?;
+    sources::write(&mut txn, &imported, "web")?;
+    txn.commit()?;
 
-    for row in rows {
-        let (source_id, url) = (row.id, row.url);
-        
-        info!("Crawling source: {} - {}", source_id, url);
+    let sources = state.db()?.query(&Sources)?;
 
-        let response = reqwest::get(url.clone()).await
-            .with_context(|| format!("Failed to fetch URL: {}", url))?;
+    let tera = template_new()?;
+    let mut context = tera::Context::new();
+    context.insert("sources", &sources);
+    let body = tera.render("source/list_partial.html", &context)?;
 
-        let etag = if let Some(etag_value) = response.headers().get(header::ETAG) {
-            Some(String::from(etag_value.to_str()
-                .with_context(|| format!("Failed to convert ETag header to string for URL: {}", url))?))
-        } else {
-            None
-        };
+    Ok(Html(body))
+}
 
-        // Check if the request was successful (status code 2xx)
-        let body = if response.status().is_success() {
-            // Get the response body as text
-            response.text().await
-                .with_context(|| format!("Failed to get response body as text for URL: {}", url))?
-        } else {
-            warn!("Request failed for {} with status: {}", url, response.status());
-            continue; // Skip to the next source
-        };
+/// The body of a response as text, decoded by chu with the charset of its
+/// headers or of the page itself.
+async fn text(response: reqwest::Response) -> reqwest::Result<String> {
+    let content_type = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(String::from);
+    let bytes = response.bytes().await?;
 
-        let document = chu::extract_tables(&body);
-        let text = chu::tables_to_string(document.tables);
-        let now = &Local::now().to_rfc3339();
-        
-        db.execute(&UpdateCrawlDate(source_id, now))
-            .with_context(|| format!("Failed to update crawl date for source ID: {}", source_id))?;
-        
-        db.execute(&AddDocument {
-            hash: &format!("{:x}", Sha256::digest(body.as_bytes())), // body needs to be bytes for digest
-            source_id,
-            retrieved_date: now,
-            etag: etag.as_deref(),
-            title: document.title.as_deref(),
-            content: &text,
-        }).with_context(|| format!("Failed to add document for source ID: {}", source_id))?;
-    }
+    Ok(chu::decode(&bytes, content_type.as_deref()))
+}
+
+#[axum::debug_handler]
+pub async fn crawl(
+    extract::State(state): extract::State<Arc<AppState>>,
+) -> Result<Html<String>, AppError> {
+    crawl_stale(&state).await?;
 
     let sources = state.db()?.query(&Sources)?;
 
@@ -134,3 +231,200 @@ pub async fn crawl(
 
     Ok(Html(body))
 }
+
+/// Crawls the sources not crawled in the last 12 hours, and those marked to
+/// be crawled again, storing a document for each.
+#[instrument(skip_all)]
+pub async fn crawl_stale(state: &AppState) -> anyhow::Result<()> {
+    let mut db = Client::open(&state.db_path)?;
+    let rows = db.query(&StaleSources)?;
+    let sources = rows.len();
+    let mut changed = 0;
+
+    // a source that cannot be crawled is tried again on the next crawl
+    for row in rows {
+        let url = row.url.clone();
+        match crawl_source(state, &mut db, row).await {
+            Ok(true) => changed += 1,
+            Ok(false) => {}
+            Err(e) => warn!("Could not crawl {}: {:#}", url, e),
+        }
+    }
+    search::notify_saved_searches(state).await?;
+    notify(&state.webhooks, &Event::CrawlFinished { sources, changed }).await;
+
+    Ok(())
+}
+
+/// Crawls a source, storing a document of its pages. Returns whether the
+/// document differs from the last one stored.
+async fn crawl_source(
+    state: &AppState,
+    db: &mut Client,
+    row: StaleSourceRow,
+) -> anyhow::Result<bool> {
+    let (source_id, url, main_content, max_pages) =
+        (row.id, row.url, row.main_content, row.max_pages);
+
+    info!("Crawling source: {} - {}", source_id, url);
+
+    let response = reqwest::get(url.clone()).await
+        .with_context(|| format!("Failed to fetch URL: {}", url))?;
+
+    // an ETag that is not text is only a missed hint
+    let etag = response
+        .headers()
+        .get(header::ETAG)
+        .and_then(|value| value.to_str().ok())
+        .map(String::from);
+
+    // Check if the request was successful (status code 2xx)
+    let body = if response.status().is_success() {
+        text(response).await
+            .with_context(|| format!("Failed to get response body as text for URL: {}", url))?
+    } else {
+        warn!("Request failed for {} with status: {}", url, response.status());
+        return Ok(false);
+    };
+
+    // follow next links, for sites that split lists across pages, keeping
+    // the pages fetched so far when one cannot be
+    let mut bodies = vec![body];
+    let mut page_url = url.clone();
+    let mut visited = HashSet::from([url.clone()]);
+    while (bodies.len() as i64) < max_pages.clamp(1, sources::MAX_PAGES) {
+        let Some(next_url) = bodies.last().and_then(|body| chu::next_page(body, &page_url)) else {
+            break;
+        };
+        if !visited.insert(next_url.clone()) {
+            break;
+        }
+        info!("Following next page: {}", next_url);
+        let response = match reqwest::get(next_url.clone()).await {
+            Ok(response) => response,
+            Err(e) => {
+                warn!("Failed to fetch URL {}: {}", next_url, e);
+                break;
+            }
+        };
+        if !response.status().is_success() {
+            warn!("Request failed for {} with status: {}", next_url, response.status());
+            break;
+        }
+        match text(response).await {
+            Ok(body) => bodies.push(body),
+            Err(e) => {
+                warn!("Failed to get response body as text for URL {}: {}", next_url, e);
+                break;
+            }
+        }
+        page_url = next_url;
+    }
+
+    let selectors: Vec<chu::NamedSelector> = db
+        .query(&SourceSelectors(source_id))?
+        .into_iter()
+        .map(|row| chu::NamedSelector {
+            name: row.name,
+            selector: row.selector,
+        })
+        .collect();
+    // the pages are concatenated into one logical document
+    let mut document: Option<chu::Document> = None;
+    for body in &bodies {
+        let page = if selectors.is_empty() {
+            chu::extract_tables(body)
+        } else {
+            chu::extract_selected(body, &selectors)
+                .with_context(|| format!("Failed to extract document for URL: {}", url))?
+        };
+        match &mut document {
+            Some(document) => document.merge(page),
+            None => document = Some(page),
+        }
+    }
+    let Some(document) = document else {
+        return Ok(false);
+    };
+    let title = document.title.clone();
+    let structured = serde_json::to_string(&document)
+        .with_context(|| format!("Failed to serialize document for URL: {}", url))?;
+    let main_texts: Vec<String> = bodies
+        .iter()
+        .filter(|_| main_content)
+        .filter_map(|body| chu::main_content(body))
+        .collect();
+    let text = if main_texts.is_empty() {
+        chu::document_to_string(document)
+    } else {
+        main_texts.join("\n")
+    };
+    let language = chu::language(&text);
+    let body = bodies.concat();
+    let hash = format!("{:x}", Sha256::digest(body.as_bytes())); // body needs to be bytes for digest
+    let previous_hash = db.query(&LatestDocumentHash(source_id))?.pop().map(|row| row.0);
+    let now = &Local::now().to_rfc3339();
+    
+    db.execute(&UpdateCrawlDate(source_id, now))
+        .with_context(|| format!("Failed to update crawl date for source ID: {}", source_id))?;
+    
+    db.execute(&AddDocument {
+        hash: &hash,
+        source_id,
+        retrieved_date: now,
+        etag: etag.as_deref(),
+        title: title.as_deref(),
+        content: &text,
+        structured: Some(&structured),
+        language,
+    }).with_context(|| format!("Failed to add document for source ID: {}", source_id))?;
+    let document_id = db.as_ref().last_insert_rowid();
+    db.execute(&AuditInsert {
+        actor: "crawler",
+        action: "crawl",
+        target: &url,
+        detail: Some(&format!("document {}", document_id)),
+    })?;
+
+    // a document that cannot be embedded can still be found by keyword
+    if let Some(embedder) = &state.embedder {
+        match embedder.embed(&text).await {
+            Ok(vector) => {
+                db.execute(&AddDocumentEmbedding {
+                    document_id,
+                    model: &embedder.model,
+                    vector: &embedding::to_blob(&vector),
+                })?;
+            }
+            Err(e) => warn!("Could not embed document for {}: {:#}", url, e),
+        }
+    }
+
+    // a failed pipeline is reported on its page and does not stop the crawl
+    for pipeline in state.pipelines.iter().filter(|pipeline| pipeline.source == url) {
+        let name = pipeline.name.clone();
+        let (pipeline, db_path, pages) = (pipeline.clone(), state.db_path.clone(), bodies.clone());
+        let result = tokio::task::spawn_blocking(move || pipeline.run(&db_path, document_id, &pages)).await?;
+        let error = result.err().map(|e| format!("{:#}", e));
+        if let Some(error) = &error {
+            warn!("Pipeline {} failed: {}", name, error);
+        }
+        db.execute(&PipelineRunInsert {
+            pipeline: &name,
+            document_id,
+            error: error.as_deref(),
+        })?;
+    }
+
+    let changed = previous_hash.as_deref() != Some(hash.as_str());
+    if changed {
+        notify(&state.webhooks, &Event::DocumentChanged {
+            source_id,
+            url: &url,
+            hash: &hash,
+            title: title.as_deref(),
+        }).await;
+    }
+
+    Ok(changed)
+}
@@ -0,0 +1,180 @@
+use std::sync::Arc;
+
+use anyhow::Context;
+use axum::{extract, response::Html};
+use aykroyd::rusqlite::Client;
+use serde::Serialize;
+
+use crate::{
+    schema,
+    serve::{AppError, AppState, template_new},
+    table::{self, Column, Table},
+    validate::Validator,
+};
+
+/// How many rows of an uploaded table are shown before it is imported.
+const PREVIEW_ROWS: usize = 20;
+
+#[axum::debug_handler]
+pub async fn upload_form(
+    extract::State(state): extract::State<Arc<AppState>>,
+) -> Result<Html<String>, AppError> {
+    let schemas: Vec<String> = schema::read(&mut state.db()?)?
+        .into_iter()
+        .filter(|(_, schema)| !schema.abstrct)
+        .map(|(name, _)| name)
+        .collect();
+
+    let tera = template_new()?;
+    let mut context = tera::Context::new();
+    context.insert("schemas", &schemas);
+    let body = tera.render("table/upload.html", &context)?;
+
+    Ok(Html(body))
+}
+
+/// A property a column can be imported as.
+#[derive(Serialize)]
+struct Attribute {
+    /// The property as `schema.name`.
+    name: String,
+    label: String,
+}
+
+/// The properties an entity of a schema can have, from the schema and those
+/// it extends.
+fn attributes(db: &mut Client, schema_name: &str) -> anyhow::Result<Vec<Attribute>> {
+    let validator = Validator::load(db)?;
+    let mut attributes = Vec::new();
+    for (property_schema, schema) in schema::read(db)? {
+        if !validator.extends(schema_name, &property_schema) {
+            continue;
+        }
+        for (name, property) in schema.properties.into_iter().flatten() {
+            attributes.push(Attribute {
+                label: property.label.unwrap_or_else(|| name.clone()),
+                name: format!("{}.{}", property_schema, name),
+            });
+        }
+    }
+
+    Ok(attributes)
+}
+
+/// The attribute a column is imported as unless changed, the one whose name
+/// or label is its header, ignoring case.
+fn suggest(attributes: &[Attribute], header: &str) -> String {
+    let header = header.trim();
+    attributes
+        .iter()
+        .find(|attribute| {
+            let name = attribute.name.split_once('.').map_or("", |(_, name)| name);
+            name.eq_ignore_ascii_case(header) || attribute.label.eq_ignore_ascii_case(header)
+        })
+        .map(|attribute| attribute.name.clone())
+        .unwrap_or_default()
+}
+
+#[axum::debug_handler]
+pub async fn preview(
+    extract::State(state): extract::State<Arc<AppState>>,
+    mut multipart: extract::Multipart,
+) -> Result<Html<String>, AppError> {
+    let mut file = None;
+    let mut schema_name = String::new();
+    while let Some(field) = multipart.next_field().await? {
+        match field.name() {
+            Some("file") => {
+                let file_name = field.file_name().unwrap_or_default().to_string();
+                file = Some((file_name, field.bytes().await?));
+            }
+            Some("schema") => schema_name = field.text().await?,
+            _ => {}
+        }
+    }
+    let (file_name, bytes) = file.context("no file was uploaded")?;
+    let table = table::read(&file_name, &bytes)?;
+    let attributes = attributes(&mut state.db()?, &schema_name)?;
+    let suggestions: Vec<String> = table
+        .header
+        .iter()
+        .map(|header| suggest(&attributes, header))
+        .collect();
+
+    let tera = template_new()?;
+    let mut context = tera::Context::new();
+    context.insert("file_name", &file_name);
+    context.insert("schema", &schema_name);
+    context.insert("header", &table.header);
+    context.insert("rows", &table.rows[..table.rows.len().min(PREVIEW_ROWS)]);
+    context.insert("row_count", &table.rows.len());
+    context.insert("attributes", &attributes);
+    context.insert("suggestions", &suggestions);
+    context.insert("csv", &table.to_csv()?);
+    let body = tera.render("table/preview_partial.html", &context)?;
+
+    Ok(Html(body))
+}
+
+#[axum::debug_handler]
+pub async fn import(
+    extract::State(state): extract::State<Arc<AppState>>,
+    extract::Form(fields): extract::Form<Vec<(String, String)>>,
+) -> Result<Html<String>, AppError> {
+    let field = |name: &str| {
+        fields
+            .iter()
+            .find(|(key, _)| key == name)
+            .map_or("", |(_, value)| value.as_str())
+    };
+    let file_name = field("file_name").to_string();
+    let schema_name = field("schema").to_string();
+    let id_column = Some(field("id_column").to_string()).filter(|column| !column.is_empty());
+    let lenient = !field("lenient").is_empty();
+    // the table as previewed, whatever the format of the uploaded file
+    let table = Table::from_csv(field("csv").as_bytes())?;
+    if !Validator::load(&mut state.db()?)?.has_schema(&schema_name) {
+        return Err(anyhow::anyhow!("unknown schema {}", schema_name).into());
+    }
+
+    // each column is mapped by a field named after its position
+    let columns: Vec<Column> = table
+        .header
+        .iter()
+        .enumerate()
+        .filter_map(|(index, header)| {
+            let attribute = field(&format!("column-{}", index));
+            (!attribute.is_empty()).then(|| Column {
+                header: header.clone(),
+                attribute: attribute.to_string(),
+            })
+        })
+        .collect();
+
+    let db_path = state.db_path.clone();
+    let rows = table.rows.len();
+    let imported_schema = schema_name.clone();
+    let result = tokio::task::spawn_blocking(move || {
+        table::import(
+            &db_path,
+            &file_name,
+            &table,
+            &imported_schema,
+            &columns,
+            id_column.as_deref(),
+            lenient,
+        )
+    })
+    .await?;
+
+    let tera = template_new()?;
+    let mut context = tera::Context::new();
+    context.insert("schema", &schema_name);
+    context.insert("rows", &rows);
+    if let Err(e) = result {
+        context.insert("error", &format!("{:#}", e));
+    }
+    let body = tera.render("table/imported_partial.html", &context)?;
+
+    Ok(Html(body))
+}
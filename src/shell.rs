@@ -0,0 +1,313 @@
+use anyhow::{Context, Result, bail};
+use aykroyd::rusqlite::Client;
+use jaq_json::Val;
+use rustyline::{
+    Editor, Helper, completion::Completer, error::ReadlineError, highlight::Highlighter,
+    hint::Hinter, history::DefaultHistory, validate::Validator as LineValidator,
+};
+use std::{
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    delete,
+    mapper::Property,
+    store::{
+        audit::AuditInsert,
+        entity::{
+            EntitiesQuery, EntitiesWithValueQuery, InsertEntityStatement, PropertyForEntityDelete,
+            PropertyForEntityQuery, PropertyForEntitySchemaInsert, PropertyForEntitySchemaQuery,
+        },
+        schema::{SchemaNamesQuery, SchemaPropertiesQuery},
+    },
+    validate::Validator,
+};
+
+const COMMANDS: &[&str] = &[
+    "help", "schemas", "list", "read", "write", "delete", "query", "quit",
+];
+
+const HELP: &str = "\
+schemas                               list the schemas
+list [schema]                         list the entities, of a schema or all
+read schema/id                        print the values of an entity
+write schema/id schema.name value     set a value, or add one to a property with many
+delete schema/id [schema.name]        delete the values of a property, or the entity
+query schema.name value               list the entities with a value
+quit                                  leave the shell";
+
+/// Completes command names, entities as `schema/id` and properties as
+/// `schema.name`.
+struct ShellHelper {
+    words: Vec<String>,
+}
+
+impl Completer for ShellHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &rustyline::Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        let start = line[..pos].rfind(' ').map_or(0, |i| i + 1);
+        let prefix = &line[start..pos];
+        let candidates = self
+            .words
+            .iter()
+            .filter(|word| word.starts_with(prefix))
+            .cloned()
+            .collect();
+
+        Ok((start, candidates))
+    }
+}
+
+impl Hinter for ShellHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ShellHelper {}
+
+impl LineValidator for ShellHelper {}
+
+impl Helper for ShellHelper {}
+
+/// A database kept open between commands.
+pub struct Shell {
+    db: Client,
+    validator: Validator,
+    /// Whether a command wrote to the database since completions were last
+    /// read.
+    written: bool,
+}
+
+fn entity(text: &str) -> Result<(&str, &str)> {
+    text.split_once('/')
+        .with_context(|| format!("expected an entity as schema/id but found {}", text))
+}
+
+fn property(text: &str) -> Result<(&str, &str)> {
+    text.split_once('.')
+        .with_context(|| format!("expected a property as schema.name but found {}", text))
+}
+
+impl Shell {
+    pub fn open(db_path: &Path) -> Result<Self> {
+        let mut db = Client::open(db_path)?;
+        let validator = Validator::load(&mut db)?;
+
+        Ok(Shell {
+            db,
+            validator,
+            written: false,
+        })
+    }
+
+    /// The words offered for completion.
+    fn words(&mut self) -> Result<Vec<String>> {
+        let mut words: Vec<String> = COMMANDS.iter().map(|command| command.to_string()).collect();
+        for row in self.db.query(&SchemaNamesQuery)? {
+            words.push(row.name);
+        }
+        for row in self.db.query(&SchemaPropertiesQuery)? {
+            words.push(format!("{}.{}", row.schema_name, row.name));
+        }
+        for row in self.db.query(&EntitiesQuery)? {
+            words.push(format!("{}/{}", row.schema_name, row.id));
+        }
+
+        Ok(words)
+    }
+
+    /// Runs a command line, writing its output to `out`. Returns whether the
+    /// shell should go on.
+    pub fn execute(&mut self, line: &str, out: &mut impl Write) -> Result<bool> {
+        let mut words = line.split_whitespace();
+        let Some(command) = words.next() else {
+            return Ok(true);
+        };
+        let args: Vec<&str> = words.collect();
+
+        match (command, args.as_slice()) {
+            ("help", []) => writeln!(out, "{}", HELP)?,
+            ("quit" | "exit", []) => return Ok(false),
+            ("schemas", []) => {
+                for row in self.db.query(&SchemaNamesQuery)? {
+                    writeln!(out, "{}", row.name)?;
+                }
+            }
+            ("list", [] | [_]) => {
+                for row in self.db.query(&EntitiesQuery)? {
+                    if args.first().is_none_or(|schema| *schema == row.schema_name) {
+                        writeln!(out, "{}/{}", row.schema_name, row.id)?;
+                    }
+                }
+            }
+            ("read", [entity_text]) => {
+                let (schema, id) = entity(entity_text)?;
+                for row in self.db.query(&PropertyForEntityQuery { schema, id })? {
+                    writeln!(
+                        out,
+                        "{}.{} {}",
+                        row.property_schema_name, row.property_name, row.value
+                    )?;
+                }
+            }
+            ("write", [entity_text, property_text, ..]) if args.len() > 2 => {
+                let (schema, id) = entity(entity_text)?;
+                let (property_schema, name) = property(property_text)?;
+                self.write(schema, id, property_schema, name, &args[2..].join(" "))?;
+                self.written = true;
+            }
+            ("delete", [entity_text]) => {
+                let (schema, id) = entity(entity_text)?;
+                let mut txn = self.db.transaction()?;
+                delete::delete(&mut txn, schema, id, None, true, "shell")?;
+                txn.commit()?;
+                self.written = true;
+            }
+            ("delete", [entity_text, property_text]) => {
+                let (schema, id) = entity(entity_text)?;
+                property(property_text)?;
+                let mut txn = self.db.transaction()?;
+                delete::delete(&mut txn, schema, id, Some(property_text), false, "shell")?;
+                txn.commit()?;
+                self.written = true;
+            }
+            ("query", [property_text, ..]) if args.len() > 1 => {
+                let (property_schema, name) = property(property_text)?;
+                let value = args[1..].join(" ");
+                for row in self.db.query(&EntitiesWithValueQuery {
+                    property_schema,
+                    name,
+                    value: &value,
+                })? {
+                    writeln!(out, "{}/{}", row.schema_name, row.id)?;
+                }
+            }
+            _ => bail!("unknown command `{}`, try `help`", line.trim()),
+        }
+
+        Ok(true)
+    }
+
+    fn write(
+        &mut self,
+        schema: &str,
+        id: &str,
+        property_schema: &str,
+        name: &str,
+        value: &str,
+    ) -> Result<()> {
+        if !self.validator.has_schema(schema) {
+            bail!("unknown schema {}", schema);
+        }
+        // a value of a property holding one replaces the value it has
+        self.validator.check(
+            schema,
+            &Property {
+                schema: property_schema.to_string(),
+                name: name.to_string(),
+                filter: String::new(),
+                value: Val::utf8_str(value.to_string()),
+            },
+            &[],
+        )?;
+
+        let many = self.validator.many(schema, property_schema, name);
+        let mut txn = self.db.transaction()?;
+        txn.execute(&InsertEntityStatement {
+            schema_name: schema,
+            id,
+        })?;
+        let position = if many {
+            let existing = txn.query(&PropertyForEntitySchemaQuery {
+                schema,
+                id,
+                property_schema,
+            })?;
+            existing
+                .iter()
+                .filter(|row| row.property_name == name)
+                .count() as i64
+        } else {
+            txn.execute(&PropertyForEntityDelete {
+                schema,
+                id,
+                property_schema,
+                name,
+            })?;
+            0
+        };
+        txn.execute(&PropertyForEntitySchemaInsert {
+            schema,
+            id,
+            property_schema,
+            name,
+            value,
+            position,
+            provenance: "edit:shell",
+        })?;
+        txn.execute(&AuditInsert {
+            actor: "shell",
+            action: "write",
+            target: &format!("{}/{}", schema, id),
+            detail: Some(&format!("{}.{}", property_schema, name)),
+        })?;
+        txn.commit()?;
+
+        Ok(())
+    }
+}
+
+fn history_path() -> Option<PathBuf> {
+    std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".pika_history"))
+}
+
+/// Reads commands against a database until `quit` or end of input, with
+/// history and tab completion.
+pub fn run(db_path: &Path) -> Result<()> {
+    let mut shell = Shell::open(db_path)?;
+    let mut editor: Editor<ShellHelper, DefaultHistory> = Editor::new()?;
+    editor.set_helper(Some(ShellHelper {
+        words: shell.words()?,
+    }));
+    let history = history_path();
+    if let Some(history) = &history {
+        // there is no history the first time
+        let _ = editor.load_history(history);
+    }
+
+    let mut out = io::stdout();
+    loop {
+        let line = match editor.readline("pika> ") {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(e.into()),
+        };
+        editor.add_history_entry(line.as_str())?;
+        match shell.execute(&line, &mut out) {
+            Ok(true) => {}
+            Ok(false) => break,
+            Err(e) => eprintln!("error: {:#}", e),
+        }
+        // writes and deletes change what can be completed
+        if std::mem::take(&mut shell.written) {
+            let words = shell.words()?;
+            if let Some(helper) = editor.helper_mut() {
+                helper.words = words;
+            }
+        }
+    }
+    if let Some(history) = &history {
+        editor
+            .save_history(history)
+            .with_context(|| format!("could not save history to {}", history.display()))?;
+    }
+
+    Ok(())
+}
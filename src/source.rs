@@ -0,0 +1,267 @@
+use anyhow::{Context, Result, bail};
+use aykroyd::rusqlite::{Client, Transaction};
+use jaq_json::Val;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    fs,
+    io::{Read, Write},
+    path::Path,
+};
+
+use crate::{
+    input::xml,
+    store::{
+        audit::AuditInsert,
+        source::{
+            AddSourceSelector, SourceConfigs, SourceSelectors, SourceSelectorsDelete, UpsertSource,
+        },
+    },
+};
+
+/// How a source is crawled, as exported and imported.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SourceConfig {
+    pub url: String,
+    /// Whether the main content of pages is stored instead of their tables.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub main_content: bool,
+    #[serde(default = "default_max_pages")]
+    pub max_pages: i64,
+    /// CSS selectors by field name, extracted instead of every table.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub selectors: BTreeMap<String, String>,
+}
+
+/// The most pages a source is crawled through, whatever it asks for.
+pub const MAX_PAGES: i64 = 50;
+
+fn default_max_pages() -> i64 {
+    1
+}
+
+#[derive(Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SourcesFile {
+    #[serde(default)]
+    source: Vec<SourceConfig>,
+}
+
+/// The formats a source list can be written in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum Format {
+    #[default]
+    Toml,
+    /// An outline per source, with crawl settings in `pika` attributes.
+    Opml,
+}
+
+/// Reads the sources of a database, in URL order.
+pub fn read(db: &mut Client) -> Result<Vec<SourceConfig>> {
+    let mut sources = Vec::new();
+    for row in db.query(&SourceConfigs)? {
+        let selectors = db
+            .query(&SourceSelectors(row.id))?
+            .into_iter()
+            .map(|selector| (selector.name, selector.selector))
+            .collect();
+        sources.push(SourceConfig {
+            url: row.url,
+            main_content: row.main_content,
+            max_pages: row.max_pages,
+            selectors,
+        });
+    }
+
+    Ok(sources)
+}
+
+/// Adds sources, replacing the settings and selectors of those that exist.
+pub fn write(txn: &mut Transaction, sources: &[SourceConfig], actor: &str) -> Result<()> {
+    for source in sources {
+        txn.execute(&UpsertSource(
+            &source.url,
+            source.main_content,
+            source.max_pages.clamp(1, MAX_PAGES),
+        ))?;
+        txn.execute(&SourceSelectorsDelete(&source.url))?;
+        for (name, selector) in &source.selectors {
+            txn.execute(&AddSourceSelector {
+                url: &source.url,
+                name,
+                selector,
+            })?;
+        }
+        txn.execute(&AuditInsert {
+            actor,
+            action: "source_import",
+            target: &source.url,
+            detail: None,
+        })?;
+    }
+
+    Ok(())
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Writes sources in a format.
+pub fn to_string(sources: &[SourceConfig], format: Format) -> Result<String> {
+    match format {
+        Format::Toml => Ok(toml::to_string(&SourcesFile {
+            source: sources.to_vec(),
+        })?),
+        Format::Opml => {
+            let mut opml = String::from(
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<opml version=\"2.0\">\n  <head><title>pika sources</title></head>\n  <body>\n",
+            );
+            for source in sources {
+                write!(
+                    opml,
+                    "    <outline type=\"link\" text=\"{0}\" url=\"{0}\" pikaMainContent=\"{1}\" pikaMaxPages=\"{2}\"",
+                    escape(&source.url),
+                    source.main_content,
+                    source.max_pages
+                )?;
+                if source.selectors.is_empty() {
+                    opml.push_str("/>\n");
+                    continue;
+                }
+                opml.push_str(">\n");
+                for (name, selector) in &source.selectors {
+                    writeln!(
+                        opml,
+                        "      <outline text=\"{}\" pikaSelector=\"{}\"/>",
+                        escape(name),
+                        escape(selector)
+                    )?;
+                }
+                opml.push_str("    </outline>\n");
+            }
+            opml.push_str("  </body>\n</opml>\n");
+
+            Ok(opml)
+        }
+    }
+}
+
+/// An attribute of an outline, with the entities that the XML conversion
+/// leaves in place replaced.
+fn attribute(outline: &Val, name: &str) -> Option<String> {
+    let text = xml::field(outline, &format!("@{}", name)).and_then(xml::as_str)?;
+    Some(
+        text.replace("&lt;", "<")
+            .replace("&gt;", ">")
+            .replace("&quot;", "\"")
+            .replace("&apos;", "'")
+            .replace("&amp;", "&"),
+    )
+}
+
+/// The outlines directly under an outline, which the XML conversion gives as
+/// one object or an array of them.
+fn outlines(parent: &Val) -> Vec<&Val> {
+    match xml::field(parent, "outline") {
+        Some(Val::Arr(outlines)) => outlines.iter().collect(),
+        Some(outline) => vec![outline],
+        None => Vec::new(),
+    }
+}
+
+fn from_opml(text: &str) -> Result<Vec<SourceConfig>> {
+    let roots = jaq_json::xml::parse_many(text)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| anyhow::anyhow!("could not parse OPML: {}", e))?;
+
+    let mut sources = Vec::new();
+    // outlines without a URL are folders of sources
+    for outline in xml::records(roots, Some("outline")) {
+        let mut pending = vec![outline];
+        while let Some(outline) = pending.pop() {
+            let Some(url) = attribute(&outline, "url").or_else(|| attribute(&outline, "xmlUrl"))
+            else {
+                pending.extend(outlines(&outline).into_iter().rev().cloned());
+                continue;
+            };
+            let max_pages = match attribute(&outline, "pikaMaxPages") {
+                Some(pages) => pages
+                    .parse()
+                    .with_context(|| format!("invalid pikaMaxPages for {}", url))?,
+                None => default_max_pages(),
+            };
+            let selectors = outlines(&outline)
+                .into_iter()
+                .filter_map(|selector| {
+                    Some((
+                        attribute(selector, "text")?,
+                        attribute(selector, "pikaSelector")?,
+                    ))
+                })
+                .collect();
+            sources.push(SourceConfig {
+                main_content: attribute(&outline, "pikaMainContent").as_deref() == Some("true"),
+                url,
+                max_pages,
+                selectors,
+            });
+        }
+    }
+
+    Ok(sources)
+}
+
+/// Reads sources in a format.
+pub fn from_str(text: &str, format: Format) -> Result<Vec<SourceConfig>> {
+    match format {
+        Format::Toml => {
+            let file: SourcesFile = toml::from_str(text).context("could not parse sources")?;
+            Ok(file.source)
+        }
+        Format::Opml => from_opml(text),
+    }
+}
+
+/// Writes the sources of a database to `out`.
+pub fn export(db_path: &Path, format: Format, mut out: impl Write) -> Result<()> {
+    let mut db = Client::open(db_path)?;
+    out.write_all(to_string(&read(&mut db)?, format)?.as_bytes())?;
+
+    Ok(())
+}
+
+/// Adds the sources of a file, or of stdin for `-`, to a database. The format
+/// is OPML for `.opml` and `.xml` files and TOML otherwise.
+pub fn import(db_path: &Path, path: &Path) -> Result<()> {
+    let text = if path == Path::new("-") {
+        let mut text = String::new();
+        std::io::stdin().read_to_string(&mut text)?;
+        text
+    } else {
+        fs::read_to_string(path).with_context(|| format!("could not read {}", path.display()))?
+    };
+    let format = match path.extension().and_then(|extension| extension.to_str()) {
+        Some("opml" | "xml") => Format::Opml,
+        _ if text.trim_start().starts_with('<') => Format::Opml,
+        _ => Format::Toml,
+    };
+    let sources = from_str(&text, format)
+        .with_context(|| format!("could not read sources from {}", path.display()))?;
+    if sources.is_empty() {
+        bail!("no sources in {}", path.display());
+    }
+
+    let mut db = Client::open(db_path)?;
+    let mut txn = db.transaction()?;
+    write(&mut txn, &sources, "cli")?;
+    txn.commit()?;
+
+    Ok(())
+}
@@ -0,0 +1,54 @@
+use anyhow::Result;
+use aykroyd::rusqlite::Client;
+use std::{io::Write, path::Path};
+
+use crate::{
+    schema::inspect::Format,
+    store::stat::{AttributeStatsQuery, TotalsQuery},
+};
+
+/// Writes how many entities, values, sources and documents a database holds
+/// or, with `attributes`, a line per attribute with its number of values,
+/// distinct values, average value size in bytes and when a value was last
+/// written.
+pub fn run(db_path: &Path, attributes: bool, format: Format, mut out: impl Write) -> Result<()> {
+    let mut db = Client::open(db_path)?;
+    if !attributes {
+        let totals = db.query_one(&TotalsQuery)?;
+        match format {
+            Format::Json => writeln!(out, "{}", serde_json::to_string_pretty(&totals)?)?,
+            Format::Plain => {
+                writeln!(out, "entities: {}", totals.entities)?;
+                writeln!(out, "values: {}", totals.values)?;
+                writeln!(out, "sources: {}", totals.sources)?;
+                writeln!(out, "documents: {}", totals.documents)?;
+            }
+        }
+        return Ok(());
+    }
+
+    let stats = db.query(&AttributeStatsQuery)?;
+    match format {
+        Format::Json => writeln!(out, "{}", serde_json::to_string_pretty(&stats)?)?,
+        Format::Plain => {
+            writeln!(
+                out,
+                "attribute\tvalues\tdistinct\taverage size\tlast modified"
+            )?;
+            for stat in stats {
+                writeln!(
+                    out,
+                    "{}.{}\t{}\t{}\t{:.1}\t{}",
+                    stat.property_schema_name,
+                    stat.property_name,
+                    stat.values,
+                    stat.distinct_values,
+                    stat.average_size,
+                    stat.last_modified.as_deref().unwrap_or("unknown")
+                )?;
+            }
+        }
+    }
+
+    Ok(())
+}
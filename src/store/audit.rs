@@ -0,0 +1,64 @@
+use aykroyd::{FromRow, Query, Statement};
+use serde::Serialize;
+
+/// Appends a mutation to the audit log.
+///
+/// The actor is what made the change: `cli`, `shell`, `web` or `crawler`.
+#[derive(Statement)]
+#[aykroyd(text = "
+    INSERT INTO audit (audit_date, actor, action, target, detail)
+    VALUES (strftime('%Y-%m-%dT%H:%M:%SZ', 'now'), $1, $2, $3, $4)
+")]
+pub struct AuditInsert<'a> {
+    #[aykroyd(param = "$1")]
+    pub actor: &'a str,
+
+    #[aykroyd(param = "$2")]
+    pub action: &'a str,
+
+    /// What was changed, such as `schema/id` for an entity or a URL for a
+    /// source.
+    #[aykroyd(param = "$3")]
+    pub target: &'a str,
+
+    #[aykroyd(param = "$4")]
+    pub detail: Option<&'a str>,
+}
+
+#[derive(FromRow, Serialize)]
+pub struct AuditRow {
+    pub audit_date: String,
+    pub actor: String,
+    pub action: String,
+    pub target: String,
+    pub detail: Option<String>,
+}
+
+/// The latest entries of the audit log, optionally only those of an action or
+/// with a target containing some text.
+#[derive(Query)]
+#[aykroyd(
+    row(AuditRow),
+    text = "
+    SELECT audit_date, actor, action, target, detail FROM audit
+    WHERE ($1 IS NULL OR action = $1) AND ($2 IS NULL OR instr(target, $2) > 0)
+    ORDER BY id DESC
+    LIMIT 500
+"
+)]
+pub struct AuditQuery<'a> {
+    #[aykroyd(param = "$1")]
+    pub action: Option<&'a str>,
+
+    #[aykroyd(param = "$2")]
+    pub target: Option<&'a str>,
+}
+
+#[derive(FromRow)]
+pub struct AuditActionRow {
+    pub action: String,
+}
+
+#[derive(Query)]
+#[aykroyd(row(AuditActionRow), text = "SELECT DISTINCT action FROM audit ORDER BY action")]
+pub struct AuditActionsQuery;
@@ -3,7 +3,7 @@ use serde::Serialize;
 
 #[derive(Statement)]
 #[aykroyd(text = "
-    INSERT OR IGNORE INTO document (source_id, hash, retrieved_date, etag, title, content) VALUES ($1, $2, $3, $4, $5, $6)
+    INSERT OR IGNORE INTO document (source_id, hash, retrieved_date, etag, title, content, structured, language) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
 ")]
 pub struct AddDocument<'a> {
     pub source_id: i64,
@@ -12,6 +12,10 @@ pub struct AddDocument<'a> {
     pub etag: Option<&'a str>,
     pub title: Option<&'a str>,
     pub content: &'a str,
+    /// The document as chu extracted it, as JSON.
+    pub structured: Option<&'a str>,
+    /// The ISO 639-3 code of the language of the content, when detected.
+    pub language: Option<&'a str>,
 }
 
 #[derive(FromRow, Serialize)]
@@ -25,6 +29,17 @@ pub struct Content(pub String);
 ")]
 pub struct GetContent(pub i64);
 
+#[derive(FromRow)]
+pub struct Structured(pub Option<String>);
+
+#[derive(QueryOne)]
+#[aykroyd(
+    row(Structured),
+    text = "
+        SELECT structured FROM document WHERE id = $1
+")]
+pub struct GetStructured(pub i64);
+
 #[derive(Query)]
 #[aykroyd(
     row(SearchDocumentRow),
@@ -37,6 +52,59 @@ pub struct GetContent(pub i64);
 )]
 pub struct SearchDocuments<'a>(pub &'a str);
 
+/// Searches the documents in a language other than English, Chinese,
+/// Japanese and Korean, which have indexes of their own.
+#[derive(Query)]
+#[aykroyd(
+    row(SearchDocumentRow),
+    text = "
+        SELECT d.id, s.url, d.retrieved_date, d.title, snippet(i.fts_document, -1, '<b>', '</b>', '...', 16) AS snippet
+        FROM fts_document($1) AS i
+        LEFT JOIN document AS d ON d.id = i.rowid
+        LEFT JOIN source AS s ON d.source_id = s.id
+        WHERE d.language = $2
+"
+)]
+pub struct SearchDocumentsInLanguage<'a>(pub &'a str, pub &'a str);
+
+/// Searches English documents by word stems.
+#[derive(Query)]
+#[aykroyd(
+    row(SearchDocumentRow),
+    text = "
+        SELECT d.id, s.url, d.retrieved_date, d.title, snippet(i.fts_document_english, -1, '<b>', '</b>', '...', 16) AS snippet
+        FROM fts_document_english($1) AS i
+        LEFT JOIN document AS d ON d.id = i.rowid
+        LEFT JOIN source AS s ON d.source_id = s.id
+"
+)]
+pub struct SearchEnglishDocuments<'a>(pub &'a str);
+
+/// Searches Chinese, Japanese or Korean documents by trigrams.
+#[derive(Query)]
+#[aykroyd(
+    row(SearchDocumentRow),
+    text = "
+        SELECT d.id, s.url, d.retrieved_date, d.title, snippet(i.fts_document_cjk, -1, '<b>', '</b>', '...', 16) AS snippet
+        FROM fts_document_cjk($1) AS i
+        LEFT JOIN document AS d ON d.id = i.rowid
+        LEFT JOIN source AS s ON d.source_id = s.id
+        WHERE d.language = $2
+"
+)]
+pub struct SearchCjkDocuments<'a>(pub &'a str, pub &'a str);
+
+#[derive(FromRow)]
+pub struct DocumentLanguage(pub String);
+
+#[derive(Query)]
+#[aykroyd(
+    row(DocumentLanguage),
+    text = "
+        SELECT DISTINCT language FROM document WHERE language IS NOT NULL ORDER BY language
+")]
+pub struct DocumentLanguages;
+
 #[derive(FromRow, Serialize)]
 pub struct SearchDocumentRow {
     pub id: i64,
@@ -45,3 +113,50 @@ pub struct SearchDocumentRow {
     pub title: Option<String>,
     pub snippet: String,
 }
+
+#[derive(FromRow)]
+pub struct DocumentHash(pub String);
+
+#[derive(Query)]
+#[aykroyd(
+    row(DocumentHash),
+    text = "
+        SELECT hash FROM document WHERE source_id = $1 ORDER BY id DESC LIMIT 1
+")]
+pub struct LatestDocumentHash(pub i64);
+
+#[derive(Query)]
+#[aykroyd(
+    row(SearchDocumentRow),
+    text = "
+        SELECT d.id, s.url, d.retrieved_date, d.title, substr(d.content, 1, 200) AS snippet
+        FROM document AS d
+        LEFT JOIN source AS s ON d.source_id = s.id
+        WHERE d.id = $1
+")]
+pub struct GetSearchDocument(pub i64);
+
+#[derive(Statement)]
+#[aykroyd(text = "
+    INSERT OR REPLACE INTO document_embedding (document_id, model, vector) VALUES ($1, $2, $3)
+")]
+pub struct AddDocumentEmbedding<'a> {
+    pub document_id: i64,
+    pub model: &'a str,
+    /// The vector as little-endian floats.
+    pub vector: &'a [u8],
+}
+
+#[derive(FromRow)]
+pub struct DocumentEmbeddingRow {
+    pub document_id: i64,
+    pub vector: Vec<u8>,
+}
+
+#[derive(Query)]
+#[aykroyd(
+    row(DocumentEmbeddingRow),
+    text = "
+        SELECT document_id, vector FROM document_embedding WHERE model = $1
+")]
+pub struct DocumentEmbeddings<'a>(pub &'a str);
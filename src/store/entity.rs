@@ -1,17 +1,20 @@
-use aykroyd::{FromRow, Query, Statement};
+use aykroyd::{FromRow, Query, QueryOne, Statement};
 
 #[derive(FromRow)]
 pub struct PropertyRow {
     pub property_schema_name: String,
     pub property_name: String,
     pub value: String,
+    pub provenance: String,
+    pub provenance_date: Option<String>,
 }
 
 #[derive(Query)]
 #[aykroyd(
     row(PropertyRow),
     text = "
-    SELECT property_schema_name, property_name, value FROM entity_property WHERE entity_schema_name = $1 AND entity_id = $2
+    SELECT property_schema_name, property_name, value, provenance, provenance_date FROM entity_property WHERE entity_schema_name = $1 AND entity_id = $2
+    ORDER BY property_schema_name, property_name, position
 "
 )]
 pub struct PropertyForEntityQuery<'a> {
@@ -26,13 +29,16 @@ pub struct PropertyForEntityQuery<'a> {
 pub struct PropertyForSchemaRow {
     pub property_name: String,
     pub value: String,
+    pub provenance: String,
+    pub provenance_date: Option<String>,
 }
 
 #[derive(Query)]
 #[aykroyd(
     row(PropertyForSchemaRow),
     text = "
-    SELECT property_name, value FROM entity_property WHERE entity_schema_name = $1 AND entity_id = $2 AND property_schema_name = $3
+    SELECT property_name, value, provenance, provenance_date FROM entity_property WHERE entity_schema_name = $1 AND entity_id = $2 AND property_schema_name = $3
+    ORDER BY property_name, position
 "
 )]
 pub struct PropertyForEntitySchemaQuery<'a> {
@@ -63,7 +69,59 @@ pub struct PropertyForEntitySchemaDelete<'a> {
 
 #[derive(Statement)]
 #[aykroyd(text = "
-    INSERT INTO entity_property (entity_schema_name, entity_id, property_schema_name, property_name, value) VALUES (?1, ?2, ?3, ?4, ?5)
+    DELETE FROM entity_property
+    WHERE entity_schema_name = $1 AND entity_id = $2 AND property_schema_name = $3 AND property_name = $4
+")]
+pub struct PropertyForEntityDelete<'a> {
+    #[aykroyd(param = "$1")]
+    pub schema: &'a str,
+
+    #[aykroyd(param = "$2")]
+    pub id: &'a str,
+
+    #[aykroyd(param = "$3")]
+    pub property_schema: &'a str,
+
+    #[aykroyd(param = "$4")]
+    pub name: &'a str,
+}
+
+#[derive(Statement)]
+#[aykroyd(text = "
+    DELETE FROM entity_property WHERE entity_schema_name = $1 AND entity_id = $2
+")]
+pub struct PropertiesForEntityDelete<'a> {
+    #[aykroyd(param = "$1")]
+    pub schema: &'a str,
+
+    #[aykroyd(param = "$2")]
+    pub id: &'a str,
+}
+
+/// Deletes the values an import of a data file gave the entities imported
+/// from it, leaving values from elsewhere.
+#[derive(Statement)]
+#[aykroyd(text = "
+    DELETE FROM entity_property
+    WHERE entity_schema_name = $1 AND provenance = $2 AND entity_id IN (
+        SELECT entity_id FROM import_entity WHERE schema_name = $1 AND path = $3
+    )
+")]
+pub struct PropertiesForImportFileDelete<'a> {
+    #[aykroyd(param = "$1")]
+    pub schema: &'a str,
+
+    #[aykroyd(param = "$2")]
+    pub provenance: &'a str,
+
+    #[aykroyd(param = "$3")]
+    pub path: &'a str,
+}
+
+#[derive(Statement)]
+#[aykroyd(text = "
+    INSERT INTO entity_property (entity_schema_name, entity_id, property_schema_name, property_name, value, position, provenance, provenance_date)
+    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, strftime('%Y-%m-%dT%H:%M:%SZ', 'now'))
 ")]
 pub struct PropertyForEntitySchemaInsert<'a> {
     #[aykroyd(param = "$1")]
@@ -80,13 +138,295 @@ pub struct PropertyForEntitySchemaInsert<'a> {
     
     #[aykroyd(param = "$5")]
     pub value: &'a str,
+
+    /// The index of the value among the values of a property with many.
+    #[aykroyd(param = "$6")]
+    pub position: i64,
+
+    /// Where the value came from, as `import:<data file>`,
+    /// `document:<document id>`, `triples:<file>` or `edit:<actor>`.
+    #[aykroyd(param = "$7")]
+    pub provenance: &'a str,
 }
 
+/// Inserts an entity, leaving it as it is if it already exists.
 #[derive(Statement)]
-#[aykroyd(text = "INSERT INTO entity (schema_name, id) VALUES ($1, $2)")]
+#[aykroyd(text = "INSERT INTO entity (schema_name, id) VALUES ($1, $2) ON CONFLICT DO NOTHING")]
 pub struct InsertEntityStatement<'a> {
     #[aykroyd(param = "$1")]
     pub schema_name: &'a str,
     #[aykroyd(param = "$2")]
     pub id: &'a str,
 }
+
+#[derive(Statement)]
+#[aykroyd(text = "DELETE FROM entity WHERE schema_name = $1 AND id = $2")]
+pub struct EntityDelete<'a> {
+    #[aykroyd(param = "$1")]
+    pub schema_name: &'a str,
+    #[aykroyd(param = "$2")]
+    pub id: &'a str,
+}
+
+#[derive(FromRow)]
+pub struct EntityRow {
+    pub schema_name: String,
+    pub id: String,
+}
+
+#[derive(Query)]
+#[aykroyd(row(EntityRow), text = "SELECT schema_name, id FROM entity ORDER BY schema_name, id")]
+pub struct EntitiesQuery;
+
+/// The entities that have a value for a property.
+#[derive(Query)]
+#[aykroyd(
+    row(EntityRow),
+    text = "
+    SELECT DISTINCT entity_schema_name AS schema_name, entity_id AS id FROM entity_property
+    WHERE property_schema_name = $1 AND property_name = $2 AND value = $3
+    ORDER BY schema_name, id
+"
+)]
+pub struct EntitiesWithValueQuery<'a> {
+    #[aykroyd(param = "$1")]
+    pub property_schema: &'a str,
+
+    #[aykroyd(param = "$2")]
+    pub name: &'a str,
+
+    #[aykroyd(param = "$3")]
+    pub value: &'a str,
+}
+
+/// Records the values of a property schema of an entity before an edit
+/// replaced them.
+#[derive(Statement)]
+#[aykroyd(text = "
+    INSERT INTO entity_edit (entity_schema_name, entity_id, property_schema_name, edit_date, previous)
+    VALUES ($1, $2, $3, $4, $5)
+")]
+pub struct EntityEditInsert<'a> {
+    #[aykroyd(param = "$1")]
+    pub schema: &'a str,
+
+    #[aykroyd(param = "$2")]
+    pub id: &'a str,
+
+    #[aykroyd(param = "$3")]
+    pub property_schema: &'a str,
+
+    #[aykroyd(param = "$4")]
+    pub edit_date: &'a str,
+
+    /// The previous values as a JSON object of arrays by property name.
+    #[aykroyd(param = "$5")]
+    pub previous: &'a str,
+}
+
+#[derive(FromRow)]
+pub struct EntityEditRow {
+    pub id: i64,
+    pub property_schema_name: String,
+    pub edit_date: String,
+    pub previous: String,
+    pub undone: bool,
+}
+
+/// The edits of an entity, latest first.
+#[derive(Query)]
+#[aykroyd(
+    row(EntityEditRow),
+    text = "
+    SELECT id, property_schema_name, edit_date, previous, undone FROM entity_edit
+    WHERE entity_schema_name = $1 AND entity_id = $2
+    ORDER BY id DESC
+"
+)]
+pub struct EntityEditsQuery<'a> {
+    #[aykroyd(param = "$1")]
+    pub schema: &'a str,
+
+    #[aykroyd(param = "$2")]
+    pub id: &'a str,
+}
+
+#[derive(Statement)]
+#[aykroyd(text = "UPDATE entity_edit SET undone = TRUE WHERE id = $1")]
+pub struct EntityEditUndone(pub i64);
+
+#[derive(Statement)]
+#[aykroyd(text = "DELETE FROM entity_edit WHERE entity_schema_name = $1 AND entity_id = $2")]
+pub struct EntityEditsDelete<'a> {
+    #[aykroyd(param = "$1")]
+    pub schema: &'a str,
+
+    #[aykroyd(param = "$2")]
+    pub id: &'a str,
+}
+
+/// Replaces every value that is exactly one text with another, as when the
+/// entity a value names as `schema/id` is merged into another.
+#[derive(Statement)]
+#[aykroyd(text = "UPDATE entity_property SET value = $1 WHERE value = $2")]
+pub struct PropertyValuesUpdate<'a> {
+    #[aykroyd(param = "$2")]
+    pub value: &'a str,
+
+    #[aykroyd(param = "$1")]
+    pub new_value: &'a str,
+}
+
+#[derive(FromRow)]
+pub struct EntityAliasRow {
+    pub alias: String,
+    pub id: String,
+}
+
+/// The ids of the entities of a schema merged into others, with the ids of
+/// the entities they were merged into.
+#[derive(Query)]
+#[aykroyd(
+    row(EntityAliasRow),
+    text = "SELECT alias, id FROM entity_alias WHERE schema_name = $1"
+)]
+pub struct EntityAliasesQuery<'a>(pub &'a str);
+
+/// Records that the id of a merged entity now stands for another.
+#[derive(Statement)]
+#[aykroyd(text = "
+    INSERT OR REPLACE INTO entity_alias (schema_name, alias, id, merge_date)
+    VALUES ($1, $2, $3, strftime('%Y-%m-%dT%H:%M:%SZ', 'now'))
+")]
+pub struct EntityAliasInsert<'a> {
+    #[aykroyd(param = "$1")]
+    pub schema: &'a str,
+
+    #[aykroyd(param = "$2")]
+    pub alias: &'a str,
+
+    #[aykroyd(param = "$3")]
+    pub id: &'a str,
+}
+
+/// Points the aliases of an entity at another.
+#[derive(Statement)]
+#[aykroyd(text = "UPDATE entity_alias SET id = $1 WHERE schema_name = $2 AND id = $3")]
+pub struct EntityAliasesUpdate<'a> {
+    #[aykroyd(param = "$2")]
+    pub schema: &'a str,
+
+    #[aykroyd(param = "$3")]
+    pub id: &'a str,
+
+    #[aykroyd(param = "$1")]
+    pub new_id: &'a str,
+}
+
+/// Forgets the aliases of an entity.
+#[derive(Statement)]
+#[aykroyd(text = "DELETE FROM entity_alias WHERE schema_name = $1 AND id = $2")]
+pub struct EntityAliasesDelete<'a> {
+    #[aykroyd(param = "$1")]
+    pub schema: &'a str,
+
+    #[aykroyd(param = "$2")]
+    pub id: &'a str,
+}
+
+#[derive(FromRow)]
+pub struct DuplicateRow {
+    pub schema_name: String,
+    pub value: String,
+    /// The ids of the entities, one per line.
+    pub ids: String,
+}
+
+/// The entities of a schema that have the same value, ignoring case and
+/// surrounding space, for properties of a name.
+#[derive(Query)]
+#[aykroyd(
+    row(DuplicateRow),
+    text = "
+    SELECT schema_name, min(value) AS value, group_concat(id, char(10)) AS ids FROM (
+        SELECT entity_schema_name AS schema_name, lower(trim(value)) AS key, min(value) AS value, entity_id AS id
+        FROM entity_property WHERE property_name = $1
+        GROUP BY schema_name, key, id
+    )
+    GROUP BY schema_name, key HAVING count(*) > 1
+    ORDER BY schema_name, key
+"
+)]
+pub struct DuplicatesQuery<'a> {
+    #[aykroyd(param = "$1")]
+    pub name: &'a str,
+}
+
+#[derive(FromRow)]
+pub struct PropertyChangeRow {
+    pub id: i64,
+    pub op: String,
+    pub entity_schema_name: String,
+    pub entity_id: String,
+    pub property_schema_name: String,
+    pub property_name: String,
+    pub value: String,
+}
+
+/// The changes to property values after a change, in order.
+#[derive(Query)]
+#[aykroyd(
+    row(PropertyChangeRow),
+    text = "
+        SELECT id, op, entity_schema_name, entity_id, property_schema_name, property_name, value
+        FROM entity_property_change WHERE id > $1 ORDER BY id
+")]
+pub struct PropertyChangesQuery(pub i64);
+
+#[derive(FromRow)]
+pub struct PropertyChangeId(pub i64);
+
+#[derive(QueryOne)]
+#[aykroyd(
+    row(PropertyChangeId),
+    text = "SELECT COALESCE(MAX(id), 0) FROM entity_property_change"
+)]
+pub struct LastPropertyChangeQuery;
+
+/// Forgets the changes to property values every watch has seen.
+#[derive(Statement)]
+#[aykroyd(text = "
+    DELETE FROM entity_property_change
+    WHERE NOT EXISTS (SELECT 1 FROM watcher WHERE last_change < entity_property_change.id)
+")]
+pub struct PropertyChangesDelete;
+
+#[derive(Statement)]
+#[aykroyd(text = "INSERT INTO watcher (last_change, expire_date) VALUES ($1, $2)")]
+pub struct WatcherInsert<'a> {
+    #[aykroyd(param = "$1")]
+    pub last_change: i64,
+    #[aykroyd(param = "$2")]
+    pub expire_date: &'a str,
+}
+
+#[derive(Statement)]
+#[aykroyd(text = "UPDATE watcher SET last_change = $1, expire_date = $2 WHERE id = $3")]
+pub struct WatcherUpdate<'a> {
+    #[aykroyd(param = "$1")]
+    pub last_change: i64,
+    #[aykroyd(param = "$2")]
+    pub expire_date: &'a str,
+    #[aykroyd(param = "$3")]
+    pub id: i64,
+}
+
+/// Forgets the watches that expired before a date, and with them the
+/// changes they had yet to see.
+#[derive(Statement)]
+#[aykroyd(text = "DELETE FROM watcher WHERE expire_date <= $1")]
+pub struct WatchersExpiredDelete<'a>(pub &'a str);
+
+#[derive(Statement)]
+#[aykroyd(text = "DELETE FROM watcher WHERE id = $1")]
+pub struct WatcherDelete(pub i64);
@@ -0,0 +1,102 @@
+use aykroyd::{FromRow, Query, Statement};
+
+#[derive(FromRow)]
+pub struct ImportFileRow {
+    pub path: String,
+    pub hash: String,
+}
+
+#[derive(Query)]
+#[aykroyd(
+    row(ImportFileRow),
+    text = "SELECT path, hash FROM import_file WHERE schema_name = $1"
+)]
+pub struct ImportFilesQuery<'a> {
+    #[aykroyd(param = "$1")]
+    pub schema_name: &'a str,
+}
+
+#[derive(Statement)]
+#[aykroyd(text = "
+    INSERT INTO import_file (schema_name, path, hash) VALUES ($1, $2, $3)
+    ON CONFLICT (schema_name, path) DO UPDATE SET hash = excluded.hash
+")]
+pub struct UpsertImportFileStatement<'a> {
+    #[aykroyd(param = "$1")]
+    pub schema_name: &'a str,
+    #[aykroyd(param = "$2")]
+    pub path: &'a str,
+    #[aykroyd(param = "$3")]
+    pub hash: &'a str,
+}
+
+#[derive(Statement)]
+#[aykroyd(text = "DELETE FROM import_file WHERE schema_name = $1 AND path = $2")]
+pub struct ImportFileDelete<'a> {
+    #[aykroyd(param = "$1")]
+    pub schema_name: &'a str,
+    #[aykroyd(param = "$2")]
+    pub path: &'a str,
+}
+
+#[derive(FromRow)]
+pub struct ImportEntityRow {
+    pub path: String,
+    pub entity_id: String,
+}
+
+#[derive(Query)]
+#[aykroyd(
+    row(ImportEntityRow),
+    text = "SELECT path, entity_id FROM import_entity WHERE schema_name = $1"
+)]
+pub struct ImportEntitiesQuery<'a> {
+    #[aykroyd(param = "$1")]
+    pub schema_name: &'a str,
+}
+
+#[derive(Statement)]
+#[aykroyd(text = "
+    INSERT INTO import_entity (schema_name, path, entity_id) VALUES ($1, $2, $3) ON CONFLICT DO NOTHING
+")]
+pub struct InsertImportEntityStatement<'a> {
+    #[aykroyd(param = "$1")]
+    pub schema_name: &'a str,
+    #[aykroyd(param = "$2")]
+    pub path: &'a str,
+    #[aykroyd(param = "$3")]
+    pub entity_id: &'a str,
+}
+
+#[derive(Statement)]
+#[aykroyd(text = "DELETE FROM import_entity WHERE schema_name = $1 AND path = $2")]
+pub struct ImportEntitiesForFileDelete<'a> {
+    #[aykroyd(param = "$1")]
+    pub schema_name: &'a str,
+    #[aykroyd(param = "$2")]
+    pub path: &'a str,
+}
+
+#[derive(Statement)]
+#[aykroyd(text = "DELETE FROM import_entity WHERE schema_name = $1 AND entity_id = $2")]
+pub struct ImportEntityDelete<'a> {
+    #[aykroyd(param = "$1")]
+    pub schema_name: &'a str,
+    #[aykroyd(param = "$2")]
+    pub entity_id: &'a str,
+}
+
+/// Makes the files an entity was imported from count as files of another,
+/// unless they already are.
+#[derive(Statement)]
+#[aykroyd(text = "
+    UPDATE OR IGNORE import_entity SET entity_id = $1 WHERE schema_name = $2 AND entity_id = $3
+")]
+pub struct ImportEntityRename<'a> {
+    #[aykroyd(param = "$2")]
+    pub schema_name: &'a str,
+    #[aykroyd(param = "$3")]
+    pub entity_id: &'a str,
+    #[aykroyd(param = "$1")]
+    pub new_id: &'a str,
+}
@@ -1,3 +1,9 @@
 pub mod entity;
 pub mod source;
-pub mod document;
\ No newline at end of file
+pub mod document;
+pub mod schema;
+pub mod import;
+pub mod audit;
+pub mod pipeline;
+pub mod search;
+pub mod stat;
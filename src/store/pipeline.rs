@@ -0,0 +1,39 @@
+use aykroyd::{FromRow, Query, Statement};
+use serde::Serialize;
+
+/// Records a run of a pipeline, with the error it failed with if it did.
+#[derive(Statement)]
+#[aykroyd(text = "
+    INSERT INTO pipeline_run (pipeline, run_date, document_id, error)
+    VALUES ($1, strftime('%Y-%m-%dT%H:%M:%SZ', 'now'), $2, $3)
+")]
+pub struct PipelineRunInsert<'a> {
+    #[aykroyd(param = "$1")]
+    pub pipeline: &'a str,
+
+    /// The crawled document the pipeline imported.
+    #[aykroyd(param = "$2")]
+    pub document_id: i64,
+
+    #[aykroyd(param = "$3")]
+    pub error: Option<&'a str>,
+}
+
+#[derive(FromRow, Serialize)]
+pub struct PipelineRunRow {
+    pub pipeline: String,
+    pub run_date: String,
+    pub document_id: i64,
+    pub error: Option<String>,
+}
+
+/// The latest run of each pipeline.
+#[derive(Query)]
+#[aykroyd(
+    row(PipelineRunRow),
+    text = "
+        SELECT pipeline, run_date, document_id, error FROM pipeline_run
+        WHERE id IN (SELECT max(id) FROM pipeline_run GROUP BY pipeline)
+"
+)]
+pub struct LatestPipelineRuns;
@@ -0,0 +1,121 @@
+use aykroyd::{FromRow, Query, QueryOne, Statement};
+
+use crate::schema::{Cardinality, Type};
+
+#[derive(FromRow)]
+pub struct SchemaNameRow {
+    pub name: String,
+}
+
+#[derive(Query)]
+#[aykroyd(row(SchemaNameRow), text = "SELECT name FROM schema")]
+pub struct SchemaNamesQuery;
+
+#[derive(FromRow)]
+pub struct SchemaPropertyRow {
+    pub schema_name: String,
+    pub name: String,
+    #[aykroyd(column = "type")]
+    pub typ: Type,
+    pub required: bool,
+    pub cardinality: Cardinality,
+    pub label: Option<String>,
+    pub description: Option<String>,
+    pub display_order: Option<i64>,
+}
+
+#[derive(Query)]
+#[aykroyd(
+    row(SchemaPropertyRow),
+    text = "
+        SELECT schema_name, name, type, required, cardinality, label, description, display_order
+        FROM schema_property
+    "
+)]
+pub struct SchemaPropertiesQuery;
+
+#[derive(Statement)]
+#[aykroyd(text = "
+    UPDATE schema_property SET label = $1, description = $2, display_order = $3
+    WHERE schema_name = $4 AND name = $5
+")]
+pub struct UpdateSchemaPropertyDisplayStatement<'a> {
+    #[aykroyd(param = "$1")]
+    pub label: Option<&'a str>,
+    #[aykroyd(param = "$2")]
+    pub description: Option<&'a str>,
+    #[aykroyd(param = "$3")]
+    pub display_order: Option<i64>,
+    #[aykroyd(param = "$4")]
+    pub schema_name: &'a str,
+    #[aykroyd(param = "$5")]
+    pub name: &'a str,
+}
+
+#[derive(FromRow)]
+pub struct SchemaPropertyValueRow {
+    pub schema_name: String,
+    pub property_name: String,
+    pub value: String,
+}
+
+#[derive(Query)]
+#[aykroyd(
+    row(SchemaPropertyValueRow),
+    text = "SELECT schema_name, property_name, value FROM schema_property_value"
+)]
+pub struct SchemaPropertyValuesQuery;
+
+#[derive(FromRow)]
+pub struct SchemaExtendRow {
+    pub schema_name: String,
+    pub extends: String,
+}
+
+#[derive(Query)]
+#[aykroyd(
+    row(SchemaExtendRow),
+    text = "SELECT schema_name, extends FROM schema_extend"
+)]
+pub struct SchemaExtendsQuery;
+
+#[derive(FromRow)]
+pub struct SchemaOverrideRow {
+    pub schema_name: String,
+    pub property_name: String,
+    pub from_schema: String,
+}
+
+#[derive(Query)]
+#[aykroyd(
+    row(SchemaOverrideRow),
+    text = "SELECT schema_name, property_name, from_schema FROM schema_override"
+)]
+pub struct SchemaOverridesQuery;
+
+#[derive(FromRow)]
+pub struct SchemaRow {
+    pub name: String,
+    #[aykroyd(column = "abstract")]
+    pub abstrct: bool,
+}
+
+#[derive(Query)]
+#[aykroyd(row(SchemaRow), text = "SELECT name, abstract FROM schema")]
+pub struct SchemasQuery;
+
+#[derive(FromRow)]
+pub struct SchemaVersionRow {
+    pub version: Option<i64>,
+}
+
+#[derive(QueryOne)]
+#[aykroyd(
+    row(SchemaVersionRow),
+    text = "SELECT MAX(version) AS version FROM schema_version"
+)]
+pub struct SchemaVersionQuery;
+
+#[derive(Statement)]
+#[aykroyd(text = "INSERT INTO schema_version (applied_date) VALUES (datetime('now'))")]
+pub struct InsertSchemaVersionStatement;
@@ -0,0 +1,70 @@
+use aykroyd::{FromRow, Query, Statement};
+use serde::Serialize;
+
+/// Saves a search under a name, replacing any saved under it before. Only
+/// documents crawled after it is saved count as new results.
+#[derive(Statement)]
+#[aykroyd(text = "
+    INSERT OR REPLACE INTO saved_search (name, kind, query, language, attribute, notify, last_document_id)
+    VALUES ($1, $2, $3, $4, $5, $6, (SELECT coalesce(max(id), 0) FROM document))
+")]
+pub struct SavedSearchInsert<'a> {
+    #[aykroyd(param = "$1")]
+    pub name: &'a str,
+
+    /// `document` for a search of crawled documents, `entity` for entities
+    /// with a value.
+    #[aykroyd(param = "$2")]
+    pub kind: &'a str,
+
+    /// The full-text search, or the value of the entity filter.
+    #[aykroyd(param = "$3")]
+    pub query: &'a str,
+
+    #[aykroyd(param = "$4")]
+    pub language: Option<&'a str>,
+
+    /// The property of the entity filter as `schema.name`.
+    #[aykroyd(param = "$5")]
+    pub attribute: Option<&'a str>,
+
+    /// Whether webhooks are told of new results after a crawl.
+    #[aykroyd(param = "$6")]
+    pub notify: bool,
+}
+
+#[derive(FromRow, Serialize)]
+pub struct SavedSearchRow {
+    pub name: String,
+    pub kind: String,
+    pub query: String,
+    pub language: Option<String>,
+    pub attribute: Option<String>,
+    pub notify: bool,
+    pub last_document_id: i64,
+}
+
+#[derive(Query)]
+#[aykroyd(
+    row(SavedSearchRow),
+    text = "
+        SELECT name, kind, query, language, attribute, notify, last_document_id FROM saved_search
+        ORDER BY name
+"
+)]
+pub struct SavedSearches;
+
+#[derive(Statement)]
+#[aykroyd(text = "DELETE FROM saved_search WHERE name = $1")]
+pub struct SavedSearchDelete<'a>(pub &'a str);
+
+/// Records the latest document a saved search was notified of.
+#[derive(Statement)]
+#[aykroyd(text = "UPDATE saved_search SET last_document_id = $1 WHERE name = $2")]
+pub struct SavedSearchSeen<'a> {
+    #[aykroyd(param = "$2")]
+    pub name: &'a str,
+
+    #[aykroyd(param = "$1")]
+    pub last_document_id: i64,
+}
@@ -17,13 +17,15 @@ pub struct GetSourceUrlQuery {
 pub struct StaleSourceRow {
     pub id: i64,
     pub url: String,
+    pub main_content: bool,
+    pub max_pages: i64,
 }
 
 #[derive(Query)]
 #[aykroyd(
     row(StaleSourceRow),
     text = "
-        SELECT id, url FROM source WHERE (((crawl_date IS NULL) OR (unixepoch('now') - unixepoch(crawl_date)) > 12 * 60 * 60) OR force_crawl = TRUE)
+        SELECT id, url, main_content, max_pages FROM source WHERE (((crawl_date IS NULL) OR (unixepoch('now') - unixepoch(crawl_date)) > 12 * 60 * 60) OR force_crawl = TRUE)
     "
 )]
 pub struct StaleSources;
@@ -53,9 +55,9 @@ pub struct UpdateCrawlDate<'a>(pub i64, pub &'a str);
 
 #[derive(Statement)]
 #[aykroyd(text = "
-    INSERT INTO source (url) VALUES ($1)
+    INSERT INTO source (url, main_content, max_pages) VALUES ($1, $2, $3)
 ")]
-pub struct AddSource<'a>(pub &'a str);
+pub struct AddSource<'a>(pub &'a str, pub bool, pub i64);
 
 #[derive(FromRow, Debug)]
 pub struct SimpleSourceRow {
@@ -70,3 +72,56 @@ pub struct GetSourceByIdQuery {
     #[aykroyd(param = "$1")]
     pub id: i64,
 }
+
+#[derive(Statement)]
+#[aykroyd(text = "
+    INSERT INTO source_selector (source_id, name, selector)
+    VALUES ((SELECT id FROM source WHERE url = $1), $2, $3)
+")]
+pub struct AddSourceSelector<'a> {
+    pub url: &'a str,
+    pub name: &'a str,
+    pub selector: &'a str,
+}
+
+#[derive(FromRow)]
+pub struct SourceSelectorRow {
+    pub name: String,
+    pub selector: String,
+}
+
+#[derive(Query)]
+#[aykroyd(
+    row(SourceSelectorRow),
+    text = "SELECT name, selector FROM source_selector WHERE source_id = $1 ORDER BY rowid"
+)]
+pub struct SourceSelectors(pub i64);
+
+#[derive(FromRow)]
+pub struct SourceConfigRow {
+    pub id: i64,
+    pub url: String,
+    pub main_content: bool,
+    pub max_pages: i64,
+}
+
+#[derive(Query)]
+#[aykroyd(
+    row(SourceConfigRow),
+    text = "SELECT id, url, main_content, max_pages FROM source ORDER BY url"
+)]
+pub struct SourceConfigs;
+
+/// Adds a source, or updates how an existing one is crawled.
+#[derive(Statement)]
+#[aykroyd(text = "
+    INSERT INTO source (url, main_content, max_pages) VALUES ($1, $2, $3)
+    ON CONFLICT(url) DO UPDATE SET main_content = excluded.main_content, max_pages = excluded.max_pages
+")]
+pub struct UpsertSource<'a>(pub &'a str, pub bool, pub i64);
+
+#[derive(Statement)]
+#[aykroyd(text = "
+    DELETE FROM source_selector WHERE source_id = (SELECT id FROM source WHERE url = $1)
+")]
+pub struct SourceSelectorsDelete<'a>(pub &'a str);
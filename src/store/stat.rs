@@ -0,0 +1,55 @@
+use aykroyd::{FromRow, Query, QueryOne};
+use serde::Serialize;
+
+#[derive(FromRow, Serialize)]
+pub struct TotalsRow {
+    pub entities: i64,
+    pub values: i64,
+    pub sources: i64,
+    pub documents: i64,
+}
+
+#[derive(QueryOne)]
+#[aykroyd(
+    row(TotalsRow),
+    text = "
+        SELECT
+            (SELECT count(*) FROM entity) AS entities,
+            (SELECT count(*) FROM entity_property) AS \"values\",
+            (SELECT count(*) FROM source) AS sources,
+            (SELECT count(*) FROM document) AS documents
+"
+)]
+pub struct TotalsQuery;
+
+#[derive(FromRow, Serialize)]
+pub struct AttributeStatRow {
+    pub property_schema_name: String,
+    pub property_name: String,
+    pub values: i64,
+    pub distinct_values: i64,
+    /// The average size of a value in bytes.
+    pub average_size: f64,
+    /// When a value was last written, unknown for values written before
+    /// provenance was recorded.
+    pub last_modified: Option<String>,
+}
+
+/// The values of each attribute, how many differ and how large they are.
+#[derive(Query)]
+#[aykroyd(
+    row(AttributeStatRow),
+    text = "
+        SELECT
+            property_schema_name,
+            property_name,
+            count(*) AS \"values\",
+            count(DISTINCT value) AS distinct_values,
+            avg(length(CAST(value AS BLOB))) AS average_size,
+            max(provenance_date) AS last_modified
+        FROM entity_property
+        GROUP BY property_schema_name, property_name
+        ORDER BY property_schema_name, property_name
+"
+)]
+pub struct AttributeStatsQuery;
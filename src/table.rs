@@ -0,0 +1,183 @@
+use std::{collections::BTreeMap, fs, io::Cursor, path::Path};
+
+use anyhow::{Context, Result, bail};
+use calamine::Reader;
+use tempfile::TempDir;
+use tracing::instrument;
+
+use crate::import;
+
+/// A table read from an uploaded CSV file or spreadsheet, as text.
+#[derive(Debug, Default, PartialEq)]
+pub struct Table {
+    pub header: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+/// Whether a file can be read as a table, by its extension.
+pub fn supported(file_name: &str) -> bool {
+    let extension = Path::new(file_name)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or_default();
+    ["csv", "xlsx", "xlsm", "xls", "ods"].contains(&extension.to_lowercase().as_str())
+}
+
+/// Reads a CSV file, or the first sheet of a spreadsheet, whose first row is
+/// the header.
+pub fn read(file_name: &str, bytes: &[u8]) -> Result<Table> {
+    if !supported(file_name) {
+        bail!("{} is not a CSV file or a spreadsheet", file_name);
+    }
+    if file_name.to_lowercase().ends_with(".csv") {
+        return Table::from_csv(bytes).with_context(|| format!("could not read {}", file_name));
+    }
+
+    let mut workbook = calamine::open_workbook_auto_from_rs(Cursor::new(bytes))
+        .with_context(|| format!("could not open {}", file_name))?;
+    let range = workbook
+        .worksheet_range_at(0)
+        .with_context(|| format!("{} has no sheets", file_name))?
+        .with_context(|| format!("could not read the first sheet of {}", file_name))?;
+    let mut rows = range
+        .rows()
+        .map(|row| row.iter().map(|cell| cell.to_string()).collect::<Vec<_>>());
+    let header = rows.next().unwrap_or_default();
+
+    Ok(Table {
+        header,
+        rows: rows.collect(),
+    })
+}
+
+impl Table {
+    /// Reads a CSV file whose first row is the header.
+    pub fn from_csv(bytes: &[u8]) -> Result<Table> {
+        let mut reader = csv::Reader::from_reader(bytes);
+        let header = reader.headers()?.iter().map(String::from).collect();
+        let rows = reader
+            .records()
+            .map(|record| Ok(record?.iter().map(String::from).collect()))
+            .collect::<Result<_>>()?;
+
+        Ok(Table { header, rows })
+    }
+
+    /// The table as a CSV file, as it is imported.
+    pub fn to_csv(&self) -> Result<String> {
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        writer.write_record(&self.header)?;
+        for row in &self.rows {
+            writer.write_record(row)?;
+        }
+
+        Ok(String::from_utf8(writer.into_inner()?)?)
+    }
+}
+
+/// A column imported as the values of a property.
+#[derive(Debug)]
+pub struct Column {
+    pub header: String,
+    /// The property as `schema.name`.
+    pub attribute: String,
+}
+
+/// A mapping that imports the columns of a table as properties, with a row's
+/// id from the slug of a column, or its row number when none is given. Empty
+/// cells give no value, and columns of the same property give it a value
+/// each.
+pub fn mapping(columns: &[Column], id_column: Option<&str>) -> Result<String> {
+    let mut filters: BTreeMap<&str, BTreeMap<&str, Vec<String>>> = BTreeMap::new();
+    for column in columns {
+        let (property_schema, name) = column
+            .attribute
+            .split_once('.')
+            .with_context(|| format!("{} is not written as schema.name", column.attribute))?;
+        filters
+            .entry(property_schema)
+            .or_default()
+            .entry(name)
+            .or_default()
+            .push(format!(".[{}]", serde_json::to_string(&column.header)?));
+    }
+
+    let mut mapping = toml::Table::new();
+    if let Some(id_column) = id_column {
+        mapping.insert(
+            "id".to_string(),
+            format!(".[{}] | slugify", serde_json::to_string(id_column)?).into(),
+        );
+    }
+    let mut properties = toml::Table::new();
+    for (property_schema, names) in filters {
+        let mut schema_properties = toml::Table::new();
+        for (name, cells) in names {
+            let filter = format!("({}) | select(. != \"\")", cells.join(", "));
+            schema_properties.insert(name.to_string(), filter.into());
+        }
+        properties.insert(property_schema.to_string(), schema_properties.into());
+    }
+    mapping.insert("properties".to_string(), properties.into());
+
+    Ok(toml::to_string(&mapping)?)
+}
+
+/// Imports the rows of a table as entities of a schema, through a mapping
+/// of its columns written next to it in a temporary directory. Rows take
+/// their ids from the id column, or else from the file name and their row
+/// number, so uploading the file again replaces the entities it gave. The
+/// file is not recorded as imported, so syncing a data directory leaves its
+/// entities be.
+#[instrument(skip_all, fields(file = file_name, schema))]
+pub fn import(
+    db_path: &Path,
+    file_name: &str,
+    table: &Table,
+    schema: &str,
+    columns: &[Column],
+    id_column: Option<&str>,
+    lenient: bool,
+) -> Result<()> {
+    // removed when dropped, and unique to this upload
+    let dir = TempDir::with_prefix("pika-table-").context("could not create a temporary directory")?;
+    let data_path = dir.path().join("data");
+    let mapping_path = dir.path().join("mapping");
+    let stem: String = Path::new(file_name)
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("table")
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    let data_file = data_path.join(schema).join(format!("{}.csv", stem));
+    fs::create_dir_all(data_path.join(schema))?;
+    fs::create_dir_all(&mapping_path)?;
+    fs::write(&data_file, table.to_csv()?)
+        .with_context(|| format!("could not write {}", data_file.display()))?;
+    fs::write(
+        mapping_path.join(format!("{}.toml", schema)),
+        mapping(columns, id_column)?,
+    )?;
+
+    import::run(
+        db_path,
+        data_path,
+        mapping_path,
+        &import::Options {
+            lenient,
+            force: true,
+            schema: Some(schema.to_string()),
+            actor: Some("web"),
+            provenance: Some(format!("upload:{}", file_name)),
+            untracked: true,
+            ..Default::default()
+        },
+    )
+}
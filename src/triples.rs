@@ -0,0 +1,227 @@
+use anyhow::{Context, Result, bail};
+use aykroyd::rusqlite::{Client, Transaction};
+use jaq_json::Val;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    fs::File,
+    io::{self, BufRead, BufReader, Write},
+    path::Path,
+};
+use tracing::{info, instrument};
+
+use crate::{
+    mapper::Property,
+    progress::Progress,
+    store::{
+        audit::AuditInsert,
+        entity::{InsertEntityStatement, PropertyForEntityDelete, PropertyForEntitySchemaInsert},
+    },
+    validate::Validator,
+};
+
+const TRIPLES_PER_BATCH: usize = 1000;
+
+/// A property value of an entity, as one line of JSON.
+///
+/// The entity is written as `schema/id` and the attribute as `schema.name`.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Triple {
+    pub e: String,
+    pub a: String,
+    pub v: String,
+}
+
+/// Calls `f` with every property value of a database as a triple, in entity,
+/// attribute and position order.
+pub fn for_each(db: &Client, mut f: impl FnMut(Triple) -> Result<()>) -> Result<()> {
+    // read row by row, as a database may not fit in memory
+    let mut statement = db.as_ref().prepare(
+        "SELECT entity_schema_name, entity_id, property_schema_name, property_name, value
+        FROM entity_property
+        ORDER BY entity_schema_name, entity_id, property_schema_name, property_name, position",
+    )?;
+    let mut rows = statement.query([])?;
+    while let Some(row) = rows.next()? {
+        let triple = Triple {
+            e: format!("{}/{}", row.get::<_, String>(0)?, row.get::<_, String>(1)?),
+            a: format!("{}.{}", row.get::<_, String>(2)?, row.get::<_, String>(3)?),
+            v: row.get(4)?,
+        };
+        f(triple)?;
+    }
+
+    Ok(())
+}
+
+/// Writes every property value of a database as a JSON Lines triple, in
+/// entity, attribute and position order.
+pub fn export(db_path: &Path, out: impl Write) -> Result<()> {
+    let db = Client::open(db_path)?;
+    let mut out = io::BufWriter::new(out);
+    for_each(&db, |triple| {
+        serde_json::to_writer(&mut out, &triple)?;
+        out.write_all(b"\n")?;
+        Ok(())
+    })?;
+    out.flush()?;
+
+    Ok(())
+}
+
+/// Writes the triples of a JSON Lines file, or of stdin for `-`, to a
+/// database.
+///
+/// The values of each attribute of an entity in the file replace those in the
+/// database, in the order of the file. Values are checked against the schemas
+/// and written in batches, each reported to `progress`, all in one
+/// transaction so that an invalid triple leaves the database unchanged.
+#[instrument(skip(db_path, progress), fields(path = %path.display()))]
+pub fn import(db_path: &Path, path: &Path, progress: &dyn Progress) -> Result<()> {
+    let reader: Box<dyn BufRead> = if path == Path::new("-") {
+        Box::new(io::stdin().lock())
+    } else {
+        let file =
+            File::open(path).with_context(|| format!("could not open {}", path.display()))?;
+        Box::new(BufReader::new(file))
+    };
+
+    let mut db = Client::open(db_path)?;
+    let validator = Validator::load(&mut db)?;
+    let mut txn = db.transaction()?;
+
+    // the next position of each attribute of an entity written so far
+    let mut positions: HashMap<(String, String), i64> = HashMap::new();
+    // the attributes of entities given a value so far
+    let mut seen: HashSet<(String, String)> = HashSet::new();
+    let mut batch = Vec::new();
+    let mut count = 0;
+    let provenance = format!("triples:{}", path.display());
+    progress.start("triples", None);
+    for (index, line) in reader.lines().enumerate() {
+        let line = line.with_context(|| format!("could not read {}", path.display()))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let triple: Triple = serde_json::from_str(&line)
+            .with_context(|| format!("could not parse line {} of {}", index + 1, path.display()))?;
+        let repeated = !seen.insert((triple.e.clone(), triple.a.clone()));
+        check(&validator, &triple, repeated)
+            .with_context(|| format!("invalid triple on line {}", index + 1))?;
+        batch.push(triple);
+        if batch.len() == TRIPLES_PER_BATCH {
+            let written = write(&mut txn, &mut positions, &provenance, batch.drain(..))?;
+            progress.advance(written as u64);
+            count += written;
+        }
+    }
+    count += write(&mut txn, &mut positions, &provenance, batch.drain(..))?;
+    txn.execute(&AuditInsert {
+        actor: "cli",
+        action: "triples_import",
+        target: &path.display().to_string(),
+        detail: Some(&format!("{} values", count)),
+    })?;
+    txn.commit()?;
+    progress.finish();
+
+    info!(
+        "imported {} values of {} attributes",
+        count,
+        positions.len()
+    );
+
+    Ok(())
+}
+
+fn split(triple: &Triple) -> Result<(&str, &str, &str, &str)> {
+    let Some((schema, id)) = triple.e.split_once('/') else {
+        bail!("entity {} is not written as schema/id", triple.e);
+    };
+    let Some((property_schema, property_name)) = triple.a.split_once('.') else {
+        bail!("attribute {} is not written as schema.name", triple.a);
+    };
+
+    Ok((schema, id, property_schema, property_name))
+}
+
+/// Checks a triple as imports check a property, given whether its attribute
+/// of its entity had a value earlier in the file.
+fn check(validator: &Validator, triple: &Triple, repeated: bool) -> Result<()> {
+    let (schema, _, property_schema, property_name) = split(triple)?;
+    if !validator.has_schema(schema) {
+        bail!("unknown schema {}", schema);
+    }
+    let property = Property {
+        schema: property_schema.to_string(),
+        name: property_name.to_string(),
+        filter: String::new(),
+        value: Val::utf8_str(triple.v.clone()),
+    };
+    let previous = if repeated {
+        std::slice::from_ref(&property)
+    } else {
+        &[]
+    };
+    validator.check(schema, &property, previous)?;
+
+    Ok(())
+}
+
+fn write(
+    txn: &mut Transaction,
+    positions: &mut HashMap<(String, String), i64>,
+    provenance: &str,
+    triples: impl Iterator<Item = Triple>,
+) -> Result<usize> {
+    let mut count = 0;
+    for triple in triples {
+        write_triple(txn, positions, provenance, &triple)
+            .with_context(|| format!("could not write {} {} {}", triple.e, triple.a, triple.v))?;
+        count += 1;
+    }
+
+    Ok(count)
+}
+
+fn write_triple(
+    txn: &mut Transaction,
+    positions: &mut HashMap<(String, String), i64>,
+    provenance: &str,
+    triple: &Triple,
+) -> Result<()> {
+    let (schema, id, property_schema, property_name) = split(triple)?;
+    let key = (triple.e.clone(), triple.a.clone());
+    let position = match positions.get_mut(&key) {
+        Some(position) => {
+            *position += 1;
+            *position
+        }
+        None => {
+            // the first value of an attribute replaces those already stored
+            txn.execute(&InsertEntityStatement {
+                schema_name: schema,
+                id,
+            })?;
+            txn.execute(&PropertyForEntityDelete {
+                schema,
+                id,
+                property_schema,
+                name: property_name,
+            })?;
+            positions.insert(key, 0);
+            0
+        }
+    };
+    txn.execute(&PropertyForEntitySchemaInsert {
+        schema,
+        id,
+        property_schema,
+        name: property_name,
+        value: &triple.v,
+        position,
+        provenance,
+    })?;
+
+    Ok(())
+}
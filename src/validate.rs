@@ -0,0 +1,452 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    path::Path,
+};
+
+use anyhow::{Result, bail};
+use aykroyd::rusqlite::Client;
+use jaq_json::Val;
+use serde::Serialize;
+
+use crate::{
+    mapper::Property,
+    schema::{Cardinality, Type, inspect::Format},
+    store::{
+        entity::{EntitiesQuery, PropertyForEntityQuery},
+        schema::{
+            SchemaExtendsQuery, SchemaNamesQuery, SchemaOverridesQuery, SchemaPropertiesQuery,
+            SchemaPropertyValuesQuery,
+        },
+    },
+};
+
+#[derive(thiserror::Error, Debug)]
+pub enum ValidationError {
+    #[error("unknown schema {0}")]
+    UnknownSchema(String),
+    #[error("unknown property {name} for schema {schema}")]
+    UnknownProperty { schema: String, name: String },
+    #[error("property {name} of schema {schema} expects a {expected} but got {value}")]
+    TypeMismatch {
+        schema: String,
+        name: String,
+        expected: Type,
+        value: String,
+    },
+    #[error("property {name} of schema {schema} does not allow {value}, only {}", allowed.join(", "))]
+    NotAllowed {
+        schema: String,
+        name: String,
+        value: String,
+        allowed: Vec<String>,
+    },
+    #[error("property {name} of schema {schema} holds a single value")]
+    TooManyValues { schema: String, name: String },
+    #[error("missing required property {name} of schema {schema}")]
+    MissingProperty { schema: String, name: String },
+    #[error("property {name} of schema {schema} belongs to a schema that {entity_schema} does not extend")]
+    NotExtended {
+        entity_schema: String,
+        schema: String,
+        name: String,
+    },
+    #[error("property {name} of schema {schema} names {value}, which does not exist")]
+    DanglingReference {
+        schema: String,
+        name: String,
+        value: String,
+    },
+}
+
+/// Checks properties against the schema tables of a database.
+pub struct Validator {
+    schemas: HashMap<String, HashMap<String, Type>>,
+    /// The names of the required properties of each schema.
+    required: HashMap<String, Vec<String>>,
+    /// The schemas each schema extends.
+    extends: HashMap<String, Vec<String>>,
+    /// The values allowed for each enum property, by schema and property name.
+    values: HashMap<(String, String), Vec<String>>,
+    /// The properties that hold many values, by schema and property name.
+    many: HashSet<(String, String)>,
+    /// For properties inherited with conflicting definitions, the schema whose
+    /// definition is used, by schema and property name.
+    overrides: HashMap<(String, String), String>,
+}
+
+impl Validator {
+    pub fn load(db: &mut Client) -> Result<Self> {
+        let mut schemas: HashMap<String, HashMap<String, Type>> = HashMap::new();
+        for row in db.query(&SchemaNamesQuery)? {
+            schemas.entry(row.name).or_default();
+        }
+        let mut required: HashMap<String, Vec<String>> = HashMap::new();
+        let mut many = HashSet::new();
+        for row in db.query(&SchemaPropertiesQuery)? {
+            if row.cardinality == Cardinality::Many {
+                many.insert((row.schema_name.clone(), row.name.clone()));
+            }
+            if row.required {
+                required
+                    .entry(row.schema_name.clone())
+                    .or_default()
+                    .push(row.name.clone());
+            }
+            schemas
+                .entry(row.schema_name)
+                .or_default()
+                .insert(row.name, row.typ);
+        }
+        for names in required.values_mut() {
+            names.sort();
+        }
+        let mut extends: HashMap<String, Vec<String>> = HashMap::new();
+        for row in db.query(&SchemaExtendsQuery)? {
+            extends.entry(row.schema_name).or_default().push(row.extends);
+        }
+
+        let mut values: HashMap<(String, String), Vec<String>> = HashMap::new();
+        for row in db.query(&SchemaPropertyValuesQuery)? {
+            values
+                .entry((row.schema_name, row.property_name))
+                .or_default()
+                .push(row.value);
+        }
+        let overrides = db
+            .query(&SchemaOverridesQuery)?
+            .into_iter()
+            .map(|row| ((row.schema_name, row.property_name), row.from_schema))
+            .collect();
+
+        Ok(Self {
+            schemas,
+            required,
+            extends,
+            values,
+            many,
+            overrides,
+        })
+    }
+
+    /// The schema whose definition of a property applies to an entity of
+    /// `schema`: the one `schema` or a schema it extends chose in its
+    /// overrides, or else `property_schema` itself.
+    pub fn definition<'a>(&'a self, schema: &str, property_schema: &'a str, name: &str) -> &'a str {
+        let mut seen = HashSet::new();
+        let mut pending = vec![schema];
+        while let Some(schema) = pending.pop() {
+            if !seen.insert(schema) {
+                continue;
+            }
+            if let Some(from_schema) = self.overrides.get(&(schema.to_string(), name.to_string()))
+                && self.extends(schema, property_schema)
+            {
+                return from_schema;
+            }
+            if let Some(parents) = self.extends.get(schema) {
+                pending.extend(parents.iter().map(String::as_str));
+            }
+        }
+
+        property_schema
+    }
+
+    /// Whether the database has a schema of this name.
+    pub fn has_schema(&self, schema: &str) -> bool {
+        self.schemas.contains_key(schema)
+    }
+
+    /// Whether a property of an entity of `schema` holds many values rather
+    /// than one.
+    pub fn many(&self, schema: &str, property_schema: &str, name: &str) -> bool {
+        let property_schema = self.definition(schema, property_schema, name);
+        self.many.contains(&(property_schema.to_string(), name.to_string()))
+    }
+
+    /// Checks that a property holding a single value is not given another
+    /// when the entity of `schema` already has `properties`.
+    pub fn validate_cardinality(
+        &self,
+        schema: &str,
+        property: &Property,
+        properties: &[Property],
+    ) -> Result<(), ValidationError> {
+        let repeated = properties
+            .iter()
+            .any(|p| p.schema == property.schema && p.name == property.name);
+        if repeated && !self.many(schema, &property.schema, &property.name) {
+            return Err(ValidationError::TooManyValues {
+                schema: property.schema.clone(),
+                name: property.name.clone(),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// The values allowed for a property, if it is an enum.
+    pub fn allowed(&self, schema: &str, name: &str) -> Option<&[String]> {
+        self.values
+            .get(&(schema.to_string(), name.to_string()))
+            .map(Vec::as_slice)
+    }
+
+    /// Checks a value written as text, as from a form, against the values
+    /// allowed for its property in an entity of `schema`.
+    pub fn validate_text(
+        &self,
+        schema: &str,
+        property_schema: &str,
+        name: &str,
+        value: &str,
+    ) -> Result<(), ValidationError> {
+        match self.allowed(self.definition(schema, property_schema, name), name) {
+            Some(allowed) if !allowed.iter().any(|a| a == value) => Err(ValidationError::NotAllowed {
+                schema: property_schema.to_string(),
+                name: name.to_string(),
+                value: value.to_string(),
+                allowed: allowed.to_vec(),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// The names of the required properties of `property_schema` in an
+    /// entity of `schema`, leaving out those whose definition is overridden
+    /// by another schema's.
+    pub fn required<'a>(&'a self, schema: &'a str, property_schema: &'a str) -> impl Iterator<Item = &'a String> {
+        self.required
+            .get(property_schema)
+            .into_iter()
+            .flatten()
+            .filter(move |name| self.definition(schema, property_schema, name) == property_schema)
+    }
+
+    /// Whether an entity of `schema` may have properties of `property_schema`,
+    /// being that schema or extending it.
+    pub fn extends(&self, schema: &str, property_schema: &str) -> bool {
+        let mut seen = HashSet::new();
+        let mut pending = vec![schema];
+        while let Some(schema) = pending.pop() {
+            if schema == property_schema {
+                return true;
+            }
+            if !seen.insert(schema) {
+                continue;
+            }
+            if let Some(parents) = self.extends.get(schema) {
+                pending.extend(parents.iter().map(String::as_str));
+            }
+        }
+
+        false
+    }
+
+    /// Checks that the properties of an entity of `schema` include the
+    /// required properties of its schema and of every schema it extends. A
+    /// property inherited with conflicting definitions is only required if
+    /// the definition chosen for it is, and may be given under any of them.
+    pub fn validate_required(&self, schema: &str, properties: &[Property]) -> Vec<ValidationError> {
+        let mut errors = Vec::new();
+        let mut seen = HashSet::new();
+        let mut pending = vec![schema];
+        while let Some(ancestor) = pending.pop() {
+            if !seen.insert(ancestor) {
+                continue;
+            }
+            for name in self.required(schema, ancestor) {
+                let present = properties.iter().any(|property| {
+                    &property.name == name
+                        && self.definition(schema, &property.schema, name) == ancestor
+                });
+                if !present {
+                    errors.push(ValidationError::MissingProperty {
+                        schema: ancestor.to_string(),
+                        name: name.clone(),
+                    });
+                }
+            }
+            if let Some(parents) = self.extends.get(ancestor) {
+                pending.extend(parents.iter().map(String::as_str));
+            }
+        }
+
+        errors
+    }
+
+    /// Checks a property of an entity of `schema` against its definition.
+    pub fn validate(&self, schema: &str, property: &Property) -> Result<(), ValidationError> {
+        let properties = self
+            .schemas
+            .get(&property.schema)
+            .ok_or_else(|| ValidationError::UnknownSchema(property.schema.clone()))?;
+        if !properties.contains_key(&property.name) {
+            return Err(ValidationError::UnknownProperty {
+                schema: property.schema.clone(),
+                name: property.name.clone(),
+            });
+        }
+        let definition = self.definition(schema, &property.schema, &property.name);
+        let typ = self
+            .schemas
+            .get(definition)
+            .and_then(|properties| properties.get(&property.name))
+            .ok_or_else(|| ValidationError::UnknownProperty {
+                schema: definition.to_string(),
+                name: property.name.clone(),
+            })?;
+
+        let matches = match typ {
+            Type::Name | Type::Enum => matches!(property.value, Val::Str(..)),
+        };
+        if !matches {
+            return Err(ValidationError::TypeMismatch {
+                schema: property.schema.clone(),
+                name: property.name.clone(),
+                expected: *typ,
+                value: property.value.to_string(),
+            });
+        }
+        if let Val::Str(s, _) = &property.value {
+            self.validate_text(schema, &property.schema, &property.name, &String::from_utf8_lossy(s))?;
+        }
+
+        Ok(())
+    }
+
+    /// Checks a property of an entity of `schema` as it is written: against
+    /// its definition, that `schema` is or extends the property's schema, and
+    /// that it is not another value of a property holding one when the entity
+    /// already has `properties`.
+    pub fn check(
+        &self,
+        schema: &str,
+        property: &Property,
+        properties: &[Property],
+    ) -> Result<(), ValidationError> {
+        self.validate(schema, property)?;
+        if !self.extends(schema, &property.schema) {
+            return Err(ValidationError::NotExtended {
+                entity_schema: schema.to_string(),
+                schema: property.schema.clone(),
+                name: property.name.clone(),
+            });
+        }
+        self.validate_cardinality(schema, property, properties)
+    }
+}
+
+/// An entity that failed validation, and why.
+#[derive(Debug, Serialize)]
+pub struct EntityError {
+    pub id: String,
+    pub error: String,
+}
+
+/// How the entities of a schema fared.
+#[derive(Debug, Default, Serialize)]
+pub struct SchemaReport {
+    pub entities: usize,
+    pub errors: Vec<EntityError>,
+}
+
+/// The validation errors of a database, by the schema of the entities.
+#[derive(Debug, Default, Serialize)]
+pub struct Report {
+    pub schemas: BTreeMap<String, SchemaReport>,
+}
+
+impl Report {
+    pub fn errors(&self) -> usize {
+        self.schemas.values().map(|schema| schema.errors.len()).sum()
+    }
+}
+
+/// Checks every entity of a database against its schema.
+///
+/// Besides what imports check, values written as `schema/id` for a schema of
+/// the database must name an entity that exists.
+pub fn check(db: &mut Client) -> Result<Report> {
+    let validator = Validator::load(db)?;
+    let entities = db.query(&EntitiesQuery)?;
+    let existing: HashSet<String> = entities
+        .iter()
+        .map(|entity| format!("{}/{}", entity.schema_name, entity.id))
+        .collect();
+
+    let mut report = Report::default();
+    for entity in entities {
+        let schema = entity.schema_name.as_str();
+        let schema_report = report.schemas.entry(entity.schema_name.clone()).or_default();
+        schema_report.entities += 1;
+        let mut errors = Vec::new();
+        if !validator.has_schema(schema) {
+            errors.push(ValidationError::UnknownSchema(schema.to_string()));
+        }
+
+        let mut properties: Vec<Property> = Vec::new();
+        for row in db.query(&PropertyForEntityQuery { schema, id: &entity.id })? {
+            let property = Property {
+                schema: row.property_schema_name,
+                name: row.property_name,
+                filter: String::new(),
+                value: Val::utf8_str(row.value.clone()),
+            };
+            if let Err(e) = validator.check(schema, &property, &properties) {
+                errors.push(e);
+            }
+            if let Some((value_schema, _)) = row.value.split_once('/')
+                && validator.has_schema(value_schema)
+                && !existing.contains(&row.value)
+            {
+                errors.push(ValidationError::DanglingReference {
+                    schema: property.schema.clone(),
+                    name: property.name.clone(),
+                    value: row.value,
+                });
+            }
+            properties.push(property);
+        }
+        if validator.has_schema(schema) {
+            errors.extend(validator.validate_required(schema, &properties));
+        }
+
+        schema_report
+            .errors
+            .extend(errors.into_iter().map(|e| EntityError {
+                id: entity.id.clone(),
+                error: e.to_string(),
+            }));
+    }
+
+    Ok(report)
+}
+
+/// Prints the validation report of a database, failing if any entity is
+/// invalid.
+pub fn run(db_path: &Path, format: Format) -> Result<()> {
+    let report = check(&mut Client::open(db_path)?)?;
+    match format {
+        Format::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+        Format::Plain => {
+            for (schema, schema_report) in &report.schemas {
+                println!(
+                    "{}: {} entities, {} errors",
+                    schema,
+                    schema_report.entities,
+                    schema_report.errors.len()
+                );
+                for error in &schema_report.errors {
+                    println!("  {}/{}: {}", schema, error.id, error.error);
+                }
+            }
+        }
+    }
+
+    let errors = report.errors();
+    if errors > 0 {
+        bail!("found {} validation errors", errors);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,160 @@
+use anyhow::{Result, bail};
+use aykroyd::rusqlite::Client;
+use chrono::Utc;
+use rusqlite::Connection;
+use serde::Serialize;
+use std::{io::Write, path::Path, thread, time::Duration};
+use tracing::warn;
+
+use crate::{
+    migrate,
+    store::entity::{
+        LastPropertyChangeQuery, PropertyChangesDelete, PropertyChangesQuery, WatcherDelete,
+        WatcherInsert, WatcherUpdate, WatchersExpiredDelete,
+    },
+    triples::Triple,
+};
+
+/// How long a watch outlives its interval without being renewed, after
+/// which it is taken to have stopped and changes are no longer recorded
+/// for it.
+pub const GRACE: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Op {
+    Add,
+    Remove,
+}
+
+/// A triple added to or removed from a database.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct Change {
+    pub op: Op,
+    #[serde(flatten)]
+    pub triple: Triple,
+}
+
+/// The id of the last change to the property values of a database.
+pub fn last_change(db: &mut Client) -> Result<i64> {
+    Ok(db.query_one(&LastPropertyChangeQuery)?.0)
+}
+
+/// The changes to the property values of a database after the change
+/// `after`, in the order they were made, with the id of the last.
+pub fn changes(db: &mut Client, after: i64) -> Result<(i64, Vec<Change>)> {
+    let mut last = after;
+    let mut changes = Vec::new();
+    for row in db.query(&PropertyChangesQuery(after))? {
+        last = row.id;
+        let op = match row.op.as_str() {
+            "add" => Op::Add,
+            "remove" => Op::Remove,
+            op => bail!("unknown change {} to {}/{}", op, row.entity_schema_name, row.entity_id),
+        };
+        changes.push(Change {
+            op,
+            triple: Triple {
+                e: format!("{}/{}", row.entity_schema_name, row.entity_id),
+                a: format!("{}.{}", row.property_schema_name, row.property_name),
+                v: row.value,
+            },
+        });
+    }
+
+    Ok((last, changes))
+}
+
+/// A watch registered in a database. Changes to property values are
+/// recorded while any watch is registered, and removed once every watch has
+/// seen them.
+pub struct Watcher {
+    id: i64,
+    last: i64,
+    ttl: Duration,
+}
+
+fn expire_date(ttl: Duration) -> Result<String> {
+    Ok((Utc::now() + chrono::Duration::from_std(ttl)?)
+        .format("%Y-%m-%dT%H:%M:%SZ")
+        .to_string())
+}
+
+impl Watcher {
+    /// Registers a watch that sees the changes made from now on, and expires
+    /// unless renewed by [`Watcher::changes`] within `ttl`.
+    pub fn register(db: &mut Client, ttl: Duration) -> Result<Watcher> {
+        let last = last_change(db)?;
+        db.execute(&WatcherInsert {
+            last_change: last,
+            expire_date: &expire_date(ttl)?,
+        })?;
+        let id = db.as_ref().last_insert_rowid();
+
+        Ok(Watcher { id, last, ttl })
+    }
+
+    /// The changes made since the watch last saw them, renewing the watch
+    /// and forgetting the changes every watch has seen.
+    pub fn changes(&mut self, db: &mut Client) -> Result<Vec<Change>> {
+        let (last, changes) = changes(db, self.last)?;
+        self.last = last;
+
+        let expire_date = expire_date(self.ttl)?;
+        let renewed = db.execute(&WatcherUpdate {
+            last_change: self.last,
+            expire_date: &expire_date,
+            id: self.id,
+        })?;
+        if renewed == 0 {
+            // the watch was taken to have stopped, so changes since may
+            // not have been recorded
+            warn!("the watch expired before it was renewed, so changes may have been missed");
+            *self = Watcher::register(db, self.ttl)?;
+        }
+        let now = Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string();
+        db.execute(&WatchersExpiredDelete(&now))?;
+        db.execute(&PropertyChangesDelete)?;
+
+        Ok(changes)
+    }
+
+    /// Removes the watch, so changes are no longer recorded for it.
+    pub fn unregister(self, db: &mut Client) -> Result<()> {
+        db.execute(&WatcherDelete(self.id))?;
+        db.execute(&PropertyChangesDelete)?;
+
+        Ok(())
+    }
+}
+
+/// Prints the triples added to and removed from a database as other
+/// connections write to it, checking every `interval`, either as `+`/`-`
+/// lines or as JSON Lines. Every value written is reported, so a value
+/// written again or moved within its property shows as removed and added.
+/// Changes are only recorded while a watch runs, and the watch is renewed
+/// on every check.
+pub fn run(db_path: &Path, interval: Duration, json: bool, mut out: impl Write) -> Result<()> {
+    // older databases do not record changes until migrated
+    let mut connection = Connection::open(db_path)?;
+    migrate::run(&mut connection)?;
+    let mut db: Client = connection.into();
+    let mut watcher = Watcher::register(&mut db, interval + GRACE)?;
+    loop {
+        thread::sleep(interval);
+        for change in watcher.changes(&mut db)? {
+            if json {
+                serde_json::to_writer(&mut out, &change)?;
+                writeln!(out)?;
+            } else {
+                let sign = match change.op {
+                    Op::Add => '+',
+                    Op::Remove => '-',
+                };
+                let Triple { e, a, v } = &change.triple;
+                writeln!(out, "{} {} {} {}", sign, e, a, v)?;
+            }
+        }
+        out.flush()?;
+    }
+}
@@ -0,0 +1,128 @@
+use anyhow::{Context, Result, bail};
+use serde_json::{Map, Value, json};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use tracing::info;
+
+use crate::import;
+
+const ENTITY_DATA_URL: &str = "https://www.wikidata.org/wiki/Special:EntityData";
+
+/// A claim value as text: the id of an item, the text of a string, the time of
+/// a date, the amount of a quantity or `latitude,longitude` of a coordinate.
+fn datavalue_text(datavalue: &Value) -> Option<String> {
+    let value = &datavalue["value"];
+    match datavalue["type"].as_str()? {
+        "string" => value.as_str().map(String::from),
+        "wikibase-entityid" => value["id"].as_str().map(String::from),
+        "time" => value["time"].as_str().map(String::from),
+        "quantity" => value["amount"]
+            .as_str()
+            .map(|amount| amount.trim_start_matches('+').to_string()),
+        "monolingualtext" => value["text"].as_str().map(String::from),
+        "globecoordinate" => Some(format!("{},{}", value["latitude"], value["longitude"])),
+        _ => None,
+    }
+}
+
+/// Simplifies a Wikidata entity, as returned by `Special:EntityData`, into a
+/// record for mappers: its `id`, the `label`, `description` and `aliases` in
+/// `language`, and `claims` as the texts of each property's values, leaving
+/// out deprecated ones.
+pub fn record(entity: &Value, language: &str) -> Value {
+    let text = |field: &str| entity[field][language]["value"].clone();
+    let aliases: Vec<Value> = entity["aliases"][language]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|alias| alias["value"].clone())
+        .collect();
+
+    let mut claims = Map::new();
+    for (property, statements) in entity["claims"].as_object().into_iter().flatten() {
+        let values: Vec<Value> = statements
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter(|statement| statement["rank"] != "deprecated")
+            .filter_map(|statement| datavalue_text(&statement["mainsnak"]["datavalue"]))
+            .map(Value::String)
+            .collect();
+        if !values.is_empty() {
+            claims.insert(property.clone(), Value::Array(values));
+        }
+    }
+
+    json!({
+        "id": entity["id"],
+        "label": text("labels"),
+        "description": text("descriptions"),
+        "aliases": aliases,
+        "claims": claims,
+    })
+}
+
+fn is_item_id(item: &str) -> bool {
+    item.len() > 1 && item.starts_with('Q') && item[1..].bytes().all(|b| b.is_ascii_digit())
+}
+
+#[tokio::main]
+async fn fetch(items: &[String]) -> Result<Vec<Value>> {
+    let client = reqwest::Client::builder()
+        .user_agent(concat!("pika/", env!("CARGO_PKG_VERSION")))
+        .build()?;
+    let mut entities = Vec::new();
+    for item in items {
+        info!("fetching {}", item);
+        let url = format!("{}/{}.json", ENTITY_DATA_URL, item);
+        let bytes = client
+            .get(&url)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .with_context(|| format!("could not fetch {}", url))?
+            .bytes()
+            .await?;
+        let data: Value =
+            serde_json::from_slice(&bytes).with_context(|| format!("could not parse {}", url))?;
+        // a redirected item is returned under the id it redirects to
+        let Some(entity) = data["entities"]
+            .as_object()
+            .and_then(|entities| entities.values().next())
+        else {
+            bail!("no entity in {}", url);
+        };
+        entities.push(entity.clone());
+    }
+
+    Ok(entities)
+}
+
+/// Fetches Wikidata items into `<data>/<dir>/<item>.json` as records, then
+/// imports the data directory with the mappings, like `pika import`.
+pub fn run(
+    db_path: &Path,
+    data_path: PathBuf,
+    mapping_path: PathBuf,
+    dir: &str,
+    items: &[String],
+    language: &str,
+    options: &import::Options,
+) -> Result<()> {
+    if let Some(item) = items.iter().find(|item| !is_item_id(item)) {
+        bail!("{} is not a Wikidata item id such as Q42", item);
+    }
+
+    let item_path = data_path.join(dir);
+    fs::create_dir_all(&item_path)
+        .with_context(|| format!("could not create {}", item_path.display()))?;
+    for (item, entity) in items.iter().zip(fetch(items)?) {
+        let path = item_path.join(format!("{}.json", item));
+        let text = serde_json::to_string_pretty(&record(&entity, language))?;
+        fs::write(&path, text).with_context(|| format!("could not write {}", path.display()))?;
+    }
+
+    import::run(db_path, data_path, mapping_path, options)
+}
@@ -0,0 +1,195 @@
+use anyhow::Result;
+use pika::chu::{self, Format};
+use std::path::PathBuf;
+
+fn pikachu() -> Result<chu::Document> {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/html/pikachu.html");
+    Ok(chu::extract_tables(&std::fs::read_to_string(path)?))
+}
+
+#[test]
+fn test_formats() -> Result<()> {
+    let csv = chu::format(pikachu()?, Format::Csv)?;
+    assert!(csv.starts_with("Game,Location\nYellow,Starter\nYellow,Viridian Forest\n"));
+    assert!(csv.contains("\n\nVersion\nCrystal\n"));
+
+    let markdown = chu::format(pikachu()?, Format::Markdown)?;
+    assert!(markdown.starts_with("# Pikachu - Pokédex\n"));
+    assert!(markdown.contains("| Game | Location |\n| --- | --- |\n| Yellow | Starter |\n"));
+    assert!(markdown.contains("- Thunder Shock\n"));
+    assert!(markdown.contains("## Evolution\n\nEvolves from Pichu.\nEvolves into Raichu.\n"));
+
+    let json: serde_json::Value = serde_json::from_str(&chu::format(pikachu()?, Format::Json)?)?;
+    assert_eq!(json["tables"][0][1]["Location"], "Viridian Forest");
+    assert_eq!(json["definitions"][0]["Type"], "Electric");
+
+    Ok(())
+}
+
+#[test]
+fn test_column_order() -> Result<()> {
+    // columns are written in the order of the table, not by name
+    let html = "<table>\
+        <tr><th>Name</th><th>Type</th><th>Ability</th></tr>\
+        <tr><td>Pikachu</td><td>Electric</td><td>Static</td></tr>\
+    </table>";
+    let csv = chu::format(chu::extract_tables(html), Format::Csv)?;
+    assert_eq!(csv, "Name,Type,Ability\nPikachu,Electric,Static\n");
+    let markdown = chu::format(chu::extract_tables(html), Format::Markdown)?;
+    assert!(markdown.contains("| Name | Type | Ability |\n| --- | --- | --- |\n| Pikachu | Electric | Static |\n"));
+    let json = chu::format(chu::extract_tables(html), Format::Json)?;
+    assert!(json.contains(r#""Name": "Pikachu",
+        "Type": "Electric",
+        "Ability": "Static""#), "{}", json);
+
+    Ok(())
+}
+
+#[test]
+fn test_selected() -> Result<()> {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/html/pikachu.html");
+    let selectors = ["title = h1", "evolution = h2 ~ p", "locations = h2 + table"]
+        .into_iter()
+        .map(chu::NamedSelector::parse)
+        .collect::<Result<Vec<_>>>()?;
+    let document = chu::extract_selected(&std::fs::read_to_string(path)?, &selectors)?;
+
+    assert_eq!(document.title.as_deref(), Some("Pikachu"));
+    assert_eq!(
+        document.fields["evolution"],
+        ["Evolves from Pichu.", "Evolves into Raichu."]
+    );
+    assert_eq!(document.tables.len(), 1);
+    assert!(document.lists.is_empty());
+    assert!(chu::NamedSelector::parse("price = [").is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_vertical_table() -> Result<()> {
+    let document = pikachu()?;
+
+    // the infobox has its headers in the first column
+    let infobox = &document.tables[2].rows;
+    assert_eq!(infobox.len(), 1);
+    assert_eq!(infobox[0]["Species"], "Mouse");
+    assert_eq!(infobox[0]["Height"], "0.4 m");
+
+    Ok(())
+}
+
+#[test]
+fn test_huge_spans() {
+    // spans are clamped as browsers do, rather than repeating a cell billions
+    // of times
+    let document = chu::extract_tables(
+        "<table>\
+            <tr><th>Name</th><th>Type</th></tr>\
+            <tr><td rowspan=\"4294967295\">Pikachu</td><td colspan=\"4294967295\">Electric</td></tr>\
+            <tr><td>Mouse</td></tr>\
+        </table>",
+    );
+    let rows = &document.tables[0].rows;
+    assert_eq!(rows.len(), 2);
+    assert_eq!(rows[0]["Type"], "Electric");
+    assert_eq!(rows[1]["Name"], "Pikachu");
+    assert_eq!(rows[1]["Type"], "Mouse");
+}
+
+#[test]
+fn test_short_row_under_span() {
+    // a row with fewer cells than columns still takes up the rowspan of the
+    // last column, which ends where it should
+    let document = chu::extract_tables(
+        "<table>\
+            <tr><th>Name</th><th>Type</th><th>Species</th></tr>\
+            <tr><td>Pikachu</td><td>Electric</td><td rowspan=\"3\">Mouse</td></tr>\
+            <tr><td>Raichu</td></tr>\
+            <tr><td>Pichu</td><td>Electric</td></tr>\
+            <tr><td>Meowth</td><td>Normal</td><td>Scratch Cat</td></tr>\
+        </table>",
+    );
+    let rows = &document.tables[0].rows;
+    assert_eq!(rows.len(), 4);
+    assert_eq!(rows[1]["Name"], "Raichu");
+    assert_eq!(rows[1]["Species"], "Mouse");
+    assert_eq!(rows[2]["Species"], "Mouse");
+    assert_eq!(rows[3]["Type"], "Normal");
+    assert_eq!(rows[3]["Species"], "Scratch Cat");
+}
+
+#[test]
+fn test_main_content() -> Result<()> {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/html/article.html");
+    let content = chu::main_content(&std::fs::read_to_string(path)?).expect("no main content");
+
+    assert!(content.starts_with("Thunderbolt\nThunderbolt is a damage-dealing Electric-type move"));
+    assert!(content.contains("staple of competitive play."));
+    assert!(!content.contains("Browse every move"));
+    assert!(!content.contains("Copyright"));
+
+    Ok(())
+}
+
+#[test]
+fn test_next_page() {
+    let page_url = "https://example.com/pokemon?page=1";
+
+    let html = r#"<html><head><link rel="next" href="?page=2"></head></html>"#;
+    assert_eq!(
+        chu::next_page(html, page_url).as_deref(),
+        Some("https://example.com/pokemon?page=2")
+    );
+
+    let html = r#"<a href="/pokemon">Pokémon</a> <a href="/pokemon/2">Next »</a>"#;
+    assert_eq!(
+        chu::next_page(html, page_url).as_deref(),
+        Some("https://example.com/pokemon/2")
+    );
+
+    // a bare arrow may lead to the last page, or separate breadcrumbs
+    let html = r#"<a href="/">Home</a> » <a href="/pokemon">Pokémon</a> <a href="/pokemon/9">»</a>"#;
+    assert_eq!(chu::next_page(html, page_url), None);
+
+    assert_eq!(chu::next_page("<p>The end</p>", page_url), None);
+}
+
+#[test]
+fn test_encoding() -> Result<()> {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/html/latin1.html");
+    let bytes = std::fs::read(path)?;
+
+    // the charset comes from the meta tag, and entities are decoded once
+    let document = chu::extract_tables(&chu::decode(&bytes, None));
+    assert_eq!(document.title.as_deref(), Some("Pokédex"));
+    assert_eq!(document.tables[0].rows[0]["Name"], "Flabébé");
+    assert_eq!(document.tables[0].rows[0]["Note"], "Café & fairy, &lt;3");
+
+    // the charset of a header wins over the page
+    let text = chu::decode("Pokédex".as_bytes(), Some("text/html; charset=\"utf-8\""));
+    assert_eq!(text, "Pokédex");
+
+    Ok(())
+}
+
+#[test]
+fn test_language() {
+    assert_eq!(
+        chu::language(
+            "Thunderbolt is a damage-dealing Electric-type move. It has a chance of \
+             paralyzing the target, and it has been a staple of competitive play \
+             since it was first introduced in the earliest games of the series."
+        ),
+        Some("eng")
+    );
+    assert_eq!(
+        chu::language(
+            "Tonnerre est une capacité de type Électrik qui inflige des dégâts. Elle \
+             peut paralyser la cible, et elle est très utilisée en combat depuis \
+             qu'elle a été introduite dans les premiers jeux de la série."
+        ),
+        Some("fra")
+    );
+    assert_eq!(chu::language("42"), None);
+}
@@ -0,0 +1,25 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+use pika::config::Config;
+
+#[test]
+fn test_config() -> Result<()> {
+    let manifest_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let config_dir = manifest_path.join("tests/config");
+
+    let config = Config::read(&config_dir.join("config.toml"))?;
+    assert_eq!(config.db, Some(config_dir.join("pika.db")));
+    assert_eq!(config.serve.port, Some(8081));
+    assert_eq!(config.serve.webhooks, vec!["https://ntfy.sh/pika-crawls"]);
+    assert_eq!(config.serve.theme, Some(config_dir.join("theme")));
+    assert_eq!(
+        config.serve.embedding.map(|embedder| embedder.model).as_deref(),
+        Some("nomic-embed-text")
+    );
+    let pipeline = &config.serve.pipelines[0];
+    assert_eq!(pipeline.format, "html");
+    assert_eq!(pipeline.mapping, config_dir.join("mapping"));
+
+    Ok(())
+}
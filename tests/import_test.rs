@@ -1,8 +1,20 @@
-use std::path::PathBuf;
+use std::{
+    path::PathBuf,
+    time::Duration,
+    sync::{Arc, Mutex},
+};
 
 use anyhow::{Context, Result};
 use aykroyd::rusqlite::Client;
-use pika::{import, init, store::entity::PropertyForEntitySchemaQuery};
+use pika::{
+    delete, import, init, input, merge, provenance, schema, stat,
+    progress::{NoProgress, Progress},
+    rdf, shell, triples, validate, watch,
+    store::{
+        audit::AuditQuery,
+        entity::{EntitiesQuery, InsertEntityStatement, PropertyForEntityQuery, PropertyForEntitySchemaInsert, PropertyForEntitySchemaDelete, PropertyForEntitySchemaQuery},
+    },
+};
 use tempdir::TempDir;
 
 #[test]
@@ -15,12 +27,13 @@ fn test_sample_data() -> Result<()> {
     let data_path = manifest_path.join("tests/data");
 
     let tempdir =
-        TempDir::new("pika-tests").with_context(|| format!("could not create tempdir"))?;
+        TempDir::new("pika-tests").context("could not create tempdir")?;
 
     let db_path = tempdir.path().join("sample_import.db");
 
     init::run(&db_path, schema_path).expect("could not init db");
-    import::run(&db_path, data_path, mapping_path).expect("could not import data");
+    import::run(&db_path, data_path, mapping_path, &import::Options::default())
+        .expect("could not import data");
 
     let mut db = Client::open(&db_path)?;
     let properties = db.query(&PropertyForEntitySchemaQuery {
@@ -35,3 +48,1221 @@ fn test_sample_data() -> Result<()> {
 
     Ok(())
 }
+
+/// Records the stages and items reported to it.
+#[derive(Default)]
+struct RecordedProgress(Mutex<Vec<(String, Option<u64>, u64)>>);
+
+impl Progress for RecordedProgress {
+    fn start(&self, stage: &str, total: Option<u64>) {
+        self.0.lock().unwrap().push((stage.to_string(), total, 0));
+    }
+
+    fn advance(&self, count: u64) {
+        if let Some(stage) = self.0.lock().unwrap().last_mut() {
+            stage.2 += count;
+        }
+    }
+
+    fn finish(&self) {}
+}
+
+#[test]
+fn test_import_progress() -> Result<()> {
+    let manifest_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+
+    let tempdir =
+        TempDir::new("pika-tests").context("could not create tempdir")?;
+
+    let db_path = tempdir.path().join("progress.db");
+    init::run(&db_path, manifest_path.join("tests/schema")).expect("could not init db");
+    let progress = Arc::new(RecordedProgress::default());
+    let options = import::Options {
+        progress: Some(progress.clone()),
+        ..Default::default()
+    };
+    import::run(
+        &db_path,
+        manifest_path.join("tests/data"),
+        manifest_path.join("tests/mapping"),
+        &options,
+    )?;
+
+    assert_eq!(
+        *progress.0.lock().unwrap(),
+        vec![("person".to_string(), Some(1), 1)]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_invalid_mapping() -> Result<()> {
+    let manifest_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let schema_path = manifest_path.join("tests/schema");
+    let mapping_path = manifest_path.join("tests/invalid_mapping");
+    let data_path = manifest_path.join("tests/data");
+
+    let tempdir =
+        TempDir::new("pika-tests").context("could not create tempdir")?;
+
+    let strict_db_path = tempdir.path().join("invalid_import_strict.db");
+    init::run(&strict_db_path, schema_path.clone()).expect("could not init db");
+    let result = import::run(
+        &strict_db_path,
+        data_path.clone(),
+        mapping_path.clone(),
+        &import::Options::default(),
+    );
+    assert!(result.is_err());
+
+    let db_path = tempdir.path().join("invalid_import_lenient.db");
+    init::run(&db_path, schema_path).expect("could not init db");
+    let options = import::Options {
+        lenient: true,
+        ..Default::default()
+    };
+    import::run(&db_path, data_path, mapping_path, &options).expect("lenient import failed");
+
+    let mut db = Client::open(&db_path)?;
+    let properties = db.query(&PropertyForEntitySchemaQuery {
+        schema: "person",
+        id: "pikachu",
+        property_schema: "thing",
+    })?;
+    assert!(properties.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn test_mapper_error() -> Result<()> {
+    let manifest_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+
+    let tempdir =
+        TempDir::new("pika-tests").context("could not create tempdir")?;
+
+    let db_path = tempdir.path().join("mapper_error.db");
+    init::run(&db_path, manifest_path.join("tests/schema")).expect("could not init db");
+    let e = import::run(
+        &db_path,
+        manifest_path.join("tests/data"),
+        manifest_path.join("tests/mapping_error"),
+        &import::Options::default(),
+    )
+    .expect_err("failing filter was imported");
+    // the error names the data file the filter failed on
+    let message = format!("{:#}", e);
+    assert!(message.contains("person/pikachu.toml"), "{}", message);
+
+    Ok(())
+}
+
+#[test]
+fn test_dry_run() -> Result<()> {
+    let manifest_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let schema_path = manifest_path.join("tests/schema");
+    let mapping_path = manifest_path.join("tests/mapping");
+    let data_path = manifest_path.join("tests/data");
+
+    let tempdir = TempDir::new("pika-tests").context("could not create tempdir")?;
+
+    let db_path = tempdir.path().join("dry_run.db");
+
+    init::run(&db_path, schema_path).expect("could not init db");
+    let options = import::Options {
+        dry_run: true,
+        ..Default::default()
+    };
+    let summary = import::execute(&db_path, data_path, mapping_path, &options)
+        .expect("dry run failed")
+        .to_string();
+    assert!(
+        summary.starts_with("Entities to create:\n  person: 1\nProperties per schema:\n  thing: 1\n"),
+        "{}",
+        summary
+    );
+    assert!(summary.ends_with("Validation errors: 0\n"), "{}", summary);
+
+    let mut db = Client::open(&db_path)?;
+    let properties = db.query(&PropertyForEntitySchemaQuery {
+        schema: "person",
+        id: "pikachu",
+        property_schema: "thing",
+    })?;
+    assert!(properties.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn test_json_data() -> Result<()> {
+    let manifest_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let schema_path = manifest_path.join("tests/schema");
+    let mapping_path = manifest_path.join("tests/mapping");
+    let data_path = manifest_path.join("tests/data_json");
+
+    let tempdir = TempDir::new("pika-tests").context("could not create tempdir")?;
+
+    let db_path = tempdir.path().join("json_import.db");
+
+    init::run(&db_path, schema_path).expect("could not init db");
+    import::run(&db_path, data_path, mapping_path, &import::Options::default())
+        .expect("could not import data");
+
+    let mut db = Client::open(&db_path)?;
+    for (id, name) in [("mew", "Mew"), ("starters-1", "Bulbasaur"), ("starters-3", "Charmander")] {
+        let properties = db.query(&PropertyForEntitySchemaQuery {
+            schema: "person",
+            id,
+            property_schema: "thing",
+        })?;
+        assert_eq!(properties.len(), 1);
+        assert_eq!(properties[0].value, name);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_csv_data() -> Result<()> {
+    let manifest_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let schema_path = manifest_path.join("tests/schema");
+    let mapping_path = manifest_path.join("tests/mapping");
+    let data_path = manifest_path.join("tests/data_csv");
+
+    let tempdir = TempDir::new("pika-tests").context("could not create tempdir")?;
+
+    let db_path = tempdir.path().join("csv_import.db");
+
+    init::run(&db_path, schema_path).expect("could not init db");
+    let options = import::Options {
+        input: input::Options {
+            id_column: Some(String::from("slug")),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    import::run(&db_path, data_path, mapping_path, &options).expect("could not import data");
+
+    let mut db = Client::open(&db_path)?;
+    for (id, name) in [("squirtle", "Squirtle"), ("psyduck", "Psyduck, the duck")] {
+        let properties = db.query(&PropertyForEntitySchemaQuery {
+            schema: "person",
+            id,
+            property_schema: "thing",
+        })?;
+        assert_eq!(properties.len(), 1);
+        assert_eq!(properties[0].value, name);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_csv_short_row() -> Result<()> {
+    let manifest_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+
+    let tempdir = TempDir::new("pika-tests").context("could not create tempdir")?;
+
+    let data_path = tempdir.path().join("data");
+    std::fs::create_dir_all(data_path.join("person"))?;
+    std::fs::write(
+        data_path.join("person/water.csv"),
+        "slug,name\nsquirtle,Squirtle\npsyduck\n",
+    )?;
+    let db_path = tempdir.path().join("csv_short_row.db");
+    init::run(&db_path, manifest_path.join("tests/schema")).expect("could not init db");
+    let options = import::Options {
+        input: input::Options {
+            id_column: Some(String::from("slug")),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let e = import::run(&db_path, data_path, manifest_path.join("tests/mapping"), &options)
+        .expect_err("short row was imported");
+    let message = format!("{:#}", e);
+    assert!(message.contains("row 2 of"), "{}", message);
+
+    Ok(())
+}
+
+#[test]
+fn test_xml_data() -> Result<()> {
+    let manifest_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let schema_path = manifest_path.join("tests/schema");
+    let mapping_path = manifest_path.join("tests/mapping");
+    let data_path = manifest_path.join("tests/data_xml");
+
+    let tempdir = TempDir::new("pika-tests").context("could not create tempdir")?;
+
+    let db_path = tempdir.path().join("xml_import.db");
+
+    init::run(&db_path, schema_path).expect("could not init db");
+    let options = import::Options {
+        input: input::Options {
+            record_element: Some(String::from("pokemon")),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    import::run(&db_path, data_path, mapping_path, &options).expect("could not import data");
+
+    let mut db = Client::open(&db_path)?;
+    for (id, name) in [("dex-1", "Pikachu"), ("dex-2", "Jigglypuff")] {
+        let properties = db.query(&PropertyForEntitySchemaQuery {
+            schema: "person",
+            id,
+            property_schema: "thing",
+        })?;
+        assert_eq!(properties.len(), 1);
+        assert_eq!(properties[0].value, name);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_html_data() -> Result<()> {
+    let manifest_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let schema_path = manifest_path.join("tests/schema");
+    let mapping_path = manifest_path.join("tests/mapping_html");
+    let data_path = manifest_path.join("tests/data_html");
+
+    let tempdir = TempDir::new("pika-tests").context("could not create tempdir")?;
+
+    let db_path = tempdir.path().join("html_import.db");
+
+    init::run(&db_path, schema_path).expect("could not init db");
+    import::run(&db_path, data_path, mapping_path, &import::Options::default())
+        .expect("could not import data");
+
+    let mut db = Client::open(&db_path)?;
+    let properties = db.query(&PropertyForEntitySchemaQuery {
+        schema: "person",
+        id: "raichu",
+        property_schema: "thing",
+    })?;
+    assert_eq!(properties.len(), 1);
+    assert_eq!(properties[0].value, "Raichu");
+
+    Ok(())
+}
+
+#[test]
+fn test_shared_defs() -> Result<()> {
+    let manifest_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let schema_path = manifest_path.join("tests/schema");
+    let mapping_path = manifest_path.join("tests/mapping_defs");
+    let data_path = manifest_path.join("tests/data");
+
+    let tempdir = TempDir::new("pika-tests").context("could not create tempdir")?;
+
+    let db_path = tempdir.path().join("defs_import.db");
+
+    init::run(&db_path, schema_path).expect("could not init db");
+    import::run(&db_path, data_path, mapping_path, &import::Options::default())
+        .expect("could not import data");
+
+    let mut db = Client::open(&db_path)?;
+    let properties = db.query(&PropertyForEntitySchemaQuery {
+        schema: "person",
+        id: "pikachu",
+        property_schema: "thing",
+    })?;
+    assert_eq!(properties.len(), 1);
+    assert_eq!(properties[0].value, "PIKACHU");
+
+    Ok(())
+}
+
+#[test]
+fn test_conditional_mapping() -> Result<()> {
+    let manifest_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let schema_path = manifest_path.join("tests/schema");
+    let mapping_path = manifest_path.join("tests/mapping_when");
+    let data_path = manifest_path.join("tests/data_mixed");
+
+    let tempdir = TempDir::new("pika-tests").context("could not create tempdir")?;
+
+    let db_path = tempdir.path().join("when_import.db");
+
+    init::run(&db_path, schema_path).expect("could not init db");
+    import::run(&db_path, data_path, mapping_path, &import::Options::default())
+        .expect("could not import data");
+
+    let mut db = Client::open(&db_path)?;
+    for (id, count) in [("ash", 1), ("pokeball", 0)] {
+        let properties = db.query(&PropertyForEntitySchemaQuery {
+            schema: "person",
+            id,
+            property_schema: "thing",
+        })?;
+        assert_eq!(properties.len(), count);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_computed_id() -> Result<()> {
+    let manifest_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let schema_path = manifest_path.join("tests/schema");
+    let mapping_path = manifest_path.join("tests/mapping_id");
+    let data_path = manifest_path.join("tests/data_mixed");
+
+    let tempdir = TempDir::new("pika-tests").context("could not create tempdir")?;
+
+    let db_path = tempdir.path().join("id_import.db");
+
+    init::run(&db_path, schema_path).expect("could not init db");
+    import::run(&db_path, data_path, mapping_path, &import::Options::default())
+        .expect("could not import data");
+
+    let mut db = Client::open(&db_path)?;
+    let properties = db.query(&PropertyForEntitySchemaQuery {
+        schema: "person",
+        id: "professor-oak",
+        property_schema: "thing",
+    })?;
+    assert_eq!(properties.len(), 1);
+    assert_eq!(properties[0].value, "Professor Oak");
+
+    Ok(())
+}
+
+#[test]
+fn test_incremental_import() -> Result<()> {
+    let manifest_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let schema_path = manifest_path.join("tests/schema");
+    let mapping_path = manifest_path.join("tests/mapping");
+
+    let tempdir = TempDir::new("pika-tests").context("could not create tempdir")?;
+    let data_path = tempdir.path().join("data");
+    std::fs::create_dir_all(data_path.join("person"))?;
+    let pikachu_path = data_path.join("person/pikachu.toml");
+    std::fs::write(&pikachu_path, "name = \"Pikachu\"\n")?;
+
+    let db_path = tempdir.path().join("incremental_import.db");
+    init::run(&db_path, schema_path).expect("could not init db");
+    import::run(&db_path, data_path.clone(), mapping_path.clone(), &import::Options::default())
+        .expect("could not import data");
+
+    let query = PropertyForEntitySchemaQuery {
+        schema: "person",
+        id: "pikachu",
+        property_schema: "thing",
+    };
+    let mut db = Client::open(&db_path)?;
+
+    // an unchanged file is skipped, so properties removed since are not restored
+    db.execute(&PropertyForEntitySchemaDelete {
+        schema: "person",
+        id: "pikachu",
+        property_schema: "thing",
+    })?;
+    import::run(&db_path, data_path.clone(), mapping_path.clone(), &import::Options::default())
+        .expect("could not re-import data");
+    assert!(db.query(&query)?.is_empty());
+
+    let options = import::Options {
+        force: true,
+        ..Default::default()
+    };
+    import::run(&db_path, data_path.clone(), mapping_path.clone(), &options)
+        .expect("could not force re-import data");
+    assert_eq!(db.query(&query)?[0].value, "Pikachu");
+
+    // a changed file replaces the properties imported from it before
+    std::fs::write(&pikachu_path, "name = \"Raichu\"\n")?;
+    import::run(&db_path, data_path, mapping_path, &import::Options::default())
+        .expect("could not import changed data");
+    let properties = db.query(&query)?;
+    assert_eq!(properties.len(), 1);
+    assert_eq!(properties[0].value, "Raichu");
+
+    Ok(())
+}
+
+#[test]
+fn test_reimport_keeps_other_values() -> Result<()> {
+    let manifest_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+
+    let tempdir = TempDir::new("pika-tests").context("could not create tempdir")?;
+    let data_path = tempdir.path().join("data");
+    std::fs::create_dir_all(data_path.join("person"))?;
+    std::fs::write(data_path.join("person/pikachu.toml"), "name = \"Pikachu\"\n")?;
+    let mapping_path = tempdir.path().join("mapping");
+    std::fs::create_dir_all(&mapping_path)?;
+    std::fs::write(mapping_path.join("person.toml"), "[properties.thing]\nname = \".name\"\n")?;
+
+    let db_path = tempdir.path().join("reimport.db");
+    init::run(&db_path, manifest_path.join("tests/schema_many")).expect("could not init db");
+    import::run(&db_path, data_path.clone(), mapping_path.clone(), &import::Options::default())
+        .expect("could not import data");
+
+    // a value the mapping does not give is kept when the file is imported again
+    let mut db = Client::open(&db_path)?;
+    db.execute(&PropertyForEntitySchemaInsert {
+        schema: "person",
+        id: "pikachu",
+        property_schema: "person",
+        name: "alias",
+        value: "Pika",
+        position: 0,
+        provenance: "edit:cli",
+    })?;
+
+    // a changed mapping imports the unchanged file again
+    std::fs::write(
+        mapping_path.join("person.toml"),
+        "[properties.thing]\nname = \".name | ascii_upcase\"\n",
+    )?;
+    import::run(&db_path, data_path, mapping_path, &import::Options::default())
+        .expect("could not import with changed mapping");
+    let name = db.query(&PropertyForEntitySchemaQuery {
+        schema: "person",
+        id: "pikachu",
+        property_schema: "thing",
+    })?;
+    assert_eq!(name[0].value, "PIKACHU");
+    let aliases = db.query(&PropertyForEntitySchemaQuery {
+        schema: "person",
+        id: "pikachu",
+        property_schema: "person",
+    })?;
+    assert_eq!(aliases.len(), 1);
+    assert_eq!(aliases[0].value, "Pika");
+
+    Ok(())
+}
+
+#[test]
+fn test_sync_import() -> Result<()> {
+    let manifest_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let schema_path = manifest_path.join("tests/schema");
+    let mapping_path = manifest_path.join("tests/mapping");
+
+    let tempdir = TempDir::new("pika-tests").context("could not create tempdir")?;
+    let data_path = tempdir.path().join("data");
+    std::fs::create_dir_all(data_path.join("person"))?;
+    std::fs::write(data_path.join("person/pikachu.toml"), "name = \"Pikachu\"\n")?;
+    std::fs::write(data_path.join("person/raichu.toml"), "name = \"Raichu\"\n")?;
+
+    let db_path = tempdir.path().join("sync_import.db");
+    init::run(&db_path, schema_path).expect("could not init db");
+    import::run(&db_path, data_path.clone(), mapping_path.clone(), &import::Options::default())
+        .expect("could not import data");
+
+    std::fs::remove_file(data_path.join("person/raichu.toml"))?;
+    let options = import::Options {
+        sync: true,
+        ..Default::default()
+    };
+    import::run(&db_path, data_path, mapping_path, &options).expect("could not sync data");
+
+    let mut db = Client::open(&db_path)?;
+    for (id, count) in [("pikachu", 1), ("raichu", 0)] {
+        let properties = db.query(&PropertyForEntitySchemaQuery {
+            schema: "person",
+            id,
+            property_schema: "thing",
+        })?;
+        assert_eq!(properties.len(), count);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_streamed_import() -> Result<()> {
+    let manifest_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let schema_path = manifest_path.join("tests/schema");
+    let mapping_path = manifest_path.join("tests/mapping");
+
+    // enough lines for more than one batch
+    let tempdir = TempDir::new("pika-tests").context("could not create tempdir")?;
+    let data_path = tempdir.path().join("data");
+    std::fs::create_dir_all(data_path.join("person"))?;
+    let lines = (1..=2500)
+        .map(|n| format!("{{\"name\": \"Pokemon {}\"}}\n", n))
+        .collect::<String>();
+    std::fs::write(data_path.join("person/dex.jsonl"), lines)?;
+
+    let db_path = tempdir.path().join("streamed_import.db");
+    init::run(&db_path, schema_path).expect("could not init db");
+    import::run(&db_path, data_path, mapping_path, &import::Options::default())
+        .expect("could not import data");
+
+    let mut db = Client::open(&db_path)?;
+    for (id, name) in [("dex-1", "Pokemon 1"), ("dex-2500", "Pokemon 2500")] {
+        let properties = db.query(&PropertyForEntitySchemaQuery {
+            schema: "person",
+            id,
+            property_schema: "thing",
+        })?;
+        assert_eq!(properties.len(), 1);
+        assert_eq!(properties[0].value, name);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_required_properties() -> Result<()> {
+    let manifest_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let schema_path = manifest_path.join("tests/schema_required");
+    let mapping_path = manifest_path.join("tests/mapping_required");
+    let data_path = manifest_path.join("tests/data_required");
+
+    let tempdir = TempDir::new("pika-tests").context("could not create tempdir")?;
+
+    let strict_db_path = tempdir.path().join("required_import_strict.db");
+    init::run(&strict_db_path, schema_path.clone()).expect("could not init db");
+    let result = import::run(
+        &strict_db_path,
+        data_path.clone(),
+        mapping_path.clone(),
+        &import::Options::default(),
+    );
+    assert!(result.is_err());
+
+    let db_path = tempdir.path().join("required_import_lenient.db");
+    init::run(&db_path, schema_path).expect("could not init db");
+    let options = import::Options {
+        lenient: true,
+        ..Default::default()
+    };
+    import::run(&db_path, data_path, mapping_path, &options).expect("lenient import failed");
+
+    let mut db = Client::open(&db_path)?;
+    for (id, count) in [("pikachu", 1), ("missingno", 0)] {
+        let properties = db.query(&PropertyForEntitySchemaQuery {
+            schema: "person",
+            id,
+            property_schema: "thing",
+        })?;
+        assert_eq!(properties.len(), count);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_enum_properties() -> Result<()> {
+    let manifest_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let schema_path = manifest_path.join("tests/schema_enum");
+    let mapping_path = manifest_path.join("tests/mapping_enum");
+    let data_path = manifest_path.join("tests/data_enum");
+
+    let tempdir = TempDir::new("pika-tests").context("could not create tempdir")?;
+
+    let strict_db_path = tempdir.path().join("enum_import_strict.db");
+    init::run(&strict_db_path, schema_path.clone()).expect("could not init db");
+    let result = import::run(
+        &strict_db_path,
+        data_path.clone(),
+        mapping_path.clone(),
+        &import::Options::default(),
+    );
+    assert!(result.is_err());
+
+    let db_path = tempdir.path().join("enum_import_lenient.db");
+    init::run(&db_path, schema_path).expect("could not init db");
+    let options = import::Options {
+        lenient: true,
+        ..Default::default()
+    };
+    import::run(&db_path, data_path, mapping_path, &options).expect("lenient import failed");
+
+    let mut db = Client::open(&db_path)?;
+    for (id, count) in [("pikachu", 1), ("dratini", 0)] {
+        let properties = db.query(&PropertyForEntitySchemaQuery {
+            schema: "person",
+            id,
+            property_schema: "person",
+        })?;
+        assert_eq!(properties.len(), count);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_many_values() -> Result<()> {
+    let manifest_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let schema_path = manifest_path.join("tests/schema_many");
+    let mapping_path = manifest_path.join("tests/mapping_many");
+    let data_path = manifest_path.join("tests/data_many");
+
+    let tempdir = TempDir::new("pika-tests").context("could not create tempdir")?;
+
+    let db_path = tempdir.path().join("many_import.db");
+    init::run(&db_path, schema_path).expect("could not init db");
+    import::run(&db_path, data_path, mapping_path, &import::Options::default())
+        .expect("could not import data");
+
+    let mut db = Client::open(&db_path)?;
+    let properties = db.query(&PropertyForEntitySchemaQuery {
+        schema: "person",
+        id: "pikachu",
+        property_schema: "person",
+    })?;
+    let values = properties
+        .iter()
+        .map(|property| property.value.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(values, ["Pika", "Sparky"]);
+
+    Ok(())
+}
+
+#[test]
+fn test_parallel_import() -> Result<()> {
+    let manifest_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+
+    let tempdir = TempDir::new("pika-tests").context("could not create tempdir")?;
+
+    let data_path = tempdir.path().join("data");
+    std::fs::create_dir_all(data_path.join("person"))?;
+    for i in 0..50 {
+        std::fs::write(
+            data_path.join(format!("person/pokemon-{}.toml", i)),
+            format!("name = \"Pokemon {}\"\n", i),
+        )?;
+    }
+
+    // the same files imported on one thread and on several give the same
+    // database
+    let mut exports = Vec::new();
+    for threads in [1, 4] {
+        let db_path = tempdir.path().join(format!("parallel-{}.db", threads));
+        init::run(&db_path, manifest_path.join("tests/schema")).expect("could not init db");
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()?
+            .install(|| {
+                import::run(
+                    &db_path,
+                    data_path.clone(),
+                    manifest_path.join("tests/mapping"),
+                    &import::Options::default(),
+                )
+            })
+            .expect("could not import data");
+        let mut exported = Vec::new();
+        triples::export(&db_path, &mut exported)?;
+        exports.push(String::from_utf8(exported)?);
+    }
+    assert_eq!(exports[0].lines().count(), 50);
+    assert_eq!(exports[0], exports[1]);
+
+    Ok(())
+}
+
+#[test]
+fn test_schema_override() -> Result<()> {
+    let manifest_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+
+    let tempdir = TempDir::new("pika-tests").context("could not create tempdir")?;
+    let data_path = tempdir.path().join("data");
+    std::fs::create_dir_all(data_path.join("person"))?;
+    std::fs::write(
+        data_path.join("person/pikachu.toml"),
+        "names = [\"Pikachu\", \"Pika\"]\n",
+    )?;
+    let mapping = |property_schema: &str, filter: &str| -> Result<PathBuf> {
+        let mapping_path = tempdir.path().join(format!("mapping_{}", property_schema));
+        std::fs::create_dir_all(&mapping_path)?;
+        std::fs::write(
+            mapping_path.join("person.toml"),
+            format!("[properties.{}]\nname = \"{}\"\n", property_schema, filter),
+        )?;
+        Ok(mapping_path)
+    };
+
+    // person takes name from thing, where it holds one value and is not
+    // required, over alias, where it holds many and is required
+    let db_path = tempdir.path().join("schema_override.db");
+    init::run(&db_path, manifest_path.join("tests/schema_override")).expect("could not init db");
+    import::run(
+        &db_path,
+        data_path.clone(),
+        mapping("thing", ".names[0]")?,
+        &import::Options::default(),
+    )
+    .expect("could not import a name without an alias");
+    let mut db = Client::open(&db_path)?;
+    let report = validate::check(&mut db)?;
+    assert_eq!(report.errors(), 0, "{:?}", report);
+
+    assert!(
+        import::run(
+            &db_path,
+            data_path,
+            mapping("alias", ".names[]")?,
+            &import::Options::default(),
+        )
+        .is_err()
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_delete() -> Result<()> {
+    let manifest_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+
+    let tempdir =
+        TempDir::new("pika-tests").context("could not create tempdir")?;
+
+    let db_path = tempdir.path().join("delete.db");
+    init::run(&db_path, manifest_path.join("tests/schema")).expect("could not init db");
+    import::run(
+        &db_path,
+        manifest_path.join("tests/data"),
+        manifest_path.join("tests/mapping"),
+        &import::Options::default(),
+    )
+    .expect("could not import data");
+
+    delete::run(&db_path, "person", "pikachu", Some("thing.name"), false)
+        .expect("could not delete property");
+    let mut db = Client::open(&db_path)?;
+    assert!(db.query(&PropertyForEntityQuery { schema: "person", id: "pikachu" })?.is_empty());
+
+    // nothing is left to delete
+    assert!(delete::run(&db_path, "person", "pikachu", Some("thing.name"), false).is_err());
+    delete::run(&db_path, "person", "pikachu", None, true).expect("could not delete entity");
+    assert!(delete::run(&db_path, "person", "pikachu", None, true).is_err());
+
+    // only the deletes that succeeded are in the audit log, latest first
+    let entries = db.query(&AuditQuery { action: Some("delete"), target: None })?;
+    let details: Vec<_> = entries.iter().map(|entry| entry.detail.as_deref()).collect();
+    assert_eq!(details, [None, Some("thing.name")]);
+
+    Ok(())
+}
+
+#[test]
+fn test_triples() -> Result<()> {
+    let manifest_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+
+    let tempdir =
+        TempDir::new("pika-tests").context("could not create tempdir")?;
+
+    let db_path = tempdir.path().join("triples.db");
+    init::run(&db_path, manifest_path.join("tests/schema_many")).expect("could not init db");
+    import::run(
+        &db_path,
+        manifest_path.join("tests/data_many"),
+        manifest_path.join("tests/mapping_many"),
+        &import::Options::default(),
+    )
+    .expect("could not import data");
+
+    let mut exported = Vec::new();
+    triples::export(&db_path, &mut exported)?;
+    assert!(String::from_utf8(exported.clone())?.lines().count() > 1);
+    let triples_path = tempdir.path().join("triples.jsonl");
+    std::fs::write(&triples_path, &exported)?;
+
+    // a fresh database gets the same values, in the same order
+    let copy_db_path = tempdir.path().join("triples_copy.db");
+    init::run(&copy_db_path, manifest_path.join("tests/schema_many")).expect("could not init db");
+    triples::import(&copy_db_path, &triples_path, &NoProgress).expect("could not import triples");
+    triples::import(&copy_db_path, &triples_path, &NoProgress).expect("could not import triples again");
+    let mut copied = Vec::new();
+    triples::export(&copy_db_path, &mut copied)?;
+    assert_eq!(String::from_utf8(copied)?, std::str::from_utf8(&exported)?);
+
+    let invalid_path = tempdir.path().join("invalid.jsonl");
+    std::fs::write(&invalid_path, r#"{"e":"person/pikachu","a":"thing.unknown","v":"x"}"#)?;
+    assert!(triples::import(&copy_db_path, &invalid_path, &NoProgress).is_err());
+    // triples are checked as imported properties are
+    for invalid in [
+        "{\"e\":\"person/pikachu\",\"a\":\"thing.name\",\"v\":\"a\"}\n{\"e\":\"person/pikachu\",\"a\":\"thing.name\",\"v\":\"b\"}",
+        r#"{"e":"thing/pikachu","a":"person.alias","v":"x"}"#,
+    ] {
+        std::fs::write(&invalid_path, invalid)?;
+        assert!(triples::import(&copy_db_path, &invalid_path, &NoProgress).is_err());
+    }
+
+    // an invalid triple after a batch has been written leaves the database
+    // unchanged
+    let mut lines: Vec<String> = (0..1500)
+        .map(|i| format!(r#"{{"e":"person/pokemon-{}","a":"thing.name","v":"Pokemon {}"}}"#, i, i))
+        .collect();
+    lines.push(r#"{"e":"person/pikachu","a":"thing.unknown","v":"x"}"#.to_string());
+    std::fs::write(&invalid_path, lines.join("\n"))?;
+    let mut db = Client::open(&copy_db_path)?;
+    let audited = db.query(&AuditQuery { action: None, target: None })?.len();
+    assert!(triples::import(&copy_db_path, &invalid_path, &NoProgress).is_err());
+    let mut unchanged = Vec::new();
+    triples::export(&copy_db_path, &mut unchanged)?;
+    assert_eq!(String::from_utf8(unchanged)?, std::str::from_utf8(&exported)?);
+    assert_eq!(db.query(&AuditQuery { action: None, target: None })?.len(), audited);
+
+    Ok(())
+}
+
+#[test]
+fn test_watch_changes() -> Result<()> {
+    let manifest_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+
+    let tempdir =
+        TempDir::new("pika-tests").context("could not create tempdir")?;
+
+    let db_path = tempdir.path().join("watch.db");
+    init::run(&db_path, manifest_path.join("tests/schema")).expect("could not init db");
+    import::run(
+        &db_path,
+        manifest_path.join("tests/data"),
+        manifest_path.join("tests/mapping"),
+        &import::Options::default(),
+    )
+    .expect("could not import data");
+
+    let mut db = Client::open(&db_path)?;
+    let mut watcher = watch::Watcher::register(&mut db, Duration::from_secs(60))?;
+    delete::run(&db_path, "person", "pikachu", Some("thing.name"), false)
+        .expect("could not delete property");
+    let changes = watcher.changes(&mut db)?;
+    assert_eq!(changes.len(), 1);
+    assert_eq!(changes[0].op, watch::Op::Remove);
+    assert_eq!(changes[0].triple.v, "Pikachu");
+
+    // writing a value again shows as a change though the values are the same
+    let mut shell = shell::Shell::open(&db_path)?;
+    shell.execute("write person/pikachu thing.name Pikachu", &mut Vec::new())?;
+    shell.execute("write person/pikachu thing.name Pikachu", &mut Vec::new())?;
+    let changes = watcher.changes(&mut db)?;
+    let ops: Vec<_> = changes.iter().map(|change| change.op).collect();
+    assert_eq!(ops, [watch::Op::Add, watch::Op::Remove, watch::Op::Add]);
+
+    // seen changes are forgotten, and none are recorded without a watch
+    watcher.unregister(&mut db)?;
+    let count_changes = |db: &Client| -> rusqlite::Result<i64> {
+        db.as_ref()
+            .query_row("SELECT COUNT(*) FROM entity_property_change", [], |row| row.get(0))
+    };
+    assert_eq!(count_changes(&db)?, 0);
+    for _ in 0..2 {
+        import::run(
+            &db_path,
+            manifest_path.join("tests/data"),
+            manifest_path.join("tests/mapping"),
+            &import::Options {
+                force: true,
+                ..Default::default()
+            },
+        )
+        .expect("could not import data");
+    }
+    assert_eq!(count_changes(&db)?, 0);
+
+    Ok(())
+}
+
+#[test]
+fn test_shell() -> Result<()> {
+    let manifest_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+
+    let tempdir =
+        TempDir::new("pika-tests").context("could not create tempdir")?;
+
+    let db_path = tempdir.path().join("shell.db");
+    init::run(&db_path, manifest_path.join("tests/schema")).expect("could not init db");
+
+    let mut shell = shell::Shell::open(&db_path)?;
+    let mut out = Vec::new();
+    shell.execute("write person/raichu thing.name Raichu", &mut out)?;
+    shell.execute("write person/raichu thing.name Raichu Alola", &mut out)?;
+    shell.execute("read person/raichu", &mut out)?;
+    shell.execute("query thing.name Raichu Alola", &mut out)?;
+    assert_eq!(String::from_utf8(out)?, "thing.name Raichu Alola\nperson/raichu\n");
+
+    assert!(shell.execute("write person/raichu thing.unknown x", &mut Vec::new()).is_err());
+    assert!(shell.execute("frobnicate", &mut Vec::new()).is_err());
+    shell.execute("delete person/raichu", &mut Vec::new())?;
+    // deleting goes through what the delete command does
+    assert!(shell.execute("delete person/raichu", &mut Vec::new()).is_err());
+    assert!(!shell.execute("quit", &mut Vec::new())?);
+
+    Ok(())
+}
+
+#[test]
+fn test_rdf() -> Result<()> {
+    let manifest_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+
+    let tempdir =
+        TempDir::new("pika-tests").context("could not create tempdir")?;
+
+    let db_path = tempdir.path().join("rdf.db");
+    init::run(&db_path, manifest_path.join("tests/schema")).expect("could not init db");
+    import::run(
+        &db_path,
+        manifest_path.join("tests/data"),
+        manifest_path.join("tests/mapping"),
+        &import::Options::default(),
+    )
+    .expect("could not import data");
+
+    let mut out = Vec::new();
+    rdf::export(&db_path, rdf::Syntax::NTriples, &rdf::Iris::default(), &mut out)?;
+    assert_eq!(
+        String::from_utf8(out)?,
+        "<urn:pika:person/pikachu> <http://www.w3.org/1999/02/22-rdf-syntax-ns#type> <urn:pika:person> .\n\
+         <urn:pika:person/pikachu> <urn:pika:thing#name> \"Pikachu\" .\n"
+    );
+
+    let iris = rdf::Iris {
+        base: "https://example.org/".to_string(),
+        prefixes: vec![
+            ("person".to_string(), "https://example.org/person/".to_string()),
+            ("thing".to_string(), "https://example.org/thing#".to_string()),
+        ],
+    };
+    let mut out = Vec::new();
+    rdf::export(&db_path, rdf::Syntax::Turtle, &iris, &mut out)?;
+    assert_eq!(
+        String::from_utf8(out)?,
+        "@prefix person: <https://example.org/person/> .\n\
+         @prefix thing: <https://example.org/thing#> .\n\
+         \n\
+         person:pikachu a <https://example.org/person> ;\n    thing:name \"Pikachu\" .\n"
+    );
+
+    // a value naming an entity is its IRI, and a local name that cannot
+    // follow a prefix is written in full
+    let mut shell = shell::Shell::open(&db_path)?;
+    shell.execute("write person/-raichu thing.name person/pikachu", &mut Vec::new())?;
+    let mut out = Vec::new();
+    rdf::export(&db_path, rdf::Syntax::Turtle, &iris, &mut out)?;
+    let turtle = String::from_utf8(out)?;
+    assert!(
+        turtle.contains("<https://example.org/person/-raichu> a <https://example.org/person> ;\n    thing:name person:pikachu .\n"),
+        "{}",
+        turtle
+    );
+    let mut out = Vec::new();
+    rdf::export(&db_path, rdf::Syntax::NTriples, &rdf::Iris::default(), &mut out)?;
+    assert!(String::from_utf8(out)?.contains("<urn:pika:thing#name> <urn:pika:person/pikachu> .\n"));
+
+    Ok(())
+}
+
+#[test]
+fn test_merge() -> Result<()> {
+    let manifest_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+
+    let tempdir =
+        TempDir::new("pika-tests").context("could not create tempdir")?;
+
+    let db_path = tempdir.path().join("merge.db");
+    init::run(&db_path, manifest_path.join("tests/schema")).expect("could not init db");
+    import::run(
+        &db_path,
+        manifest_path.join("tests/data"),
+        manifest_path.join("tests/mapping"),
+        &import::Options::default(),
+    )
+    .expect("could not import data");
+    let mut db = Client::open(&db_path)?;
+    db.execute(&InsertEntityStatement { schema_name: "person", id: "pika" })?;
+    db.execute(&PropertyForEntitySchemaInsert {
+        schema: "person",
+        id: "pika",
+        property_schema: "thing",
+        name: "name",
+        value: " pikachu ",
+        position: 0,
+        provenance: "edit:web",
+    })?;
+    db.execute(&InsertEntityStatement { schema_name: "person", id: "ash" })?;
+    db.execute(&PropertyForEntitySchemaInsert {
+        schema: "person",
+        id: "ash",
+        property_schema: "thing",
+        name: "name",
+        value: "person/pika",
+        position: 0,
+        provenance: "edit:web",
+    })?;
+
+    let duplicates = merge::duplicates(&mut db, "name")?;
+    assert_eq!(duplicates.len(), 1);
+    assert_eq!(duplicates[0].ids, ["pika", "pikachu"]);
+
+    assert!(merge::run(&db_path, "person/pikachu", "person/pikachu").is_err());
+    merge::run(&db_path, "person/pikachu", "person/pika").expect("could not merge");
+    // the winner keeps its own value of a property holding one
+    let properties = db.query(&PropertyForEntityQuery { schema: "person", id: "pikachu" })?;
+    assert_eq!(properties.len(), 1);
+    assert_eq!(properties[0].value, "Pikachu");
+    assert!(db.query(&PropertyForEntityQuery { schema: "person", id: "pika" })?.is_empty());
+    assert!(merge::duplicates(&mut db, "name")?.is_empty());
+    // references to the loser now name the winner
+    let properties = db.query(&PropertyForEntityQuery { schema: "person", id: "ash" })?;
+    assert_eq!(properties[0].value, "person/pikachu");
+    // the loser is gone
+    assert!(merge::run(&db_path, "person/pikachu", "person/pika").is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_reimport_after_merge() -> Result<()> {
+    let manifest_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+
+    let tempdir =
+        TempDir::new("pika-tests").context("could not create tempdir")?;
+
+    let db_path = tempdir.path().join("merge_reimport.db");
+    init::run(&db_path, manifest_path.join("tests/schema")).expect("could not init db");
+    let reimport = |force| {
+        import::run(
+            &db_path,
+            manifest_path.join("tests/data"),
+            manifest_path.join("tests/mapping"),
+            &import::Options {
+                force,
+                sync: true,
+                ..Default::default()
+            },
+        )
+    };
+    reimport(false).expect("could not import data");
+    // the imported entity is merged into one that was not imported
+    let mut db = Client::open(&db_path)?;
+    db.execute(&InsertEntityStatement { schema_name: "person", id: "pika" })?;
+    merge::run(&db_path, "person/pika", "person/pikachu").expect("could not merge");
+
+    // importing the merged entity's file again, changed or not, writes to the
+    // entity it was merged into and keeps it
+    for force in [true, false] {
+        reimport(force).expect("could not import data again");
+        let ids: Vec<String> = db.query(&EntitiesQuery)?.into_iter().map(|row| row.id).collect();
+        assert_eq!(ids, ["pika"]);
+        let properties = db.query(&PropertyForEntityQuery { schema: "person", id: "pika" })?;
+        assert_eq!(properties.len(), 1);
+        assert_eq!(properties[0].value, "Pikachu");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_provenance() -> Result<()> {
+    let manifest_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+
+    let tempdir =
+        TempDir::new("pika-tests").context("could not create tempdir")?;
+
+    let db_path = tempdir.path().join("provenance.db");
+    init::run(&db_path, manifest_path.join("tests/schema")).expect("could not init db");
+    import::run(
+        &db_path,
+        manifest_path.join("tests/data"),
+        manifest_path.join("tests/mapping"),
+        &import::Options::default(),
+    )
+    .expect("could not import data");
+
+    let mut out = Vec::new();
+    provenance::run(&db_path, "person/pikachu", "thing.name", &mut out)?;
+    let out = String::from_utf8(out)?;
+    assert!(out.starts_with("Pikachu\timport:person/pikachu.toml\t"), "{}", out);
+
+    // values written in the shell are recorded as edits
+    let mut shell = shell::Shell::open(&db_path)?;
+    shell.execute("write person/pikachu thing.name Raichu", &mut Vec::new())?;
+    let mut out = Vec::new();
+    provenance::run(&db_path, "person/pikachu", "thing.name", &mut out)?;
+    assert!(String::from_utf8(out)?.starts_with("Raichu\tedit:shell\t"));
+
+    assert!(provenance::run(&db_path, "person/pikachu", "thing.age", &mut Vec::new()).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_stat() -> Result<()> {
+    let manifest_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+
+    let tempdir =
+        TempDir::new("pika-tests").context("could not create tempdir")?;
+
+    let db_path = tempdir.path().join("stat.db");
+    init::run(&db_path, manifest_path.join("tests/schema")).expect("could not init db");
+    import::run(
+        &db_path,
+        manifest_path.join("tests/data"),
+        manifest_path.join("tests/mapping"),
+        &import::Options::default(),
+    )
+    .expect("could not import data");
+
+    let mut out = Vec::new();
+    stat::run(&db_path, false, schema::inspect::Format::Plain, &mut out)?;
+    assert!(String::from_utf8(out)?.starts_with("entities: 1\nvalues: 1\n"));
+
+    let mut out = Vec::new();
+    stat::run(&db_path, true, schema::inspect::Format::Plain, &mut out)?;
+    let out = String::from_utf8(out)?;
+    let line = out.lines().nth(1).context("no attributes")?;
+    // one value, Pikachu, of 7 bytes
+    assert!(line.starts_with("thing.name\t1\t1\t7.0\t"), "{}", line);
+
+    Ok(())
+}
+
+#[test]
+fn test_validate() -> Result<()> {
+    let manifest_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+
+    let tempdir =
+        TempDir::new("pika-tests").context("could not create tempdir")?;
+
+    let db_path = tempdir.path().join("validate.db");
+    init::run(&db_path, manifest_path.join("tests/schema")).expect("could not init db");
+    import::run(
+        &db_path,
+        manifest_path.join("tests/data"),
+        manifest_path.join("tests/mapping"),
+        &import::Options::default(),
+    )
+    .expect("could not import data");
+    let mut db = Client::open(&db_path)?;
+    assert_eq!(validate::check(&mut db)?.errors(), 0);
+    validate::run(&db_path, schema::inspect::Format::Json).expect("valid data failed validation");
+
+    for (id, name, value) in [("raichu", "name", "person/pichu"), ("raichu", "age", "3")] {
+        db.execute(&InsertEntityStatement { schema_name: "person", id })?;
+        db.execute(&PropertyForEntitySchemaInsert {
+            schema: "person",
+            id,
+            property_schema: "thing",
+            name,
+            value,
+            position: 0,
+            provenance: "edit:web",
+        })?;
+    }
+    let report = validate::check(&mut db)?;
+    let person = &report.schemas["person"];
+    assert_eq!(person.entities, 2);
+    let errors: Vec<_> = person.errors.iter().map(|error| error.error.as_str()).collect();
+    assert_eq!(
+        errors,
+        [
+            "unknown property age for schema thing",
+            "property name of schema thing names person/pichu, which does not exist",
+        ]
+    );
+    assert!(validate::run(&db_path, schema::inspect::Format::Plain).is_err());
+
+    Ok(())
+}
@@ -1,5 +1,12 @@
 use anyhow::{Context, Result};
-use pika::init;
+use aykroyd::rusqlite::Client;
+use pika::{
+    import, init, migrate, schema,
+    store::{
+        document::SearchDocuments, entity::PropertyForEntitySchemaQuery, schema::SchemaVersionQuery,
+    },
+    validate::Validator,
+};
 use std::path::PathBuf;
 use tempdir::TempDir;
 
@@ -11,7 +18,7 @@ fn test_sample_schema() -> Result<()> {
     schema_path.push("tests/schema");
 
     let tempdir = TempDir::new("pika-tests")
-        .with_context(|| format!("could not create tempdir"))?;
+        .context("could not create tempdir")?;
 
     let db_path = tempdir.path().join("sample_schema.db");
 
@@ -22,3 +29,168 @@ fn test_sample_schema() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_schema_apply() -> Result<()> {
+    let manifest_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+
+    let tempdir = TempDir::new("pika-tests")
+        .context("could not create tempdir")?;
+
+    let db_path = tempdir.path().join("schema_apply.db");
+    init::run(&db_path, manifest_path.join("tests/schema")).expect("could not init db");
+    assert!(init::run(&db_path, manifest_path.join("tests/schema")).is_err());
+
+    // the enum schema adds a property to person
+    schema::apply::run(&db_path, manifest_path.join("tests/schema_enum"))
+        .expect("could not apply schema");
+    schema::apply::run(&db_path, manifest_path.join("tests/schema_enum"))
+        .expect("could not apply schema again");
+
+    let mut db = Client::open(&db_path)?;
+    assert_eq!(db.query_one(&SchemaVersionQuery)?.version, Some(2));
+    let validator = Validator::load(&mut db)?;
+    assert_eq!(
+        validator.allowed("person", "kind"),
+        Some(&[String::from("electric"), String::from("fire")][..])
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_baseline_migration() -> Result<()> {
+    let manifest_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+
+    let tempdir = TempDir::new("pika-tests")
+        .context("could not create tempdir")?;
+
+    // a database with the tables of the first release is migrated when a
+    // schema is applied, keeping its values and documents
+    let db_path = tempdir.path().join("baseline.db");
+    rusqlite::Connection::open(&db_path)?
+        .execute_batch(&std::fs::read_to_string(manifest_path.join("tests/baseline.sql"))?)?;
+    schema::apply::run(&db_path, manifest_path.join("tests/schema_enum"))
+        .expect("could not migrate and apply schema");
+    schema::apply::run(&db_path, manifest_path.join("tests/schema_enum"))
+        .expect("could not apply schema again");
+
+    let connection = rusqlite::Connection::open(&db_path)?;
+    let version: usize = connection.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    assert_eq!(version, migrate::VERSION);
+
+    let mut db: Client = connection.into();
+    let query = PropertyForEntitySchemaQuery {
+        schema: "person",
+        id: "pikachu",
+        property_schema: "thing",
+    };
+    assert_eq!(db.query(&query)?[0].value, "Pikachu");
+    assert_eq!(db.query(&SearchDocuments("electric"))?.len(), 1);
+
+    import::run(
+        &db_path,
+        manifest_path.join("tests/data"),
+        manifest_path.join("tests/mapping"),
+        &import::Options {
+            force: true,
+            ..Default::default()
+        },
+    )
+    .expect("could not import into migrated database");
+    assert_eq!(db.query(&query)?.len(), 1);
+
+    Ok(())
+}
+
+#[test]
+fn test_schema_export() -> Result<()> {
+    let manifest_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+
+    let tempdir = TempDir::new("pika-tests")
+        .context("could not create tempdir")?;
+
+    let db_path = tempdir.path().join("schema_export.db");
+    init::run(&db_path, manifest_path.join("tests/schema_enum")).expect("could not init db");
+    schema::inspect::show(&db_path, "person", schema::inspect::Format::Plain).expect("could not show schema");
+    schema::inspect::show(&db_path, "person", schema::inspect::Format::Json).expect("could not show schema as JSON");
+    let export_path = tempdir.path().join("export");
+    schema::export::run(&db_path, export_path.clone()).expect("could not export schemas");
+
+    // a database created from the export exports the same files
+    let copy_db_path = tempdir.path().join("schema_export_copy.db");
+    init::run(&copy_db_path, export_path.clone()).expect("could not init db from export");
+    let copy_export_path = tempdir.path().join("export_copy");
+    schema::export::run(&copy_db_path, copy_export_path.clone()).expect("could not export copy");
+    for name in ["person.toml", "thing.toml"] {
+        assert_eq!(
+            std::fs::read_to_string(export_path.join(name))?,
+            std::fs::read_to_string(copy_export_path.join(name))?
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_schema_conflicts() -> Result<()> {
+    let manifest_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+
+    let tempdir = TempDir::new("pika-tests")
+        .context("could not create tempdir")?;
+
+    // person inherits name from thing and alias with different cardinalities
+    let db_path = tempdir.path().join("schema_conflict.db");
+    let error = init::run(&db_path, manifest_path.join("tests/schema_conflict"))
+        .expect_err("conflicting schemas should not init");
+    assert!(error.to_string().contains("conflicting definitions of property name"));
+
+    let db_path = tempdir.path().join("schema_override.db");
+    init::run(&db_path, manifest_path.join("tests/schema_override"))
+        .expect("could not init db with an override");
+    let mut db = Client::open(&db_path)?;
+    let schemas = schema::read(&mut db)?;
+    assert_eq!(
+        schemas["person"].overrides.get("name").map(String::as_str),
+        Some("thing")
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_json_schema() -> Result<()> {
+    let manifest_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+
+    let tempdir = TempDir::new("pika-tests")
+        .context("could not create tempdir")?;
+
+    let schema_path = tempdir.path().join("schema");
+    schema::json_schema::import(
+        &manifest_path.join("tests/json_schema/person.schema.json"),
+        schema_path.clone(),
+    )
+    .expect("could not import JSON Schema");
+    let db_path = tempdir.path().join("json_schema.db");
+    init::run(&db_path, schema_path).expect("could not init db from JSON Schema");
+
+    let mut db = Client::open(&db_path)?;
+    let schemas = schema::read(&mut db)?;
+    let person = &schemas["person"];
+    assert_eq!(person.extends, Some(vec![String::from("thing")]));
+    let properties = person.properties.as_ref().expect("person has properties");
+    assert!(properties["kind"].required);
+    assert_eq!(properties["aliases"].cardinality, schema::Cardinality::Many);
+    assert!(!properties.contains_key("age"));
+    assert!(schemas["thing"].properties.as_ref().expect("thing has properties")["name"].required);
+
+    // exported files convert back to the same extends
+    let export_path = tempdir.path().join("export");
+    schema::export::json_schema(&db_path, export_path.clone()).expect("could not export JSON Schema");
+    let exported: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(export_path.join("person.schema.json"))?)?;
+    let converted = schema::json_schema::from_json_schema("person", &exported)?;
+    assert_eq!(converted["person"].extends, person.extends);
+
+    Ok(())
+}
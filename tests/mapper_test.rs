@@ -0,0 +1,222 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use anyhow::Result;
+use pika::{input, mapper::Mapper, mapping_check};
+
+#[test]
+fn test_pika_functions() -> Result<()> {
+    let mapping = toml::from_str(
+        r#"
+        [properties.thing]
+        slug = ".name | slugify"
+        born = ".born | parse_date(\"%d/%m/%Y\")"
+        height = ".height | to_number"
+        types = ".types | split_list(\",\") | join(\"|\")"
+        name = ".name | trim"
+        "#,
+    )?;
+    let mapper = Mapper::new(mapping)?;
+
+    let data = jaq_json::toml::parse(
+        r#"
+        name = " Pikachu Jr. "
+        born = "27/02/1996"
+        height = "1,040"
+        types = "electric, , mouse"
+        "#,
+    )?;
+    let mut values = HashMap::new();
+    for result in mapper.run(data) {
+        let property = result?;
+        values.insert(property.name, property.value.to_string());
+    }
+
+    assert_eq!(values["slug"], "\"pikachu-jr\"");
+    assert_eq!(values["born"], "\"1996-02-27\"");
+    assert_eq!(values["height"], "1040");
+    assert_eq!(values["types"], "\"electric|mouse\"");
+    assert_eq!(values["name"], "\"Pikachu Jr.\"");
+
+    Ok(())
+}
+
+#[test]
+fn test_filter_error_position() -> Result<()> {
+    let mapping = toml::from_str(
+        r#"
+        [properties.thing]
+        name = ".name |\n  nonexistent"
+        "#,
+    )?;
+    let error = Mapper::new(mapping).err().expect("mapper should not compile");
+
+    assert_eq!(
+        error.to_string(),
+        "could not compile filter for thing.name: undefined filter nonexistent at line 2, column 3"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_html_tables() -> Result<()> {
+    let mapping = toml::from_str(
+        r#"
+        [properties.thing]
+        title = ".title"
+        type = ".tables[0][0].Type"
+        "#,
+    )?;
+    let mapper = Mapper::new(mapping)?;
+
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/data_html/person/raichu.html");
+    let contents = std::fs::read_to_string(&path)?;
+    let mut records = input::parse(&path, &contents, &input::Options::default())?;
+    let (id, data) = records.pop().expect("no record");
+    assert_eq!(id, "raichu");
+
+    let mut values = HashMap::new();
+    for result in mapper.run(data) {
+        let property = result?;
+        values.insert(property.name, property.value.to_string());
+    }
+
+    assert_eq!(values["title"], "\"Raichu - Pokédex\"");
+    assert_eq!(values["type"], "\"Electric\"");
+
+    Ok(())
+}
+
+#[test]
+fn test_css() -> Result<()> {
+    let mapping = toml::from_str(
+        r#"
+        [properties.thing]
+        title = "[.html | css(\"title\")] | first"
+        "#,
+    )?;
+    let mapper = Mapper::new(mapping)?;
+
+    // each page is queried as itself, whichever was read last
+    let manifest_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let mut pages = Vec::new();
+    for path in [
+        manifest_path.join("tests/data_html/person/raichu.html"),
+        manifest_path.join("tests/html/pikachu.html"),
+    ] {
+        let contents = std::fs::read_to_string(&path)?;
+        let (_, data) = input::parse(&path, &contents, &input::Options::default())?
+            .pop()
+            .expect("no record");
+        pages.push(data);
+    }
+    let mut titles = Vec::new();
+    for data in pages {
+        for result in mapper.run(data) {
+            titles.push(result?.value.to_string());
+        }
+    }
+    assert_eq!(titles, ["\"Raichu - Pokédex\"", "\"Pikachu - Pokédex\""]);
+
+    Ok(())
+}
+
+#[test]
+fn test_mapping_fixtures() -> Result<()> {
+    let manifest_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let mapping_path = manifest_path.join("tests/mapping");
+
+    let options = input::Options::default();
+    mapping_check::run(
+        mapping_path.clone(),
+        manifest_path.join("tests/mapping_fixtures"),
+        &options,
+    )?;
+    assert!(
+        mapping_check::run(
+            mapping_path.clone(),
+            manifest_path.join("tests/mapping_fixtures_failing"),
+            &options,
+        )
+        .is_err()
+    );
+
+    // fixtures are read with the options of an import
+    let options = input::Options {
+        id_column: Some(String::from("slug")),
+        ..Default::default()
+    };
+    mapping_check::run(mapping_path, manifest_path.join("tests/mapping_fixtures_csv"), &options)?;
+
+    Ok(())
+}
+
+#[test]
+fn test_html_lists_and_sections() -> Result<()> {
+    let mapping = toml::from_str(
+        r#"
+        [properties.thing]
+        move = ".lists[0][]"
+        abilities = ".definitions[0].Abilities"
+        evolution = ".sections[] | select(.heading == \"Evolution\") | .text"
+        "#,
+    )?;
+    let mapper = Mapper::new(mapping)?;
+
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/html/pikachu.html");
+    let contents = std::fs::read_to_string(&path)?;
+    let (_, data) = input::parse(&path, &contents, &input::Options::default())?
+        .pop()
+        .expect("no record");
+
+    let mut values: HashMap<String, Vec<String>> = HashMap::new();
+    for result in mapper.run(data) {
+        let property = result?;
+        values.entry(property.name).or_default().push(property.value.to_string());
+    }
+
+    assert_eq!(
+        values["move"],
+        ["\"Thunder Shock\"", "\"Quick Attack Priority move\""]
+    );
+    assert_eq!(values["abilities"], ["\"Static, Lightning Rod\""]);
+    assert_eq!(values["evolution"], ["\"Evolves from Pichu.\\nEvolves into Raichu.\""]);
+
+    Ok(())
+}
+
+#[test]
+fn test_html_spanning_cells() -> Result<()> {
+    let mapping = toml::from_str(
+        r#"
+        [properties.thing]
+        game = ".tables[0][] | .Game"
+        location = ".tables[0][] | .Location"
+        version = ".tables[1][0].Version"
+        "#,
+    )?;
+    let mapper = Mapper::new(mapping)?;
+
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/html/pikachu.html");
+    let contents = std::fs::read_to_string(&path)?;
+    let (_, data) = input::parse(&path, &contents, &input::Options::default())?
+        .pop()
+        .expect("no record");
+
+    let mut values: HashMap<String, Vec<String>> = HashMap::new();
+    for result in mapper.run(data) {
+        let property = result?;
+        values.entry(property.name).or_default().push(property.value.to_string());
+    }
+
+    // the rowspan repeats Yellow and the colspan fills both columns, without
+    // the text of the nested table
+    assert_eq!(values["game"], ["\"Yellow\"", "\"Yellow\"", "\"Not in Gold\""]);
+    assert_eq!(
+        values["location"],
+        ["\"Starter\"", "\"Viridian Forest\"", "\"Not in Gold\""]
+    );
+    assert_eq!(values["version"], ["\"Crystal\""]);
+
+    Ok(())
+}
@@ -0,0 +1,419 @@
+use std::{path::PathBuf, sync::Arc};
+
+use anyhow::{Context, Result};
+use axum::{extract, response::Html};
+use aykroyd::rusqlite::Client;
+use pika::{
+    delete, init, schema, shell,
+    serve::{
+        AppState, document, embedding, entity, notify, source, entity::properties_view_partial, pipeline::Pipeline,
+        search::notify_saved_searches,
+    },
+    store::{
+        document::{
+            AddDocument, LatestDocumentHash, SearchCjkDocuments, SearchDocuments, SearchDocumentsInLanguage,
+            SearchEnglishDocuments,
+        },
+        entity::{
+            EntityAliasInsert, EntityEditsQuery, InsertEntityStatement, PropertyForEntityQuery,
+            PropertyForEntitySchemaInsert,
+        },
+        search::{SavedSearchInsert, SavedSearches},
+        source::{AddSource, SourceSelectors, Sources},
+    },
+};
+use tempdir::TempDir;
+
+#[test]
+fn test_embedding_vectors() {
+    let vector = [0.5, -1.0, 2.0];
+    assert_eq!(embedding::from_blob(&embedding::to_blob(&vector)), vector);
+
+    assert!((embedding::cosine(&[1.0, 0.0], &[2.0, 0.0]) - 1.0).abs() < 1e-6);
+    assert!(embedding::cosine(&[1.0, 0.0], &[0.0, 3.0]).abs() < 1e-6);
+    assert_eq!(embedding::cosine(&[1.0, 0.0], &[1.0]), 0.0);
+    assert_eq!(embedding::cosine(&[0.0, 0.0], &[1.0, 0.0]), 0.0);
+}
+
+#[test]
+fn test_language_search() -> Result<()> {
+    let manifest_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+
+    let tempdir = TempDir::new("pika-tests").context("could not create tempdir")?;
+
+    let db_path = tempdir.path().join("language_search.db");
+    init::run(&db_path, manifest_path.join("tests/schema")).expect("could not init db");
+    let mut db = Client::open(&db_path)?;
+    db.execute(&AddSource("https://example.com", false, 1))?;
+    for (hash, content, language) in [
+        ("1", "Pikachu runs through the forests", Some("eng")),
+        ("2", "Pikachu se promène dans la forêt", Some("fra")),
+        ("3", "ピカチュウは森に住んでいる", Some("jpn")),
+    ] {
+        db.execute(&AddDocument {
+            source_id: 1,
+            hash,
+            retrieved_date: "2025-01-01",
+            etag: None,
+            title: None,
+            content,
+            structured: None,
+            language,
+        })?;
+    }
+
+    assert_eq!(db.query(&SearchDocuments("pikachu"))?.len(), 2);
+    // English is searched by word stems
+    assert_eq!(db.query(&SearchEnglishDocuments("running forest"))?.len(), 1);
+    // diacritics are ignored
+    assert_eq!(db.query(&SearchDocumentsInLanguage("foret", "fra"))?.len(), 1);
+    assert!(db.query(&SearchDocumentsInLanguage("pikachu", "deu"))?.is_empty());
+    // Japanese, written without spaces, is searched by trigrams
+    assert_eq!(db.query(&SearchCjkDocuments("ピカチュウ", "jpn"))?.len(), 1);
+
+    Ok(())
+}
+
+#[test]
+fn test_pipeline() -> Result<()> {
+    let manifest_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+
+    let tempdir = TempDir::new("pika-tests").context("could not create tempdir")?;
+
+    let db_path = tempdir.path().join("pipeline.db");
+    init::run(&db_path, manifest_path.join("tests/schema")).expect("could not init db");
+    let pipeline = Pipeline {
+        name: "pikachu".to_string(),
+        source: "https://example.com/pikachu".to_string(),
+        format: "html".to_string(),
+        data: tempdir.path().join("data"),
+        mapping: manifest_path.join("tests/mapping_html"),
+        schema: "person".to_string(),
+        dir: None,
+        lenient: false,
+    };
+    pipeline
+        .run(&db_path, 1, &["<html><body><h1 class=\"name\">Pikachu</h1></body></html>".to_string()])
+        .expect("could not run pipeline");
+    assert!(tempdir.path().join("data/person/pikachu.html").exists());
+
+    let mut db = Client::open(&db_path)?;
+    let properties = db.query(&PropertyForEntityQuery {
+        schema: "person",
+        id: "pikachu",
+    })?;
+    assert_eq!(properties.len(), 1);
+    assert_eq!(properties[0].value, "Pikachu");
+
+    // pages past the last of a shorter crawl are not imported again
+    let page = "<html><body><h1 class=\"name\">Pikachu</h1></body></html>".to_string();
+    pipeline
+        .run(&db_path, 2, &[page.clone(), page.clone(), page.clone()])
+        .expect("could not run pipeline");
+    assert!(tempdir.path().join("data/person/pikachu-3.html").exists());
+    pipeline
+        .run(&db_path, 3, &[page.clone(), page])
+        .expect("could not run pipeline");
+    assert!(tempdir.path().join("data/person/pikachu-2.html").exists());
+    assert!(!tempdir.path().join("data/person/pikachu-3.html").exists());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_saved_search() -> Result<()> {
+    let manifest_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+
+    let tempdir = TempDir::new("pika-tests").context("could not create tempdir")?;
+
+    let db_path = tempdir.path().join("saved_search.db");
+    init::run(&db_path, manifest_path.join("tests/schema")).expect("could not init db");
+    let mut db = Client::open(&db_path)?;
+    db.execute(&AddSource("https://example.com", false, 1))?;
+    let add = |db: &mut Client, hash, content| {
+        db.execute(&AddDocument {
+            source_id: 1,
+            hash,
+            retrieved_date: "2025-01-01",
+            etag: None,
+            title: None,
+            content,
+            structured: None,
+            language: None,
+        })
+    };
+    add(&mut db, "1", "Pikachu was seen before the search was saved")?;
+    db.execute(&SavedSearchInsert {
+        name: "pikachu",
+        kind: "document",
+        query: "pikachu",
+        language: None,
+        attribute: None,
+        notify: true,
+    })?;
+    assert_eq!(db.query(&SavedSearches)?[0].last_document_id, 1);
+
+    add(&mut db, "2", "Pikachu was seen again")?;
+    add(&mut db, "3", "Nothing to see here")?;
+    let state = AppState {
+        db_path: db_path.clone(),
+        webhooks: Vec::new(),
+        embedder: None,
+        pipelines: Vec::new(),
+    };
+    notify_saved_searches(&state).await?;
+    // only the new document that matches has been notified
+    assert_eq!(db.query(&SavedSearches)?[0].last_document_id, 2);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_property_display() -> Result<()> {
+    let manifest_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+
+    let tempdir = TempDir::new("pika-tests").context("could not create tempdir")?;
+
+    let db_path = tempdir.path().join("property_display.db");
+    init::run(&db_path, manifest_path.join("tests/schema_enum")).expect("could not init db");
+    let mut db = Client::open(&db_path)?;
+    db.execute(&InsertEntityStatement { schema_name: "person", id: "pikachu" })?;
+    db.execute(&PropertyForEntitySchemaInsert {
+        schema: "person",
+        id: "pikachu",
+        property_schema: "person",
+        name: "kind",
+        value: "electric",
+        position: 0,
+        provenance: "edit:cli",
+    })?;
+
+    // the label and order of a property change when the schema is applied
+    let schema_path = tempdir.path().join("schema");
+    std::fs::create_dir_all(&schema_path)?;
+    std::fs::copy(
+        manifest_path.join("tests/schema_enum/thing.toml"),
+        schema_path.join("thing.toml"),
+    )?;
+    let person = std::fs::read_to_string(manifest_path.join("tests/schema_enum/person.toml"))?
+        .replace("label = \"Type\"", "label = \"Element\"")
+        .replace("order = 1", "order = 2");
+    std::fs::write(schema_path.join("person.toml"), person)?;
+    schema::apply::run(&db_path, schema_path).expect("could not apply schema");
+
+    let schemas = schema::read(&mut db)?;
+    let kind = &schemas["person"].properties.as_ref().context("no properties")?["kind"];
+    assert_eq!(kind.label.as_deref(), Some("Element"));
+    assert_eq!(kind.order, Some(2));
+
+    let state = Arc::new(AppState {
+        db_path: db_path.clone(),
+        webhooks: Vec::new(),
+        embedder: None,
+        pipelines: Vec::new(),
+    });
+    let Html(body) = properties_view_partial(
+        extract::State(state),
+        extract::Path((String::from("person"), String::from("pikachu"), String::from("person"))),
+    )
+    .await
+    .expect("could not render properties");
+    assert!(body.contains(">Element</label>"), "{}", body);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_structured_not_found() -> Result<()> {
+    let manifest_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+
+    let tempdir = TempDir::new("pika-tests").context("could not create tempdir")?;
+
+    let db_path = tempdir.path().join("structured.db");
+    init::run(&db_path, manifest_path.join("tests/schema")).expect("could not init db");
+    let state = Arc::new(AppState {
+        db_path,
+        webhooks: Vec::new(),
+        embedder: None,
+        pipelines: Vec::new(),
+    });
+    let response = document::structured(extract::State(state), extract::Path(42))
+        .await
+        .expect("could not look up document");
+    assert_eq!(response.status(), axum::http::StatusCode::NOT_FOUND);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_add_source() -> Result<()> {
+    let manifest_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+
+    let tempdir = TempDir::new("pika-tests").context("could not create tempdir")?;
+
+    let db_path = tempdir.path().join("source.db");
+    init::run(&db_path, manifest_path.join("tests/schema")).expect("could not init db");
+    let state = Arc::new(AppState {
+        db_path: db_path.clone(),
+        webhooks: Vec::new(),
+        embedder: None,
+        pipelines: Vec::new(),
+    });
+    let form = |selectors: &str| {
+        extract::Form(
+            serde_json::from_value(serde_json::json!({
+                "url": "https://example.com",
+                "selectors": selectors,
+                "max_pages": 1,
+            }))
+            .expect("could not build form"),
+        )
+    };
+
+    let response = source::add(extract::State(state.clone()), form("title = h1\ntitle = h2"))
+        .await
+        .expect("could not add source");
+    assert_eq!(response.status(), axum::http::StatusCode::BAD_REQUEST);
+
+    // adding a source again replaces its selectors
+    for selectors in ["title = h1", "price = .price"] {
+        let response = source::add(extract::State(state.clone()), form(selectors))
+            .await
+            .expect("could not add source");
+        assert_eq!(response.status(), axum::http::StatusCode::OK);
+    }
+    let mut db = Client::open(&db_path)?;
+    let sources = db.query(&Sources)?;
+    assert_eq!(sources.len(), 1);
+    let selectors = db.query(&SourceSelectors(sources[0].id))?;
+    assert_eq!(selectors.len(), 1);
+    assert_eq!(selectors[0].name, "price");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_notify_timeout() -> Result<()> {
+    // a webhook that accepts the connection but never answers
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+    let address = listener.local_addr()?;
+    let server = tokio::spawn(async move {
+        let mut connections = Vec::new();
+        while let Ok((connection, _)) = listener.accept().await {
+            connections.push(connection);
+        }
+    });
+
+    let webhooks = vec![format!("http://{}/", address)];
+    let event = notify::Event::CrawlFinished {
+        sources: 1,
+        changed: 0,
+    };
+    let delivered = tokio::time::timeout(
+        notify::TIMEOUT * 2,
+        notify::notify(&webhooks, &event),
+    )
+    .await;
+    assert!(delivered.is_ok(), "notify waited on an unanswered webhook");
+    server.abort();
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_crawl_failed_source() -> Result<()> {
+    let manifest_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+
+    let tempdir = TempDir::new("pika-tests").context("could not create tempdir")?;
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+    let address = listener.local_addr()?;
+    let app = axum::Router::new().route(
+        "/",
+        axum::routing::get(|| async { Html("<html><title>Pikachu</title><body><p>Pikachu</p></body></html>") }),
+    );
+    let server = tokio::spawn(async move { axum::serve(listener, app).await });
+
+    let db_path = tempdir.path().join("crawl.db");
+    init::run(&db_path, manifest_path.join("tests/schema")).expect("could not init db");
+    let mut db = Client::open(&db_path)?;
+    // nothing listens on the first source, which must not stop the second
+    db.execute(&AddSource("http://127.0.0.1:1/", false, 1))?;
+    db.execute(&AddSource(&format!("http://{}/", address), false, 1))?;
+    let state = AppState {
+        db_path: db_path.clone(),
+        webhooks: Vec::new(),
+        embedder: None,
+        pipelines: Vec::new(),
+    };
+    source::crawl_stale(&state).await.expect("could not crawl");
+    server.abort();
+
+    let sources = db.query(&Sources)?;
+    assert!(db.query(&LatestDocumentHash(sources[0].id))?.is_empty());
+    assert_eq!(db.query(&LatestDocumentHash(sources[1].id))?.len(), 1);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_entity_edits() -> Result<()> {
+    let manifest_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+
+    let tempdir = TempDir::new("pika-tests").context("could not create tempdir")?;
+
+    let db_path = tempdir.path().join("edits.db");
+    init::run(&db_path, manifest_path.join("tests/schema")).expect("could not init db");
+    shell::Shell::open(&db_path)?.execute("write person/pikachu thing.name Pikachu", &mut Vec::new())?;
+    let state = Arc::new(AppState {
+        db_path: db_path.clone(),
+        webhooks: Vec::new(),
+        embedder: None,
+        pipelines: Vec::new(),
+    });
+    let path = || extract::Path(("person".to_string(), "pikachu".to_string()));
+    let name = |db: &mut Client| -> Result<String> {
+        Ok(db.query(&PropertyForEntityQuery { schema: "person", id: "pikachu" })?[0].value.clone())
+    };
+
+    let Html(saved) = entity::properties_save_partial(
+        extract::State(state.clone()),
+        extract::Path(("person".to_string(), "pikachu".to_string(), "thing".to_string())),
+        extract::Form(vec![("name".to_string(), "Raichu".to_string())]),
+    )
+    .await
+    .expect("could not save properties");
+    assert!(saved.contains("Raichu"), "{}", saved);
+    let mut db = Client::open(&db_path)?;
+    assert_eq!(name(&mut db)?, "Raichu");
+
+    // the history shows the values an edit replaced
+    let Html(history) = entity::audit(extract::State(state.clone()), path())
+        .await
+        .expect("could not show history");
+    assert!(history.contains("name: Pikachu"), "{}", history);
+
+    let _ = entity::undo(extract::State(state.clone()), path())
+        .await
+        .expect("could not undo");
+    assert_eq!(name(&mut db)?, "Pikachu");
+    let Html(history) = entity::audit(extract::State(state.clone()), path())
+        .await
+        .expect("could not show history");
+    assert!(history.contains("undone"), "{}", history);
+
+    // a deleted entity takes its edits and aliases with it
+    db.execute(&EntityAliasInsert {
+        schema: "person",
+        alias: "sparky",
+        id: "pikachu",
+    })?;
+    delete::run(&db_path, "person", "pikachu", None, true).expect("could not delete entity");
+    assert!(db.query(&EntityEditsQuery { schema: "person", id: "pikachu" })?.is_empty());
+    let aliases: i64 = db
+        .as_ref()
+        .query_row("SELECT COUNT(*) FROM entity_alias", [], |row| row.get(0))?;
+    assert_eq!(aliases, 0);
+
+    Ok(())
+}
@@ -0,0 +1,44 @@
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use aykroyd::rusqlite::Client;
+use pika::{init, source};
+use tempdir::TempDir;
+
+#[test]
+fn test_sources() -> Result<()> {
+    let manifest_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+
+    let tempdir = TempDir::new("pika-tests").context("could not create tempdir")?;
+
+    let db_path = tempdir.path().join("sources.db");
+    init::run(&db_path, manifest_path.join("tests/schema")).expect("could not init db");
+    source::import(&db_path, &manifest_path.join("tests/sources/sources.toml"))
+        .expect("could not import sources");
+    // importing again updates the sources instead of adding them twice
+    source::import(&db_path, &manifest_path.join("tests/sources/sources.toml"))
+        .expect("could not import sources again");
+
+    let sources = source::read(&mut Client::open(&db_path)?)?;
+    assert_eq!(sources.len(), 2);
+    assert_eq!(sources[0].url, "https://example.com/news?page=1&sort=new");
+    assert!(sources[0].main_content);
+    assert_eq!(sources[1].max_pages, 3);
+    assert_eq!(sources[1].selectors["price"], ".price");
+
+    for format in [source::Format::Toml, source::Format::Opml] {
+        let text = source::to_string(&sources, format)?;
+        assert_eq!(source::from_str(&text, format)?, sources);
+    }
+
+    // however many pages a source asks for, it is crawled through a bounded number
+    let mut endless = sources[1].clone();
+    endless.max_pages = 1_000_000;
+    let mut db = Client::open(&db_path)?;
+    let mut txn = db.transaction()?;
+    source::write(&mut txn, &[endless], "test")?;
+    txn.commit()?;
+    assert_eq!(source::read(&mut db)?[1].max_pages, source::MAX_PAGES);
+
+    Ok(())
+}
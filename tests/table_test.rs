@@ -0,0 +1,62 @@
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use aykroyd::rusqlite::Client;
+use pika::{
+    import, init,
+    store::entity::PropertyForEntityQuery,
+    table::{self, Column},
+};
+use tempdir::TempDir;
+
+#[test]
+fn test_table_import() -> Result<()> {
+    let manifest_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+
+    let tempdir = TempDir::new("pika-tests").context("could not create tempdir")?;
+
+    let db_path = tempdir.path().join("table.db");
+    init::run(&db_path, manifest_path.join("tests/schema")).expect("could not init db");
+    let table = table::read(
+        "pokemon.csv",
+        b"Trainer,Name,Type\nAsh,Pikachu,Electric\nMisty,Psyduck,\n",
+    )?;
+    assert_eq!(table.header, ["Trainer", "Name", "Type"]);
+    assert_eq!(table.rows.len(), 2);
+    assert!(table::read("pokemon.txt", b"").is_err());
+
+    let columns = [Column {
+        header: "Name".to_string(),
+        attribute: "thing.name".to_string(),
+    }];
+    table::import(&db_path, "pokemon.csv", &table, "person", &columns, Some("Name"), false)
+        .expect("could not import table");
+
+    let mut db = Client::open(&db_path)?;
+    let properties = db.query(&PropertyForEntityQuery {
+        schema: "person",
+        id: "psyduck",
+    })?;
+    assert_eq!(properties.len(), 1);
+    assert_eq!(properties[0].value, "Psyduck");
+    assert_eq!(properties[0].provenance, "upload:pokemon.csv");
+
+    // syncing a data directory of the schema leaves uploaded entities be
+    import::run(
+        &db_path,
+        manifest_path.join("tests/data"),
+        manifest_path.join("tests/mapping"),
+        &import::Options {
+            sync: true,
+            ..Default::default()
+        },
+    )
+    .expect("could not sync data");
+    let properties = db.query(&PropertyForEntityQuery {
+        schema: "person",
+        id: "psyduck",
+    })?;
+    assert_eq!(properties.len(), 1);
+
+    Ok(())
+}
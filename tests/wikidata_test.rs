@@ -0,0 +1,50 @@
+use std::{fs, path::PathBuf};
+
+use anyhow::{Context, Result};
+use aykroyd::rusqlite::Client;
+use pika::{import, init, store::entity::PropertyForEntityQuery, wikidata};
+use serde_json::{Value, json};
+use tempdir::TempDir;
+
+#[test]
+fn test_wikidata_record() -> Result<()> {
+    let manifest_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let entity: Value =
+        serde_json::from_str(&fs::read_to_string(manifest_path.join("tests/wikidata/Q9351.json"))?)?;
+
+    let record = wikidata::record(&entity, "en");
+    assert_eq!(
+        record,
+        json!({
+            "id": "Q9351",
+            "label": "Pikachu",
+            "description": "Pokémon species",
+            "aliases": ["Pika"],
+            "claims": {
+                "P31": ["Q3966183"],
+                "P2067": ["6"],
+            },
+        })
+    );
+
+    // records are imported like any other JSON data file
+    let tempdir = TempDir::new("pika-tests").context("could not create tempdir")?;
+    let data_path = tempdir.path().join("data");
+    fs::create_dir_all(data_path.join("person"))?;
+    fs::write(data_path.join("person/Q9351.json"), record.to_string())?;
+    let db_path = tempdir.path().join("wikidata.db");
+    init::run(&db_path, manifest_path.join("tests/schema")).expect("could not init db");
+    import::run(
+        &db_path,
+        data_path,
+        manifest_path.join("tests/mapping_wikidata"),
+        &import::Options::default(),
+    )?;
+
+    let mut db = Client::open(&db_path)?;
+    let properties = db.query(&PropertyForEntityQuery { schema: "person", id: "Q9351" })?;
+    assert_eq!(properties.len(), 1);
+    assert_eq!(properties[0].value, "Pikachu");
+
+    Ok(())
+}